@@ -6,6 +6,11 @@ fn main() {
         process::exit(0);
     }
 
+    // extensions call SQLite through the routines of the loading program
+    if env::var("CARGO_FEATURE_LOADABLE_EXTENSION").is_ok() {
+        return;
+    }
+
     println!("cargo:rustc-link-lib=dylib=min_sqlite3_sys");
 
     let home_path = env::var("HOME").expect("HOME environment variable is not set.");
@@ -0,0 +1,394 @@
+//! This module contains data-types and functions to attach and
+//! detach additional databases to the SQLite connection.
+
+#![forbid(missing_docs)]
+
+use std::ffi::{CString, NulError};
+
+use crate::{ehandle::MinSqliteWrapperError, prelude::*};
+
+/// Name of a database on the connection. Used by the functions that
+/// work on a single database of the connection.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum DatabaseName<'a> {
+    /// The database that is used to open the connection.
+    Main,
+    /// The database that holds the temporary tables.
+    Temp,
+    /// A database attached with `Attachments::attach`, by its alias.
+    Attached(&'a str),
+}
+
+impl<'a> DatabaseName<'a> {
+    /// Returns the name as SQLite knows it.
+    #[inline]
+    pub fn as_str(&self) -> &'a str {
+        match self {
+            DatabaseName::Main => "main",
+            DatabaseName::Temp => "temp",
+            DatabaseName::Attached(alias) => alias,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn to_cstring(self) -> Result<CString, NulError> {
+        CString::new(self.as_str())
+    }
+
+    /// Returns the name as quoted SQL identifier, so that it can be
+    /// placed into SQL statements safely.
+    #[inline]
+    pub(crate) fn to_sql_identifier(self) -> String {
+        quote_identifier(self.as_str())
+    }
+}
+
+impl<'a> From<&'a str> for DatabaseName<'a> {
+    fn from(name: &'a str) -> Self {
+        match name {
+            "main" => DatabaseName::Main,
+            "temp" => DatabaseName::Temp,
+            alias => DatabaseName::Attached(alias),
+        }
+    }
+}
+
+/// Database on the connection, as listed by `PRAGMA database_list`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseEntry {
+    /// Sequence number of the database.
+    pub seq: i64,
+    /// Name of the database, "main", "temp" or the alias.
+    pub name: String,
+    /// Absolute path of the database file. Empty for the temporary
+    /// and in-memory databases.
+    pub file: String,
+}
+
+/// Wraps the identifier with double quotes, doubling the quotes inside of it.
+#[inline]
+pub(crate) fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// Percent-encodes the characters that have special meaning in URIs.
+fn encode_uri_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Returns the URI parameters that apply the access and cache modes of
+/// `flags`. `READ_WRITE | CREATE` is the default of `ATTACH`, so it
+/// doesn't need a parameter.
+fn attach_parameters(flags: OpenFlags) -> Vec<(&'static str, &'static str)> {
+    let mut parameters = vec![];
+
+    if flags.contains(OpenFlags::MEMORY) {
+        parameters.push(("mode", "memory"));
+    } else if flags.contains(OpenFlags::READ_ONLY) {
+        parameters.push(("mode", "ro"));
+    } else if !flags.contains(OpenFlags::CREATE) && flags.contains(OpenFlags::READ_WRITE) {
+        parameters.push(("mode", "rw"));
+    }
+
+    if flags.contains(OpenFlags::SHARED_CACHE) {
+        parameters.push(("cache", "shared"));
+    } else if flags.contains(OpenFlags::PRIVATE_CACHE) {
+        parameters.push(("cache", "private"));
+    }
+
+    parameters
+}
+
+/// Builds the URI filename that applies `flags` to `path_or_uri`. The
+/// parameters that a URI already specifies take precedence over `flags`.
+fn attach_uri(path_or_uri: &str, flags: OpenFlags) -> String {
+    let mut parameters = attach_parameters(flags);
+
+    if path_or_uri.starts_with("file:") {
+        let (uri, fragment) = match path_or_uri.find('#') {
+            Some(i) => path_or_uri.split_at(i),
+            None => (path_or_uri, ""),
+        };
+
+        let query = uri.split_once('?').map_or("", |(_, query)| query);
+        let specified: Vec<&str> = query
+            .split('&')
+            .filter_map(|parameter| parameter.split('=').next())
+            .collect();
+        parameters.retain(|(key, _)| !specified.contains(key));
+
+        if parameters.is_empty() {
+            return path_or_uri.to_owned();
+        }
+
+        let separator = if uri.contains('?') { '&' } else { '?' };
+        return format!(
+            "{}{}{}{}",
+            uri,
+            separator,
+            join_parameters(&parameters),
+            fragment
+        );
+    }
+
+    let mut uri = format!("file:{}", encode_uri_path(path_or_uri));
+    if !parameters.is_empty() {
+        uri.push('?');
+        uri.push_str(&join_parameters(&parameters));
+    }
+    uri
+}
+
+#[inline]
+fn join_parameters(parameters: &[(&str, &str)]) -> String {
+    parameters
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Specifies the functions to attach and detach databases.
+pub trait Attachments {
+    /// Attaches the database at `path_or_uri` to the connection as `alias`.
+    /// `path_or_uri` is either a file path or a URI filename that starts with
+    /// "file:". Access mode of `flags` (`READ_ONLY`, `READ_WRITE`, `CREATE`,
+    /// `MEMORY`) and cache mode (`SHARED_CACHE`, `PRIVATE_CACHE`) are applied
+    /// as URI parameters, unless the URI already specifies them.
+    ///
+    /// # Warning
+    /// URIs and the flags other than `READ_WRITE | CREATE` need URI filenames,
+    /// so the connection must be opened with `OpenFlags::URI`. Otherwise, it
+    /// fails without attaching the database.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let flags = OpenFlags::default() | OpenFlags::URI;
+    /// let db = Database::open_with_flags(db_path, flags, None).unwrap();
+    ///
+    /// let status = db.attach("./repositories/core.db", "core", OpenFlags::READ_ONLY).unwrap();
+    ///
+    /// if status != SqlitePrimaryResult::Ok {
+    ///     ...
+    /// }
+    ///
+    /// let statement = String::from("SELECT * FROM core.packages;");
+    /// ...
+    ///
+    /// db.close();
+    /// ```
+    fn attach<'a>(
+        &self,
+        path_or_uri: &str,
+        alias: &str,
+        flags: OpenFlags,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>;
+
+    /// Detaches the database that is attached as `alias`.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// db.attach("./repositories/core.db", "core", OpenFlags::READ_ONLY).unwrap();
+    /// ...
+    /// let status = db.detach("core").unwrap();
+    ///
+    /// if status != SqlitePrimaryResult::Ok {
+    ///     ...
+    /// }
+    ///
+    /// db.close();
+    /// ```
+    fn detach<'a>(&self, alias: &str) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>;
+
+    /// Lists the databases of the connection, including "main" and "temp".
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// for database in db.database_list().unwrap() {
+    ///     println!("{} -> {}", database.name, database.file);
+    /// }
+    ///
+    /// db.close();
+    /// ```
+    fn database_list<'a>(&self) -> Result<Vec<DatabaseEntry>, MinSqliteWrapperError<'a>>;
+}
+
+impl Database {
+    /// Runs a statement that doesn't return rows, reporting the status
+    /// of the step like `execute`.
+    fn run_statement<'a>(
+        &self,
+        statement: &str,
+        parameter: Option<&str>,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>> {
+        let mut sql = self.prepare_checked(statement)?;
+
+        if let Some(parameter) = parameter {
+            let status = sql.bind_val(1, parameter);
+            if status != SqlitePrimaryResult::Ok {
+                return Ok(status);
+            }
+        }
+
+        match sql.execute_prepared() {
+            PreparedStatementStatus::Done | PreparedStatementStatus::FoundRow => {
+                Ok(SqlitePrimaryResult::Ok)
+            }
+            PreparedStatementStatus::Interrupted => Ok(SqlitePrimaryResult::Interrupt),
+            PreparedStatementStatus::Other(other_id) => Ok(SqlitePrimaryResult::from(other_id)),
+        }
+    }
+}
+
+impl Attachments for Database {
+    fn attach<'a>(
+        &self,
+        path_or_uri: &str,
+        alias: &str,
+        flags: OpenFlags,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>> {
+        let filename = if path_or_uri.starts_with("file:") || !attach_parameters(flags).is_empty() {
+            if !self.uri_filenames {
+                return Err(MinSqliteWrapperError {
+                    kind: "sqlite3:Attach",
+                    reason: String::from(
+                        "URI filenames are not enabled, open the connection with OpenFlags::URI",
+                    ),
+                });
+            }
+
+            attach_uri(path_or_uri, flags)
+        } else {
+            path_or_uri.to_owned()
+        };
+
+        self.run_statement(
+            &format!("ATTACH DATABASE ? AS {};", quote_identifier(alias)),
+            Some(&filename),
+        )
+    }
+
+    fn detach<'a>(&self, alias: &str) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>> {
+        self.run_statement(
+            &format!("DETACH DATABASE {};", quote_identifier(alias)),
+            None,
+        )
+    }
+
+    fn database_list<'a>(&self) -> Result<Vec<DatabaseEntry>, MinSqliteWrapperError<'a>> {
+        let rows = self.query_values("SELECT seq, name, file FROM pragma_database_list;")?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let mut row = row.into_iter();
+                DatabaseEntry {
+                    seq: match row.next() {
+                        Some(Value::Integer(t)) => t,
+                        _ => 0,
+                    },
+                    name: match row.next() {
+                        Some(Value::Text(t)) => t,
+                        _ => String::new(),
+                    },
+                    file: match row.next() {
+                        Some(Value::Text(t)) => t,
+                        _ => String::new(),
+                    },
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{remove_file, temp_path};
+
+    #[test]
+    fn attach_uri_applies_flags() {
+        assert_eq!(
+            attach_uri("/tmp/a b.db", OpenFlags::READ_ONLY),
+            "file:/tmp/a%20b.db?mode=ro"
+        );
+        assert_eq!(
+            attach_uri("core.db", OpenFlags::default() | OpenFlags::SHARED_CACHE),
+            "file:core.db?cache=shared"
+        );
+        assert_eq!(
+            attach_uri("file:core.db?cache=private", OpenFlags::READ_ONLY),
+            "file:core.db?cache=private&mode=ro"
+        );
+    }
+
+    #[test]
+    fn attach_uri_keeps_the_uri_parameters() {
+        assert_eq!(
+            attach_uri("file:core.db?mode=ro", OpenFlags::default()),
+            "file:core.db?mode=ro"
+        );
+        assert_eq!(
+            attach_uri("file:core.db#part", OpenFlags::READ_ONLY),
+            "file:core.db?mode=ro#part"
+        );
+    }
+
+    #[test]
+    fn databases_are_attached_and_detached() {
+        let file = temp_path("attach.db");
+        let path = file.to_str().unwrap();
+
+        let db = Database::open(":memory:").unwrap();
+        assert_eq!(
+            db.attach(path, "core \"db\"", OpenFlags::default())
+                .unwrap(),
+            SqlitePrimaryResult::Ok
+        );
+        db.execute_changes(String::from("CREATE TABLE \"core \"\"db\"\"\".t(x);"))
+            .unwrap();
+
+        let names: Vec<String> = db
+            .database_list()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, ["main", "core \"db\""]);
+        assert!(db.attach(path, "ro", OpenFlags::READ_ONLY).is_err());
+
+        assert_eq!(db.detach("core \"db\"").unwrap(), SqlitePrimaryResult::Ok);
+        assert_ne!(db.detach("core \"db\"").unwrap(), SqlitePrimaryResult::Ok);
+        db.close();
+
+        let flags = OpenFlags::default() | OpenFlags::URI;
+        let db = Database::open_with_flags(":memory:", flags, None).unwrap();
+        db.attach(path, "ro", OpenFlags::READ_ONLY).unwrap();
+        let error = db
+            .execute_changes(String::from("INSERT INTO ro.t VALUES (1);"))
+            .unwrap_err();
+        assert!(error.reason.starts_with("Readonly"), "{}", error.reason);
+
+        assert_ne!(
+            db.attach("/nonexistent/core.db", "missing", OpenFlags::READ_ONLY)
+                .unwrap(),
+            SqlitePrimaryResult::Ok
+        );
+
+        db.close();
+        remove_file(&file);
+    }
+}
@@ -0,0 +1,664 @@
+//! This module contains data-types and functions to authorize
+//! the actions of SQL statements while they are being prepared.
+
+#![forbid(missing_docs)]
+
+use std::{
+    ffi::CStr,
+    os,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use crate::{bindings::sqlite3_set_authorizer, ehandle::MinSqliteWrapperError, prelude::*};
+
+/// Boxed authorizer callback stored by the `Database`.
+pub(crate) type AuthorizerFn = Box<dyn FnMut(AuthAction) -> Authorization + Send>;
+
+/// Decision of the authorizer callback.
+#[repr(i32)]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Authorization {
+    /// Allows the action.
+    Allow = 0,
+    /// Aborts the SQL statement with `SqlitePrimaryResult::Auth` error.
+    Deny = 1,
+    /// Disallows the specific action but lets the SQL statement continue.
+    /// (e.g. column reads are replaced with NULL, deletes are skipped)
+    Ignore = 2,
+}
+
+/// Operation of the `BEGIN`, `COMMIT`, `ROLLBACK`, `SAVEPOINT` and
+/// `RELEASE` statements.
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum TransactionOperation {
+    /// Indicates an operation that is not known by the wrapper.
+    Unknown,
+    /// `BEGIN` or `SAVEPOINT`
+    Begin,
+    /// `COMMIT`
+    Commit,
+    /// `RELEASE`
+    Release,
+    /// `ROLLBACK`
+    Rollback,
+}
+
+impl From<&str> for TransactionOperation {
+    fn from(value: &str) -> Self {
+        match value {
+            "BEGIN" => Self::Begin,
+            "COMMIT" => Self::Commit,
+            "RELEASE" => Self::Release,
+            "ROLLBACK" => Self::Rollback,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// The action that is being authorized, along with its arguments.
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum AuthAction<'a> {
+    /// Indicates an action code that is not known by the wrapper.
+    Unknown {
+        /// Action code from SQLite
+        code: i32,
+        /// 3rd argument of the authorizer callback
+        arg1: Option<&'a str>,
+        /// 4th argument of the authorizer callback
+        arg2: Option<&'a str>,
+    },
+    /// SQLITE_CREATE_INDEX
+    CreateIndex {
+        /// Name of the index
+        index_name: &'a str,
+        /// Name of the indexed table
+        table_name: &'a str,
+    },
+    /// SQLITE_CREATE_TABLE
+    CreateTable {
+        /// Name of the table
+        table_name: &'a str,
+    },
+    /// SQLITE_CREATE_TEMP_INDEX
+    CreateTempIndex {
+        /// Name of the index
+        index_name: &'a str,
+        /// Name of the indexed table
+        table_name: &'a str,
+    },
+    /// SQLITE_CREATE_TEMP_TABLE
+    CreateTempTable {
+        /// Name of the table
+        table_name: &'a str,
+    },
+    /// SQLITE_CREATE_TEMP_TRIGGER
+    CreateTempTrigger {
+        /// Name of the trigger
+        trigger_name: &'a str,
+        /// Name of the table that trigger belongs to
+        table_name: &'a str,
+    },
+    /// SQLITE_CREATE_TEMP_VIEW
+    CreateTempView {
+        /// Name of the view
+        view_name: &'a str,
+    },
+    /// SQLITE_CREATE_TRIGGER
+    CreateTrigger {
+        /// Name of the trigger
+        trigger_name: &'a str,
+        /// Name of the table that trigger belongs to
+        table_name: &'a str,
+    },
+    /// SQLITE_CREATE_VIEW
+    CreateView {
+        /// Name of the view
+        view_name: &'a str,
+    },
+    /// SQLITE_DELETE
+    Delete {
+        /// Name of the table
+        table_name: &'a str,
+    },
+    /// SQLITE_DROP_INDEX
+    DropIndex {
+        /// Name of the index
+        index_name: &'a str,
+        /// Name of the indexed table
+        table_name: &'a str,
+    },
+    /// SQLITE_DROP_TABLE
+    DropTable {
+        /// Name of the table
+        table_name: &'a str,
+    },
+    /// SQLITE_DROP_TEMP_INDEX
+    DropTempIndex {
+        /// Name of the index
+        index_name: &'a str,
+        /// Name of the indexed table
+        table_name: &'a str,
+    },
+    /// SQLITE_DROP_TEMP_TABLE
+    DropTempTable {
+        /// Name of the table
+        table_name: &'a str,
+    },
+    /// SQLITE_DROP_TEMP_TRIGGER
+    DropTempTrigger {
+        /// Name of the trigger
+        trigger_name: &'a str,
+        /// Name of the table that trigger belongs to
+        table_name: &'a str,
+    },
+    /// SQLITE_DROP_TEMP_VIEW
+    DropTempView {
+        /// Name of the view
+        view_name: &'a str,
+    },
+    /// SQLITE_DROP_TRIGGER
+    DropTrigger {
+        /// Name of the trigger
+        trigger_name: &'a str,
+        /// Name of the table that trigger belongs to
+        table_name: &'a str,
+    },
+    /// SQLITE_DROP_VIEW
+    DropView {
+        /// Name of the view
+        view_name: &'a str,
+    },
+    /// SQLITE_INSERT
+    Insert {
+        /// Name of the table
+        table_name: &'a str,
+    },
+    /// SQLITE_PRAGMA
+    Pragma {
+        /// Name of the pragma
+        pragma_name: &'a str,
+        /// First argument of the pragma, if any
+        pragma_value: Option<&'a str>,
+    },
+    /// SQLITE_READ
+    Read {
+        /// Name of the table
+        table_name: &'a str,
+        /// Name of the column
+        column_name: &'a str,
+    },
+    /// SQLITE_SELECT
+    Select,
+    /// SQLITE_TRANSACTION
+    Transaction {
+        /// Transaction operation
+        operation: TransactionOperation,
+    },
+    /// SQLITE_UPDATE
+    Update {
+        /// Name of the table
+        table_name: &'a str,
+        /// Name of the column
+        column_name: &'a str,
+    },
+    /// SQLITE_ATTACH
+    Attach {
+        /// Path or URI of the database file
+        filename: &'a str,
+    },
+    /// SQLITE_DETACH
+    Detach {
+        /// Name of the attached database
+        database_name: &'a str,
+    },
+    /// SQLITE_ALTER_TABLE
+    AlterTable {
+        /// Name of the database
+        database_name: &'a str,
+        /// Name of the table
+        table_name: &'a str,
+    },
+    /// SQLITE_REINDEX
+    Reindex {
+        /// Name of the index
+        index_name: &'a str,
+    },
+    /// SQLITE_ANALYZE
+    Analyze {
+        /// Name of the table
+        table_name: &'a str,
+    },
+    /// SQLITE_CREATE_VTABLE
+    CreateVtable {
+        /// Name of the virtual table
+        table_name: &'a str,
+        /// Name of the virtual table module
+        module_name: &'a str,
+    },
+    /// SQLITE_DROP_VTABLE
+    DropVtable {
+        /// Name of the virtual table
+        table_name: &'a str,
+        /// Name of the virtual table module
+        module_name: &'a str,
+    },
+    /// SQLITE_FUNCTION
+    Function {
+        /// Name of the SQL function
+        function_name: &'a str,
+    },
+    /// SQLITE_SAVEPOINT
+    Savepoint {
+        /// Savepoint operation
+        operation: TransactionOperation,
+        /// Name of the savepoint
+        savepoint_name: &'a str,
+    },
+    /// SQLITE_RECURSIVE
+    Recursive,
+}
+
+impl<'a> AuthAction<'a> {
+    fn decode(code: i32, arg1: Option<&'a str>, arg2: Option<&'a str>) -> Self {
+        let a1 = arg1.unwrap_or_default();
+        let a2 = arg2.unwrap_or_default();
+
+        match code {
+            1 => Self::CreateIndex {
+                index_name: a1,
+                table_name: a2,
+            },
+            2 => Self::CreateTable { table_name: a1 },
+            3 => Self::CreateTempIndex {
+                index_name: a1,
+                table_name: a2,
+            },
+            4 => Self::CreateTempTable { table_name: a1 },
+            5 => Self::CreateTempTrigger {
+                trigger_name: a1,
+                table_name: a2,
+            },
+            6 => Self::CreateTempView { view_name: a1 },
+            7 => Self::CreateTrigger {
+                trigger_name: a1,
+                table_name: a2,
+            },
+            8 => Self::CreateView { view_name: a1 },
+            9 => Self::Delete { table_name: a1 },
+            10 => Self::DropIndex {
+                index_name: a1,
+                table_name: a2,
+            },
+            11 => Self::DropTable { table_name: a1 },
+            12 => Self::DropTempIndex {
+                index_name: a1,
+                table_name: a2,
+            },
+            13 => Self::DropTempTable { table_name: a1 },
+            14 => Self::DropTempTrigger {
+                trigger_name: a1,
+                table_name: a2,
+            },
+            15 => Self::DropTempView { view_name: a1 },
+            16 => Self::DropTrigger {
+                trigger_name: a1,
+                table_name: a2,
+            },
+            17 => Self::DropView { view_name: a1 },
+            18 => Self::Insert { table_name: a1 },
+            19 => Self::Pragma {
+                pragma_name: a1,
+                pragma_value: arg2,
+            },
+            20 => Self::Read {
+                table_name: a1,
+                column_name: a2,
+            },
+            21 => Self::Select,
+            22 => Self::Transaction {
+                operation: TransactionOperation::from(a1),
+            },
+            23 => Self::Update {
+                table_name: a1,
+                column_name: a2,
+            },
+            24 => Self::Attach { filename: a1 },
+            25 => Self::Detach { database_name: a1 },
+            26 => Self::AlterTable {
+                database_name: a1,
+                table_name: a2,
+            },
+            27 => Self::Reindex { index_name: a1 },
+            28 => Self::Analyze { table_name: a1 },
+            29 => Self::CreateVtable {
+                table_name: a1,
+                module_name: a2,
+            },
+            30 => Self::DropVtable {
+                table_name: a1,
+                module_name: a2,
+            },
+            31 => Self::Function { function_name: a2 },
+            32 => Self::Savepoint {
+                operation: TransactionOperation::from(a1),
+                savepoint_name: a2,
+            },
+            33 => Self::Recursive,
+            code => Self::Unknown { code, arg1, arg2 },
+        }
+    }
+}
+
+/// Ready-made authorizer policy which only allows reading data.
+///
+/// Writes, schema changes, `ATTACH`/`DETACH`, pragmas that aren't known to
+/// be read-only and SQL functions with side effects (e.g.
+/// `load_extension()`) are denied. Transactions can only be started with
+/// `BEGIN`, committed and rolled back.
+///
+/// Authorizers can't tell `BEGIN IMMEDIATE` and `BEGIN EXCLUSIVE` from a
+/// deferred `BEGIN`, so they would still take write locks. Use
+/// `AccessControl::set_read_only` to reject them as well.
+///
+/// # Usage
+/// let db_path = Path::new("./example.db");
+/// let mut db = Database::open(db_path).unwrap();
+///
+/// db.set_authorizer(Some(ReadOnlyAuthorizer::authorize));
+/// ```
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ReadOnlyAuthorizer;
+
+impl ReadOnlyAuthorizer {
+    /// Pragmas that are allowed to be queried.
+    const READ_ONLY_PRAGMAS: [&'static str; 28] = [
+        "application_id",
+        "auto_vacuum",
+        "cache_size",
+        "collation_list",
+        "compile_options",
+        "data_version",
+        "database_list",
+        "encoding",
+        "foreign_key_check",
+        "foreign_key_list",
+        "foreign_keys",
+        "freelist_count",
+        "function_list",
+        "index_info",
+        "index_list",
+        "index_xinfo",
+        "integrity_check",
+        "journal_mode",
+        "module_list",
+        "page_count",
+        "page_size",
+        "pragma_list",
+        "quick_check",
+        "schema_version",
+        "table_info",
+        "table_list",
+        "table_xinfo",
+        "user_version",
+    ];
+
+    /// Pragmas that take an argument without modifying anything.
+    const ARGUMENTED_READ_ONLY_PRAGMAS: [&'static str; 10] = [
+        "foreign_key_check",
+        "foreign_key_list",
+        "index_info",
+        "index_list",
+        "index_xinfo",
+        "integrity_check",
+        "quick_check",
+        "table_info",
+        "table_list",
+        "table_xinfo",
+    ];
+
+    /// SQL functions that have side effects outside of the database.
+    const DENIED_FUNCTIONS: [&'static str; 5] = [
+        "edit",
+        "fts3_tokenizer",
+        "load_extension",
+        "readfile",
+        "writefile",
+    ];
+
+    /// Authorizes the action according to read-only policy.
+    pub fn authorize(action: AuthAction) -> Authorization {
+        match action {
+            AuthAction::Select
+            | AuthAction::Read { .. }
+            | AuthAction::Recursive
+            | AuthAction::Savepoint { .. } => Authorization::Allow,
+            AuthAction::Transaction {
+                operation:
+                    TransactionOperation::Begin
+                    | TransactionOperation::Commit
+                    | TransactionOperation::Rollback,
+            } => Authorization::Allow,
+            AuthAction::Function { function_name } => {
+                let name = function_name.to_ascii_lowercase();
+                if Self::DENIED_FUNCTIONS.contains(&name.as_str()) {
+                    Authorization::Deny
+                } else {
+                    Authorization::Allow
+                }
+            }
+            AuthAction::Pragma {
+                pragma_name,
+                pragma_value,
+            } => {
+                let name = pragma_name.to_ascii_lowercase();
+                let allowed = match pragma_value {
+                    None => Self::READ_ONLY_PRAGMAS.contains(&name.as_str()),
+                    Some(_) => Self::ARGUMENTED_READ_ONLY_PRAGMAS.contains(&name.as_str()),
+                };
+
+                if allowed {
+                    Authorization::Allow
+                } else {
+                    Authorization::Deny
+                }
+            }
+            _ => Authorization::Deny,
+        }
+    }
+}
+
+/// Specifies the access control functions of the SQLite connection.
+pub trait AccessControl {
+    /// Registers an authorizer callback which is invoked while SQL statements
+    /// are being prepared, once for each action that the statement performs.
+    /// Denied actions make the preparation fail with `SqlitePrimaryResult::Auth`.
+    /// Passing `None` removes the registered authorizer.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let mut db = Database::open(db_path).unwrap();
+    ///
+    /// db.set_authorizer(Some(|action: AuthAction| match action {
+    ///     AuthAction::Attach { .. } => Authorization::Deny,
+    ///     _ => Authorization::Allow,
+    /// }));
+    ///
+    /// db.close();
+    /// ```
+    fn set_authorizer<F>(&mut self, authorizer: Option<F>) -> SqlitePrimaryResult
+    where
+        F: FnMut(AuthAction) -> Authorization + Send + 'static;
+
+    /// Registers `ReadOnlyAuthorizer` and enables `PRAGMA query_only`, so
+    /// that `BEGIN IMMEDIATE` and `BEGIN EXCLUSIVE` fail with
+    /// `SqlitePrimaryResult::Readonly` instead of taking write locks.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let mut db = Database::open(db_path).unwrap();
+    ///
+    /// db.set_read_only().unwrap();
+    ///
+    /// db.close();
+    /// ```
+    fn set_read_only<'a>(&mut self) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>;
+}
+
+impl AccessControl for Database {
+    fn set_authorizer<F>(&mut self, authorizer: Option<F>) -> SqlitePrimaryResult
+    where
+        F: FnMut(AuthAction) -> Authorization + Send + 'static,
+    {
+        match authorizer {
+            Some(authorizer) => {
+                let mut boxed: Box<AuthorizerFn> = Box::new(Box::new(authorizer));
+                let status = unsafe {
+                    SqlitePrimaryResult::from(sqlite3_set_authorizer(
+                        self.rp,
+                        Some(authorizer_trampoline),
+                        &mut *boxed as *mut AuthorizerFn as *mut os::raw::c_void,
+                    ))
+                };
+
+                if status == SqlitePrimaryResult::Ok {
+                    self.authorizer = Some(boxed);
+                }
+
+                status
+            }
+            None => {
+                let status = unsafe {
+                    SqlitePrimaryResult::from(sqlite3_set_authorizer(
+                        self.rp,
+                        None,
+                        ptr::null_mut(),
+                    ))
+                };
+                self.authorizer = None;
+
+                status
+            }
+        }
+    }
+
+    fn set_read_only<'a>(&mut self) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>> {
+        // the authorizer denies changing query_only, so it's enabled first
+        let status = self.pragma_update(None, "query_only", Value::Integer(1))?;
+        if status != SqlitePrimaryResult::Ok {
+            return Ok(status);
+        }
+
+        Ok(self.set_authorizer(Some(ReadOnlyAuthorizer::authorize)))
+    }
+}
+
+#[inline]
+unsafe fn optional_str<'a>(value: *const os::raw::c_char) -> Option<&'a str> {
+    if value.is_null() {
+        return None;
+    }
+
+    CStr::from_ptr(value).to_str().ok()
+}
+
+unsafe extern "C" fn authorizer_trampoline(
+    ctx: *mut os::raw::c_void,
+    code: os::raw::c_int,
+    arg1: *const os::raw::c_char,
+    arg2: *const os::raw::c_char,
+    _db_name: *const os::raw::c_char,
+    _accessor: *const os::raw::c_char,
+) -> os::raw::c_int {
+    let authorizer = &mut *(ctx as *mut AuthorizerFn);
+    let action = AuthAction::decode(code, optional_str(arg1), optional_str(arg2));
+
+    // unwinding into C is undefined behavior, deny the action instead
+    panic::catch_unwind(AssertUnwindSafe(|| authorizer(action))).unwrap_or(Authorization::Deny)
+        as os::raw::c_int
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{prelude::*, test_support::open_memory};
+
+    fn open() -> Database {
+        open_memory("CREATE TABLE t(x); INSERT INTO t VALUES (1);")
+    }
+
+    #[test]
+    fn authorizer_sees_and_denies_actions() {
+        let mut db = open();
+        let tables = Arc::new(Mutex::new(Vec::new()));
+
+        let seen = tables.clone();
+        db.set_authorizer(Some(move |action: AuthAction| match action {
+            AuthAction::Read { table_name, .. } => {
+                seen.lock().unwrap().push(table_name.to_owned());
+                Authorization::Allow
+            }
+            AuthAction::Delete { .. } => Authorization::Deny,
+            _ => Authorization::Allow,
+        }));
+
+        assert_eq!(
+            db.execute_changes(String::from("SELECT x FROM t;"))
+                .unwrap(),
+            0
+        );
+        assert_eq!(*tables.lock().unwrap(), vec![String::from("t")]);
+        assert!(db.execute_changes(String::from("DELETE FROM t;")).is_err());
+
+        db.set_authorizer(None::<fn(AuthAction) -> Authorization>);
+        assert_eq!(
+            db.execute_changes(String::from("DELETE FROM t;")).unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn read_only_authorizer_denies_writes_and_side_effects() {
+        let mut db = open();
+        db.set_authorizer(Some(ReadOnlyAuthorizer::authorize));
+
+        assert_eq!(
+            db.execute_changes(String::from("SELECT x FROM t;"))
+                .unwrap(),
+            0
+        );
+        assert!(db.execute_changes(String::from("BEGIN;")).is_ok());
+        assert!(db.execute_changes(String::from("COMMIT;")).is_ok());
+
+        for statement in [
+            "INSERT INTO t VALUES (2);",
+            "CREATE TABLE u(x);",
+            "ATTACH ':memory:' AS other;",
+            "SELECT load_extension('libnothing.so');",
+            "PRAGMA query_only = 0;",
+        ] {
+            assert!(
+                db.execute_changes(String::from(statement)).is_err(),
+                "{} is allowed",
+                statement
+            );
+        }
+    }
+
+    #[test]
+    fn set_read_only_rejects_write_transactions() {
+        let mut db = open();
+        db.set_read_only().unwrap();
+
+        assert!(db.execute_changes(String::from("BEGIN;")).is_ok());
+        assert!(db.execute_changes(String::from("ROLLBACK;")).is_ok());
+
+        let error = db
+            .execute_changes(String::from("BEGIN IMMEDIATE;"))
+            .unwrap_err();
+        assert!(error.reason.starts_with("Readonly"), "{}", error.reason);
+    }
+}
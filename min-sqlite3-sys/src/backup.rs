@@ -0,0 +1,404 @@
+//! This module contains data-types and functions to copy the
+//! content of the databases while they are in use.
+
+#![forbid(missing_docs)]
+
+use std::{marker::PhantomData, os, path::Path, thread, time::Duration};
+
+use crate::{
+    bindings::{
+        sqlite3_backup, sqlite3_backup_finish, sqlite3_backup_init, sqlite3_backup_pagecount,
+        sqlite3_backup_remaining, sqlite3_backup_step,
+    },
+    ehandle::MinSqliteWrapperError,
+    prelude::*,
+};
+
+/// Waiting time before retrying the steps that fail with
+/// `BackupStatus::Busy` or `BackupStatus::Locked`, if no
+/// pause is specified.
+const DEFAULT_BUSY_PAUSE: Duration = Duration::from_millis(100);
+
+/// Number of consecutive `BackupStatus::Busy` or `BackupStatus::Locked`
+/// steps after which `run_to_completion` gives up.
+const MAX_BUSY_RETRIES: u32 = 100;
+
+/// This enumeration is the list of the possible status outcomes for the
+/// `step(&mut self, pages)` function.
+#[non_exhaustive]
+#[repr(i8)]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum BackupStatus {
+    /// Indicates the actual error type id from SQLITE as an inner value.
+    Other(i32) = -1,
+    /// Indicates that the pages are copied and there are more to copy.
+    More,
+    /// Indicates that all of the pages are copied.
+    Done,
+    /// Indicates that the source database is locked by another connection
+    /// and the step can be retried later.
+    Busy,
+    /// Indicates that the source database is locked by the same connection
+    /// and the step can be retried later.
+    Locked,
+}
+
+/// Progress of the backup operation.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct BackupProgress {
+    /// Number of pages that still need to be copied.
+    pub remaining: u32,
+    /// Total number of pages in the source database.
+    pub page_count: u32,
+}
+
+/// Binded instance of the sqlite3_backup. Copies a database of the source
+/// connection into a database of the destination connection.
+pub struct Backup<'a> {
+    bp: *mut sqlite3_backup,
+    _marker: PhantomData<(&'a Database, &'a mut Database)>,
+}
+
+impl<'a> Drop for Backup<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            sqlite3_backup_finish(self.bp);
+        }
+    }
+}
+
+impl<'a> Backup<'a> {
+    /// Initializes the backup that copies the `source_name` database of
+    /// `source` into `dest_name` database of `dest`.
+    ///
+    /// # Usage
+    /// let source = Database::open(Path::new("./example.db")).unwrap();
+    /// let mut dest = Database::open(Path::new("./example_copy.db")).unwrap();
+    ///
+    /// let mut backup = Backup::new(&source, DatabaseName::Main, &mut dest, DatabaseName::Main).unwrap();
+    /// while let BackupStatus::More = backup.step(5) {}
+    /// ```
+    pub fn new<'e>(
+        source: &'a Database,
+        source_name: DatabaseName,
+        dest: &'a mut Database,
+        dest_name: DatabaseName,
+    ) -> Result<Self, MinSqliteWrapperError<'e>> {
+        let source_name = source_name.to_cstring()?;
+        let dest_name = dest_name.to_cstring()?;
+
+        let bp = unsafe {
+            sqlite3_backup_init(dest.rp, dest_name.as_ptr(), source.rp, source_name.as_ptr())
+        };
+
+        if bp.is_null() {
+            return Err(MinSqliteWrapperError::from_connection(dest.rp));
+        }
+
+        Ok(Backup {
+            bp,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Copies up to `pages` pages from source to the destination. If `pages`
+    /// is negative, all the remaining pages are copied.
+    #[inline]
+    pub fn step(&mut self, pages: i32) -> BackupStatus {
+        match unsafe { sqlite3_backup_step(self.bp, pages as os::raw::c_int) } {
+            0 => BackupStatus::More,
+            101 => BackupStatus::Done,
+            5 => BackupStatus::Busy,
+            6 => BackupStatus::Locked,
+            other_id => BackupStatus::Other(other_id),
+        }
+    }
+
+    /// Returns the number of pages still to be copied as of the most
+    /// recent `step` call.
+    #[inline]
+    pub fn remaining(&self) -> u32 {
+        unsafe { sqlite3_backup_remaining(self.bp) as u32 }
+    }
+
+    /// Returns the total number of pages in the source database as of
+    /// the most recent `step` call.
+    #[inline]
+    pub fn page_count(&self) -> u32 {
+        unsafe { sqlite3_backup_pagecount(self.bp) as u32 }
+    }
+
+    /// Returns the progress as of the most recent `step` call.
+    #[inline]
+    pub fn progress(&self) -> BackupProgress {
+        BackupProgress {
+            remaining: self.remaining(),
+            page_count: self.page_count(),
+        }
+    }
+
+    /// Runs `step` until the backup is done or fails, copying `pages_per_step`
+    /// pages per step and waiting `pause` between the steps so that the other
+    /// connections can use the source database meanwhile. `progress` is invoked
+    /// after each successful step.
+    ///
+    /// Returns `SqlitePrimaryResult::Ok` when all of the pages are copied,
+    /// `SqlitePrimaryResult::Busy` or `SqlitePrimaryResult::Locked` if the
+    /// source database stays locked for 100 consecutive retries, and
+    /// `SqlitePrimaryResult::Misuse` if `pages_per_step` is zero, since no
+    /// progress could be made.
+    ///
+    /// # Usage
+    /// let source = Database::open(Path::new("./example.db")).unwrap();
+    /// let mut dest = Database::open(Path::new("./example_copy.db")).unwrap();
+    ///
+    /// let mut backup = Backup::new(&source, DatabaseName::Main, &mut dest, DatabaseName::Main).unwrap();
+    /// let status = backup.run_to_completion(
+    ///     100,
+    ///     Some(Duration::from_millis(10)),
+    ///     Some(|p: BackupProgress| println!("{}/{}", p.page_count - p.remaining, p.page_count)),
+    /// );
+    ///
+    /// if status != SqlitePrimaryResult::Ok {
+    ///     ...
+    /// }
+    /// ```
+    pub fn run_to_completion<F>(
+        &mut self,
+        pages_per_step: i32,
+        pause: Option<Duration>,
+        mut progress: Option<F>,
+    ) -> SqlitePrimaryResult
+    where
+        F: FnMut(BackupProgress),
+    {
+        if pages_per_step == 0 {
+            return SqlitePrimaryResult::Misuse;
+        }
+
+        let mut busy_retries = 0;
+        loop {
+            let status = self.step(pages_per_step);
+            if !matches!(status, BackupStatus::Busy | BackupStatus::Locked) {
+                busy_retries = 0;
+            }
+
+            match status {
+                BackupStatus::Done => {
+                    if let Some(func) = progress.as_mut() {
+                        func(self.progress());
+                    }
+
+                    return SqlitePrimaryResult::Ok;
+                }
+                BackupStatus::More => {
+                    if let Some(func) = progress.as_mut() {
+                        func(self.progress());
+                    }
+
+                    if let Some(pause) = pause {
+                        thread::sleep(pause);
+                    }
+                }
+                BackupStatus::Busy | BackupStatus::Locked => {
+                    busy_retries += 1;
+                    if busy_retries >= MAX_BUSY_RETRIES {
+                        return match status {
+                            BackupStatus::Busy => SqlitePrimaryResult::Busy,
+                            _ => SqlitePrimaryResult::Locked,
+                        };
+                    }
+
+                    thread::sleep(pause.unwrap_or(DEFAULT_BUSY_PAUSE));
+                }
+                BackupStatus::Other(other_id) => return SqlitePrimaryResult::from(other_id),
+            }
+        }
+    }
+
+    /// Finishes the backup and releases its resources. Returns the error
+    /// of the backup if any of the steps failed.
+    #[inline]
+    pub fn finish(self) -> SqlitePrimaryResult {
+        let status = unsafe { SqlitePrimaryResult::from(sqlite3_backup_finish(self.bp)) };
+        std::mem::forget(self);
+        status
+    }
+}
+
+/// Specifies the backup functions of the SQLite connection.
+pub trait BackupOperations {
+    /// Copies the `name` database of the connection into the database file
+    /// at `path`, while the connection stays
+    /// usable. See `Backup::run_to_completion` for the rest of the arguments.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let status = db.backup_to_file(
+    ///     DatabaseName::Main,
+    ///     Path::new("./snapshot.db"),
+    ///     -1,
+    ///     None,
+    ///     None::<fn(BackupProgress)>,
+    /// ).unwrap();
+    ///
+    /// if status != SqlitePrimaryResult::Ok {
+    ///     ...
+    /// }
+    ///
+    /// db.close();
+    /// ```
+    fn backup_to_file<'a, P, F>(
+        &self,
+        name: DatabaseName,
+        path: P,
+        pages_per_step: i32,
+        pause: Option<Duration>,
+        progress: Option<F>,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>
+    where
+        P: AsRef<Path>,
+        F: FnMut(BackupProgress);
+}
+
+impl BackupOperations for Database {
+    fn backup_to_file<'a, P, F>(
+        &self,
+        name: DatabaseName,
+        path: P,
+        pages_per_step: i32,
+        pause: Option<Duration>,
+        progress: Option<F>,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>
+    where
+        P: AsRef<Path>,
+        F: FnMut(BackupProgress),
+    {
+        let mut dest = Database::open(path)?;
+        let mut backup = Backup::new(self, name, &mut dest, DatabaseName::Main)?;
+
+        let status = backup.run_to_completion(pages_per_step, pause, progress);
+        let finish_status = backup.finish();
+
+        if status != SqlitePrimaryResult::Ok {
+            return Ok(status);
+        }
+
+        Ok(finish_status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::test_support::{open_memory, remove_file, temp_path};
+
+    fn source() -> Database {
+        open_memory(
+            "CREATE TABLE t(x);
+            INSERT INTO t WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c LIMIT 1000) SELECT x FROM c;",
+        )
+    }
+
+    fn count(db: &Database) -> i64 {
+        let mut sql = db.prepare_checked("SELECT count(*) FROM t;").unwrap();
+        sql.execute_prepared();
+        sql.get_owned_data::<i64>(0).unwrap()
+    }
+
+    #[test]
+    fn run_to_completion_copies_all_pages() {
+        let source = source();
+        let mut dest = Database::open(":memory:").unwrap();
+        let mut reports = Vec::new();
+
+        let mut backup =
+            Backup::new(&source, DatabaseName::Main, &mut dest, DatabaseName::Main).unwrap();
+        let status = backup.run_to_completion(
+            1,
+            None,
+            Some(|progress: BackupProgress| reports.push(progress.remaining)),
+        );
+        assert_eq!(status, SqlitePrimaryResult::Ok);
+        assert_eq!(backup.finish(), SqlitePrimaryResult::Ok);
+
+        assert!(reports.len() > 1);
+        assert_eq!(reports.last(), Some(&0));
+        assert_eq!(count(&dest), 1000);
+    }
+
+    #[test]
+    fn run_to_completion_rejects_zero_pages() {
+        let source = source();
+        let mut dest = Database::open(":memory:").unwrap();
+
+        let mut backup =
+            Backup::new(&source, DatabaseName::Main, &mut dest, DatabaseName::Main).unwrap();
+        assert_eq!(
+            backup.run_to_completion(0, None, None::<fn(BackupProgress)>),
+            SqlitePrimaryResult::Misuse
+        );
+    }
+
+    #[test]
+    fn run_to_completion_gives_up_on_a_locked_destination() {
+        let path = temp_path("backup_busy.db");
+        let source = source();
+        let mut dest = Database::open(&path).unwrap();
+
+        let other = Database::open(&path).unwrap();
+        other
+            .execute_changes(String::from("BEGIN EXCLUSIVE;"))
+            .unwrap();
+
+        let mut backup =
+            Backup::new(&source, DatabaseName::Main, &mut dest, DatabaseName::Main).unwrap();
+        let status = backup.run_to_completion(
+            -1,
+            Some(Duration::from_millis(1)),
+            None::<fn(BackupProgress)>,
+        );
+        assert_eq!(status, SqlitePrimaryResult::Busy);
+
+        drop(backup);
+        drop(other);
+        remove_file(&path);
+    }
+
+    #[test]
+    fn backup_to_file_writes_a_copy() {
+        let path = temp_path("backup_file.db");
+        let source = source();
+
+        let status = source
+            .backup_to_file(
+                DatabaseName::Main,
+                &path,
+                -1,
+                None,
+                None::<fn(BackupProgress)>,
+            )
+            .unwrap();
+        assert_eq!(status, SqlitePrimaryResult::Ok);
+
+        let copy = Database::open(&path).unwrap();
+        assert_eq!(count(&copy), 1000);
+
+        assert!(source
+            .backup_to_file(
+                DatabaseName::Attached("missing"),
+                temp_path("backup_missing.db"),
+                -1,
+                None,
+                None::<fn(BackupProgress)>,
+            )
+            .is_err());
+
+        remove_file(&path);
+        remove_file(&temp_path("backup_missing.db"));
+    }
+}
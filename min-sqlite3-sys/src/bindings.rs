@@ -297,10 +297,47 @@ impl From<i32> for SqlitePrimaryResult {
     }
 }
 
+impl From<SqlitePrimaryResult> for i32 {
+    fn from(value: SqlitePrimaryResult) -> Self {
+        match value {
+            SqlitePrimaryResult::Ok => 0,
+            SqlitePrimaryResult::Error => 1,
+            SqlitePrimaryResult::Internal => 2,
+            SqlitePrimaryResult::Perm => 3,
+            SqlitePrimaryResult::Abort => 4,
+            SqlitePrimaryResult::Busy => 5,
+            SqlitePrimaryResult::Locked => 6,
+            SqlitePrimaryResult::NoMem => 7,
+            SqlitePrimaryResult::Readonly => 8,
+            SqlitePrimaryResult::Interrupt => 9,
+            SqlitePrimaryResult::IoErr => 10,
+            SqlitePrimaryResult::Corrupt => 11,
+            SqlitePrimaryResult::NotFound => 12,
+            SqlitePrimaryResult::Full => 13,
+            SqlitePrimaryResult::CantOpen => 14,
+            SqlitePrimaryResult::Protocol => 15,
+            SqlitePrimaryResult::Empty => 16,
+            SqlitePrimaryResult::Schema => 17,
+            SqlitePrimaryResult::TooBig => 18,
+            SqlitePrimaryResult::Constrait => 19,
+            SqlitePrimaryResult::MisMatch => 20,
+            SqlitePrimaryResult::Misuse => 21,
+            SqlitePrimaryResult::NoLfs => 22,
+            SqlitePrimaryResult::Auth => 23,
+            SqlitePrimaryResult::Format => 24,
+            SqlitePrimaryResult::Range => 25,
+            SqlitePrimaryResult::NotADB => 26,
+            SqlitePrimaryResult::Notice => 27,
+            SqlitePrimaryResult::Warning => 28,
+            SqlitePrimaryResult::Other(other_id) => other_id,
+        }
+    }
+}
+
 /// Binder of sqlite3 from C source
 #[repr(C)]
 #[derive(Copy, Clone)]
-pub(crate) struct sqlite3 {
+pub struct sqlite3 {
     __: [u8; 0],
 }
 
@@ -311,12 +348,761 @@ pub struct sqlite3_stmt {
     __: [u8; 0],
 }
 
+/// Binder of sqlite3_backup from C source
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub(crate) struct sqlite3_backup {
+    __: [u8; 0],
+}
+
+/// Binder of sqlite3_session from C source
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub(crate) struct sqlite3_session {
+    __: [u8; 0],
+}
+
+/// Binder of sqlite3_changeset_iter from C source
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub(crate) struct sqlite3_changeset_iter {
+    __: [u8; 0],
+}
+
+/// Binder of sqlite3_blob from C source
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub(crate) struct sqlite3_blob {
+    __: [u8; 0],
+}
+
+/// Binder of sqlite3_value from C source
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct sqlite3_value {
+    __: [u8; 0],
+}
+
+/// Binder of sqlite3_context from C source
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct sqlite3_context {
+    __: [u8; 0],
+}
+
+/// Binder of sqlite3_vtab from C source
+#[repr(C)]
+pub(crate) struct sqlite3_vtab {
+    pub(crate) p_module: *const sqlite3_module,
+    pub(crate) n_ref: os::raw::c_int,
+    pub(crate) z_err_msg: *mut os::raw::c_char,
+}
+
+/// Binder of sqlite3_vtab_cursor from C source
+#[repr(C)]
+pub(crate) struct sqlite3_vtab_cursor {
+    pub(crate) p_vtab: *mut sqlite3_vtab,
+}
+
+/// Binder of sqlite3_index_constraint from C source
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub(crate) struct sqlite3_index_constraint {
+    pub(crate) i_column: os::raw::c_int,
+    pub(crate) op: os::raw::c_uchar,
+    pub(crate) usable: os::raw::c_uchar,
+    pub(crate) i_term_offset: os::raw::c_int,
+}
+
+/// Binder of sqlite3_index_orderby from C source
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub(crate) struct sqlite3_index_orderby {
+    pub(crate) i_column: os::raw::c_int,
+    pub(crate) desc: os::raw::c_uchar,
+}
+
+/// Binder of sqlite3_index_constraint_usage from C source
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub(crate) struct sqlite3_index_constraint_usage {
+    pub(crate) argv_index: os::raw::c_int,
+    pub(crate) omit: os::raw::c_uchar,
+}
+
+/// Binder of sqlite3_index_info from C source
+#[repr(C)]
+pub(crate) struct sqlite3_index_info {
+    pub(crate) n_constraint: os::raw::c_int,
+    pub(crate) a_constraint: *mut sqlite3_index_constraint,
+    pub(crate) n_order_by: os::raw::c_int,
+    pub(crate) a_order_by: *mut sqlite3_index_orderby,
+    pub(crate) a_constraint_usage: *mut sqlite3_index_constraint_usage,
+    pub(crate) idx_num: os::raw::c_int,
+    pub(crate) idx_str: *mut os::raw::c_char,
+    pub(crate) need_to_free_idx_str: os::raw::c_int,
+    pub(crate) order_by_consumed: os::raw::c_int,
+    pub(crate) estimated_cost: f64,
+    pub(crate) estimated_rows: os::raw::c_longlong,
+    pub(crate) idx_flags: os::raw::c_int,
+    pub(crate) col_used: os::raw::c_ulonglong,
+}
+
+/// Binder of the xCreate and xConnect methods of sqlite3_module from C source
+pub(crate) type XConnect = unsafe extern "C" fn(
+    db: *mut sqlite3,
+    aux: *mut os::raw::c_void,
+    argc: os::raw::c_int,
+    argv: *const *const os::raw::c_char,
+    vtab: *mut *mut sqlite3_vtab,
+    err: *mut *mut os::raw::c_char,
+) -> os::raw::c_int;
+
+/// Binder of sqlite3_module from C source
+#[repr(C)]
+pub(crate) struct sqlite3_module {
+    pub(crate) i_version: os::raw::c_int,
+    pub(crate) x_create: Option<XConnect>,
+    pub(crate) x_connect: Option<XConnect>,
+    pub(crate) x_best_index: Option<
+        unsafe extern "C" fn(
+            vtab: *mut sqlite3_vtab,
+            info: *mut sqlite3_index_info,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_disconnect:
+        Option<unsafe extern "C" fn(vtab: *mut sqlite3_vtab) -> os::raw::c_int>,
+    pub(crate) x_destroy: Option<unsafe extern "C" fn(vtab: *mut sqlite3_vtab) -> os::raw::c_int>,
+    pub(crate) x_open: Option<
+        unsafe extern "C" fn(
+            vtab: *mut sqlite3_vtab,
+            cursor: *mut *mut sqlite3_vtab_cursor,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_close:
+        Option<unsafe extern "C" fn(cursor: *mut sqlite3_vtab_cursor) -> os::raw::c_int>,
+    pub(crate) x_filter: Option<
+        unsafe extern "C" fn(
+            cursor: *mut sqlite3_vtab_cursor,
+            idx_num: os::raw::c_int,
+            idx_str: *const os::raw::c_char,
+            argc: os::raw::c_int,
+            argv: *mut *mut sqlite3_value,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_next:
+        Option<unsafe extern "C" fn(cursor: *mut sqlite3_vtab_cursor) -> os::raw::c_int>,
+    pub(crate) x_eof:
+        Option<unsafe extern "C" fn(cursor: *mut sqlite3_vtab_cursor) -> os::raw::c_int>,
+    pub(crate) x_column: Option<
+        unsafe extern "C" fn(
+            cursor: *mut sqlite3_vtab_cursor,
+            ctx: *mut sqlite3_context,
+            i: os::raw::c_int,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_rowid: Option<
+        unsafe extern "C" fn(
+            cursor: *mut sqlite3_vtab_cursor,
+            rowid: *mut os::raw::c_longlong,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_update: Option<
+        unsafe extern "C" fn(
+            vtab: *mut sqlite3_vtab,
+            argc: os::raw::c_int,
+            argv: *mut *mut sqlite3_value,
+            rowid: *mut os::raw::c_longlong,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_begin: Option<unsafe extern "C" fn(vtab: *mut sqlite3_vtab) -> os::raw::c_int>,
+    pub(crate) x_sync: Option<unsafe extern "C" fn(vtab: *mut sqlite3_vtab) -> os::raw::c_int>,
+    pub(crate) x_commit: Option<unsafe extern "C" fn(vtab: *mut sqlite3_vtab) -> os::raw::c_int>,
+    pub(crate) x_rollback: Option<unsafe extern "C" fn(vtab: *mut sqlite3_vtab) -> os::raw::c_int>,
+    pub(crate) x_find_function: Option<unsafe extern "C" fn()>,
+    pub(crate) x_rename: Option<
+        unsafe extern "C" fn(
+            vtab: *mut sqlite3_vtab,
+            new_name: *const os::raw::c_char,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_savepoint:
+        Option<unsafe extern "C" fn(vtab: *mut sqlite3_vtab, i: os::raw::c_int) -> os::raw::c_int>,
+    pub(crate) x_release:
+        Option<unsafe extern "C" fn(vtab: *mut sqlite3_vtab, i: os::raw::c_int) -> os::raw::c_int>,
+    pub(crate) x_rollback_to:
+        Option<unsafe extern "C" fn(vtab: *mut sqlite3_vtab, i: os::raw::c_int) -> os::raw::c_int>,
+    pub(crate) x_shadow_name:
+        Option<unsafe extern "C" fn(name: *const os::raw::c_char) -> os::raw::c_int>,
+}
+
+/// Binder of Fts5Tokenizer from C source
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub(crate) struct Fts5Tokenizer {
+    __: [u8; 0],
+}
+
+/// Binder of the xToken callback of fts5_tokenizer from C source
+pub(crate) type XToken = unsafe extern "C" fn(
+    ctx: *mut os::raw::c_void,
+    flags: os::raw::c_int,
+    token: *const os::raw::c_char,
+    n_token: os::raw::c_int,
+    start: os::raw::c_int,
+    end: os::raw::c_int,
+) -> os::raw::c_int;
+
+/// Binder of fts5_tokenizer from C source
+#[repr(C)]
+pub(crate) struct fts5_tokenizer {
+    pub(crate) x_create: Option<
+        unsafe extern "C" fn(
+            aux: *mut os::raw::c_void,
+            argv: *mut *const os::raw::c_char,
+            argc: os::raw::c_int,
+            tokenizer: *mut *mut Fts5Tokenizer,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_delete: Option<unsafe extern "C" fn(tokenizer: *mut Fts5Tokenizer)>,
+    pub(crate) x_tokenize: Option<
+        unsafe extern "C" fn(
+            tokenizer: *mut Fts5Tokenizer,
+            ctx: *mut os::raw::c_void,
+            flags: os::raw::c_int,
+            text: *const os::raw::c_char,
+            n_text: os::raw::c_int,
+            x_token: Option<XToken>,
+        ) -> os::raw::c_int,
+    >,
+}
+
+/// Binder of fts5_api from C source
+#[repr(C)]
+pub(crate) struct fts5_api {
+    pub(crate) i_version: os::raw::c_int,
+    pub(crate) x_create_tokenizer: Option<
+        unsafe extern "C" fn(
+            api: *mut fts5_api,
+            name: *const os::raw::c_char,
+            aux: *mut os::raw::c_void,
+            tokenizer: *mut fts5_tokenizer,
+            destroy: Option<unsafe extern "C" fn(aux: *mut os::raw::c_void)>,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_find_tokenizer: Option<unsafe extern "C" fn()>,
+    pub(crate) x_create_function: Option<unsafe extern "C" fn()>,
+}
+
+/// Binder of sqlite3_file from C source
+#[repr(C)]
+pub(crate) struct sqlite3_file {
+    pub(crate) p_methods: *const sqlite3_io_methods,
+}
+
+/// Binder of sqlite3_io_methods from C source
+#[repr(C)]
+pub(crate) struct sqlite3_io_methods {
+    pub(crate) i_version: os::raw::c_int,
+    pub(crate) x_close: Option<unsafe extern "C" fn(file: *mut sqlite3_file) -> os::raw::c_int>,
+    pub(crate) x_read: Option<
+        unsafe extern "C" fn(
+            file: *mut sqlite3_file,
+            buf: *mut os::raw::c_void,
+            amount: os::raw::c_int,
+            offset: os::raw::c_longlong,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_write: Option<
+        unsafe extern "C" fn(
+            file: *mut sqlite3_file,
+            buf: *const os::raw::c_void,
+            amount: os::raw::c_int,
+            offset: os::raw::c_longlong,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_truncate: Option<
+        unsafe extern "C" fn(file: *mut sqlite3_file, size: os::raw::c_longlong) -> os::raw::c_int,
+    >,
+    pub(crate) x_sync: Option<
+        unsafe extern "C" fn(file: *mut sqlite3_file, flags: os::raw::c_int) -> os::raw::c_int,
+    >,
+    pub(crate) x_file_size: Option<
+        unsafe extern "C" fn(
+            file: *mut sqlite3_file,
+            size: *mut os::raw::c_longlong,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_lock: Option<
+        unsafe extern "C" fn(file: *mut sqlite3_file, lock: os::raw::c_int) -> os::raw::c_int,
+    >,
+    pub(crate) x_unlock: Option<
+        unsafe extern "C" fn(file: *mut sqlite3_file, lock: os::raw::c_int) -> os::raw::c_int,
+    >,
+    pub(crate) x_check_reserved_lock: Option<
+        unsafe extern "C" fn(
+            file: *mut sqlite3_file,
+            reserved: *mut os::raw::c_int,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_file_control: Option<
+        unsafe extern "C" fn(
+            file: *mut sqlite3_file,
+            op: os::raw::c_int,
+            arg: *mut os::raw::c_void,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_sector_size:
+        Option<unsafe extern "C" fn(file: *mut sqlite3_file) -> os::raw::c_int>,
+    pub(crate) x_device_characteristics:
+        Option<unsafe extern "C" fn(file: *mut sqlite3_file) -> os::raw::c_int>,
+    pub(crate) x_shm_map: Option<
+        unsafe extern "C" fn(
+            file: *mut sqlite3_file,
+            region: os::raw::c_int,
+            size: os::raw::c_int,
+            extend: os::raw::c_int,
+            pp: *mut *mut os::raw::c_void,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_shm_lock: Option<
+        unsafe extern "C" fn(
+            file: *mut sqlite3_file,
+            offset: os::raw::c_int,
+            n: os::raw::c_int,
+            flags: os::raw::c_int,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_shm_barrier: Option<unsafe extern "C" fn(file: *mut sqlite3_file)>,
+    pub(crate) x_shm_unmap: Option<
+        unsafe extern "C" fn(file: *mut sqlite3_file, delete: os::raw::c_int) -> os::raw::c_int,
+    >,
+    pub(crate) x_fetch: Option<unsafe extern "C" fn()>,
+    pub(crate) x_unfetch: Option<unsafe extern "C" fn()>,
+}
+
+/// Binder of sqlite3_vfs from C source
+#[repr(C)]
+pub(crate) struct sqlite3_vfs {
+    pub(crate) i_version: os::raw::c_int,
+    pub(crate) sz_os_file: os::raw::c_int,
+    pub(crate) mx_pathname: os::raw::c_int,
+    pub(crate) p_next: *mut sqlite3_vfs,
+    pub(crate) z_name: *const os::raw::c_char,
+    pub(crate) p_app_data: *mut os::raw::c_void,
+    pub(crate) x_open: Option<
+        unsafe extern "C" fn(
+            vfs: *mut sqlite3_vfs,
+            name: *const os::raw::c_char,
+            file: *mut sqlite3_file,
+            flags: os::raw::c_int,
+            out_flags: *mut os::raw::c_int,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_delete: Option<
+        unsafe extern "C" fn(
+            vfs: *mut sqlite3_vfs,
+            name: *const os::raw::c_char,
+            sync_dir: os::raw::c_int,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_access: Option<
+        unsafe extern "C" fn(
+            vfs: *mut sqlite3_vfs,
+            name: *const os::raw::c_char,
+            flags: os::raw::c_int,
+            result: *mut os::raw::c_int,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_full_pathname: Option<
+        unsafe extern "C" fn(
+            vfs: *mut sqlite3_vfs,
+            name: *const os::raw::c_char,
+            n_out: os::raw::c_int,
+            out: *mut os::raw::c_char,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_dl_open: Option<
+        unsafe extern "C" fn(
+            vfs: *mut sqlite3_vfs,
+            filename: *const os::raw::c_char,
+        ) -> *mut os::raw::c_void,
+    >,
+    pub(crate) x_dl_error: Option<
+        unsafe extern "C" fn(
+            vfs: *mut sqlite3_vfs,
+            n_byte: os::raw::c_int,
+            err_msg: *mut os::raw::c_char,
+        ),
+    >,
+    pub(crate) x_dl_sym: Option<
+        unsafe extern "C" fn(
+            vfs: *mut sqlite3_vfs,
+            handle: *mut os::raw::c_void,
+            symbol: *const os::raw::c_char,
+        ) -> Option<unsafe extern "C" fn()>,
+    >,
+    pub(crate) x_dl_close:
+        Option<unsafe extern "C" fn(vfs: *mut sqlite3_vfs, handle: *mut os::raw::c_void)>,
+    pub(crate) x_randomness: Option<
+        unsafe extern "C" fn(
+            vfs: *mut sqlite3_vfs,
+            n_byte: os::raw::c_int,
+            out: *mut os::raw::c_char,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_sleep: Option<
+        unsafe extern "C" fn(vfs: *mut sqlite3_vfs, microseconds: os::raw::c_int) -> os::raw::c_int,
+    >,
+    pub(crate) x_current_time:
+        Option<unsafe extern "C" fn(vfs: *mut sqlite3_vfs, time: *mut f64) -> os::raw::c_int>,
+    pub(crate) x_get_last_error: Option<
+        unsafe extern "C" fn(
+            vfs: *mut sqlite3_vfs,
+            n_byte: os::raw::c_int,
+            err_msg: *mut os::raw::c_char,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_current_time_int64: Option<
+        unsafe extern "C" fn(
+            vfs: *mut sqlite3_vfs,
+            time: *mut os::raw::c_longlong,
+        ) -> os::raw::c_int,
+    >,
+    pub(crate) x_set_system_call: Option<unsafe extern "C" fn()>,
+    pub(crate) x_get_system_call: Option<unsafe extern "C" fn()>,
+    pub(crate) x_next_system_call: Option<unsafe extern "C" fn()>,
+}
+
+/// Binder of sqlite3_rtree_query_info from C source
+#[repr(C)]
+pub(crate) struct sqlite3_rtree_query_info {
+    pub(crate) context: *mut os::raw::c_void,
+    pub(crate) n_param: os::raw::c_int,
+    pub(crate) a_param: *mut f64,
+    pub(crate) user: *mut os::raw::c_void,
+    pub(crate) x_del_user: Option<unsafe extern "C" fn(user: *mut os::raw::c_void)>,
+    pub(crate) a_coord: *mut f64,
+    pub(crate) an_queue: *mut os::raw::c_uint,
+    pub(crate) n_coord: os::raw::c_int,
+    pub(crate) i_level: os::raw::c_int,
+    pub(crate) mx_level: os::raw::c_int,
+    pub(crate) i_rowid: os::raw::c_longlong,
+    pub(crate) r_parent_score: f64,
+    pub(crate) e_parent_within: os::raw::c_int,
+    pub(crate) e_within: os::raw::c_int,
+    pub(crate) r_score: f64,
+    pub(crate) ap_sql_param: *mut *mut sqlite3_value,
+}
+
+/// Binder of sqlite3_api_routines from C source
+///
+/// The routines are declared without their signatures, and are cast to
+/// them where they're called.
+#[cfg(feature = "loadable-extension")]
+#[repr(C)]
+pub struct sqlite3_api_routines {
+    pub(crate) aggregate_context: Option<unsafe extern "C" fn()>,
+    pub(crate) aggregate_count: Option<unsafe extern "C" fn()>,
+    pub(crate) bind_blob: Option<unsafe extern "C" fn()>,
+    pub(crate) bind_double: Option<unsafe extern "C" fn()>,
+    pub(crate) bind_int: Option<unsafe extern "C" fn()>,
+    pub(crate) bind_int64: Option<unsafe extern "C" fn()>,
+    pub(crate) bind_null: Option<unsafe extern "C" fn()>,
+    pub(crate) bind_parameter_count: Option<unsafe extern "C" fn()>,
+    pub(crate) bind_parameter_index: Option<unsafe extern "C" fn()>,
+    pub(crate) bind_parameter_name: Option<unsafe extern "C" fn()>,
+    pub(crate) bind_text: Option<unsafe extern "C" fn()>,
+    pub(crate) bind_text16: Option<unsafe extern "C" fn()>,
+    pub(crate) bind_value: Option<unsafe extern "C" fn()>,
+    pub(crate) busy_handler: Option<unsafe extern "C" fn()>,
+    pub(crate) busy_timeout: Option<unsafe extern "C" fn()>,
+    pub(crate) changes: Option<unsafe extern "C" fn()>,
+    pub(crate) close: Option<unsafe extern "C" fn()>,
+    pub(crate) collation_needed: Option<unsafe extern "C" fn()>,
+    pub(crate) collation_needed16: Option<unsafe extern "C" fn()>,
+    pub(crate) column_blob: Option<unsafe extern "C" fn()>,
+    pub(crate) column_bytes: Option<unsafe extern "C" fn()>,
+    pub(crate) column_bytes16: Option<unsafe extern "C" fn()>,
+    pub(crate) column_count: Option<unsafe extern "C" fn()>,
+    pub(crate) column_database_name: Option<unsafe extern "C" fn()>,
+    pub(crate) column_database_name16: Option<unsafe extern "C" fn()>,
+    pub(crate) column_decltype: Option<unsafe extern "C" fn()>,
+    pub(crate) column_decltype16: Option<unsafe extern "C" fn()>,
+    pub(crate) column_double: Option<unsafe extern "C" fn()>,
+    pub(crate) column_int: Option<unsafe extern "C" fn()>,
+    pub(crate) column_int64: Option<unsafe extern "C" fn()>,
+    pub(crate) column_name: Option<unsafe extern "C" fn()>,
+    pub(crate) column_name16: Option<unsafe extern "C" fn()>,
+    pub(crate) column_origin_name: Option<unsafe extern "C" fn()>,
+    pub(crate) column_origin_name16: Option<unsafe extern "C" fn()>,
+    pub(crate) column_table_name: Option<unsafe extern "C" fn()>,
+    pub(crate) column_table_name16: Option<unsafe extern "C" fn()>,
+    pub(crate) column_text: Option<unsafe extern "C" fn()>,
+    pub(crate) column_text16: Option<unsafe extern "C" fn()>,
+    pub(crate) column_type: Option<unsafe extern "C" fn()>,
+    pub(crate) column_value: Option<unsafe extern "C" fn()>,
+    pub(crate) commit_hook: Option<unsafe extern "C" fn()>,
+    pub(crate) complete: Option<unsafe extern "C" fn()>,
+    pub(crate) complete16: Option<unsafe extern "C" fn()>,
+    pub(crate) create_collation: Option<unsafe extern "C" fn()>,
+    pub(crate) create_collation16: Option<unsafe extern "C" fn()>,
+    pub(crate) create_function: Option<unsafe extern "C" fn()>,
+    pub(crate) create_function16: Option<unsafe extern "C" fn()>,
+    pub(crate) create_module: Option<unsafe extern "C" fn()>,
+    pub(crate) data_count: Option<unsafe extern "C" fn()>,
+    pub(crate) db_handle: Option<unsafe extern "C" fn()>,
+    pub(crate) declare_vtab: Option<unsafe extern "C" fn()>,
+    pub(crate) enable_shared_cache: Option<unsafe extern "C" fn()>,
+    pub(crate) errcode: Option<unsafe extern "C" fn()>,
+    pub(crate) errmsg: Option<unsafe extern "C" fn()>,
+    pub(crate) errmsg16: Option<unsafe extern "C" fn()>,
+    pub(crate) exec: Option<unsafe extern "C" fn()>,
+    pub(crate) expired: Option<unsafe extern "C" fn()>,
+    pub(crate) finalize: Option<unsafe extern "C" fn()>,
+    pub(crate) free: Option<unsafe extern "C" fn()>,
+    pub(crate) free_table: Option<unsafe extern "C" fn()>,
+    pub(crate) get_autocommit: Option<unsafe extern "C" fn()>,
+    pub(crate) get_auxdata: Option<unsafe extern "C" fn()>,
+    pub(crate) get_table: Option<unsafe extern "C" fn()>,
+    pub(crate) global_recover: Option<unsafe extern "C" fn()>,
+    pub(crate) interruptx: Option<unsafe extern "C" fn()>,
+    pub(crate) last_insert_rowid: Option<unsafe extern "C" fn()>,
+    pub(crate) libversion: Option<unsafe extern "C" fn()>,
+    pub(crate) libversion_number: Option<unsafe extern "C" fn()>,
+    pub(crate) malloc: Option<unsafe extern "C" fn()>,
+    pub(crate) mprintf: Option<unsafe extern "C" fn()>,
+    pub(crate) open: Option<unsafe extern "C" fn()>,
+    pub(crate) open16: Option<unsafe extern "C" fn()>,
+    pub(crate) prepare: Option<unsafe extern "C" fn()>,
+    pub(crate) prepare16: Option<unsafe extern "C" fn()>,
+    pub(crate) profile: Option<unsafe extern "C" fn()>,
+    pub(crate) progress_handler: Option<unsafe extern "C" fn()>,
+    pub(crate) realloc: Option<unsafe extern "C" fn()>,
+    pub(crate) reset: Option<unsafe extern "C" fn()>,
+    pub(crate) result_blob: Option<unsafe extern "C" fn()>,
+    pub(crate) result_double: Option<unsafe extern "C" fn()>,
+    pub(crate) result_error: Option<unsafe extern "C" fn()>,
+    pub(crate) result_error16: Option<unsafe extern "C" fn()>,
+    pub(crate) result_int: Option<unsafe extern "C" fn()>,
+    pub(crate) result_int64: Option<unsafe extern "C" fn()>,
+    pub(crate) result_null: Option<unsafe extern "C" fn()>,
+    pub(crate) result_text: Option<unsafe extern "C" fn()>,
+    pub(crate) result_text16: Option<unsafe extern "C" fn()>,
+    pub(crate) result_text16be: Option<unsafe extern "C" fn()>,
+    pub(crate) result_text16le: Option<unsafe extern "C" fn()>,
+    pub(crate) result_value: Option<unsafe extern "C" fn()>,
+    pub(crate) rollback_hook: Option<unsafe extern "C" fn()>,
+    pub(crate) set_authorizer: Option<unsafe extern "C" fn()>,
+    pub(crate) set_auxdata: Option<unsafe extern "C" fn()>,
+    pub(crate) xsnprintf: Option<unsafe extern "C" fn()>,
+    pub(crate) step: Option<unsafe extern "C" fn()>,
+    pub(crate) table_column_metadata: Option<unsafe extern "C" fn()>,
+    pub(crate) thread_cleanup: Option<unsafe extern "C" fn()>,
+    pub(crate) total_changes: Option<unsafe extern "C" fn()>,
+    pub(crate) trace: Option<unsafe extern "C" fn()>,
+    pub(crate) transfer_bindings: Option<unsafe extern "C" fn()>,
+    pub(crate) update_hook: Option<unsafe extern "C" fn()>,
+    pub(crate) user_data: Option<unsafe extern "C" fn()>,
+    pub(crate) value_blob: Option<unsafe extern "C" fn()>,
+    pub(crate) value_bytes: Option<unsafe extern "C" fn()>,
+    pub(crate) value_bytes16: Option<unsafe extern "C" fn()>,
+    pub(crate) value_double: Option<unsafe extern "C" fn()>,
+    pub(crate) value_int: Option<unsafe extern "C" fn()>,
+    pub(crate) value_int64: Option<unsafe extern "C" fn()>,
+    pub(crate) value_numeric_type: Option<unsafe extern "C" fn()>,
+    pub(crate) value_text: Option<unsafe extern "C" fn()>,
+    pub(crate) value_text16: Option<unsafe extern "C" fn()>,
+    pub(crate) value_text16be: Option<unsafe extern "C" fn()>,
+    pub(crate) value_text16le: Option<unsafe extern "C" fn()>,
+    pub(crate) value_type: Option<unsafe extern "C" fn()>,
+    pub(crate) vmprintf: Option<unsafe extern "C" fn()>,
+    pub(crate) overload_function: Option<unsafe extern "C" fn()>,
+    pub(crate) prepare_v2: Option<unsafe extern "C" fn()>,
+    pub(crate) prepare16_v2: Option<unsafe extern "C" fn()>,
+    pub(crate) clear_bindings: Option<unsafe extern "C" fn()>,
+    pub(crate) create_module_v2: Option<unsafe extern "C" fn()>,
+    pub(crate) bind_zeroblob: Option<unsafe extern "C" fn()>,
+    pub(crate) blob_bytes: Option<unsafe extern "C" fn()>,
+    pub(crate) blob_close: Option<unsafe extern "C" fn()>,
+    pub(crate) blob_open: Option<unsafe extern "C" fn()>,
+    pub(crate) blob_read: Option<unsafe extern "C" fn()>,
+    pub(crate) blob_write: Option<unsafe extern "C" fn()>,
+    pub(crate) create_collation_v2: Option<unsafe extern "C" fn()>,
+    pub(crate) file_control: Option<unsafe extern "C" fn()>,
+    pub(crate) memory_highwater: Option<unsafe extern "C" fn()>,
+    pub(crate) memory_used: Option<unsafe extern "C" fn()>,
+    pub(crate) mutex_alloc: Option<unsafe extern "C" fn()>,
+    pub(crate) mutex_enter: Option<unsafe extern "C" fn()>,
+    pub(crate) mutex_free: Option<unsafe extern "C" fn()>,
+    pub(crate) mutex_leave: Option<unsafe extern "C" fn()>,
+    pub(crate) mutex_try: Option<unsafe extern "C" fn()>,
+    pub(crate) open_v2: Option<unsafe extern "C" fn()>,
+    pub(crate) release_memory: Option<unsafe extern "C" fn()>,
+    pub(crate) result_error_nomem: Option<unsafe extern "C" fn()>,
+    pub(crate) result_error_toobig: Option<unsafe extern "C" fn()>,
+    pub(crate) sleep: Option<unsafe extern "C" fn()>,
+    pub(crate) soft_heap_limit: Option<unsafe extern "C" fn()>,
+    pub(crate) vfs_find: Option<unsafe extern "C" fn()>,
+    pub(crate) vfs_register: Option<unsafe extern "C" fn()>,
+    pub(crate) vfs_unregister: Option<unsafe extern "C" fn()>,
+    pub(crate) xthreadsafe: Option<unsafe extern "C" fn()>,
+    pub(crate) result_zeroblob: Option<unsafe extern "C" fn()>,
+    pub(crate) result_error_code: Option<unsafe extern "C" fn()>,
+    pub(crate) test_control: Option<unsafe extern "C" fn()>,
+    pub(crate) randomness: Option<unsafe extern "C" fn()>,
+    pub(crate) context_db_handle: Option<unsafe extern "C" fn()>,
+    pub(crate) extended_result_codes: Option<unsafe extern "C" fn()>,
+    pub(crate) limit: Option<unsafe extern "C" fn()>,
+    pub(crate) next_stmt: Option<unsafe extern "C" fn()>,
+    pub(crate) sql: Option<unsafe extern "C" fn()>,
+    pub(crate) status: Option<unsafe extern "C" fn()>,
+    pub(crate) backup_finish: Option<unsafe extern "C" fn()>,
+    pub(crate) backup_init: Option<unsafe extern "C" fn()>,
+    pub(crate) backup_pagecount: Option<unsafe extern "C" fn()>,
+    pub(crate) backup_remaining: Option<unsafe extern "C" fn()>,
+    pub(crate) backup_step: Option<unsafe extern "C" fn()>,
+    pub(crate) compileoption_get: Option<unsafe extern "C" fn()>,
+    pub(crate) compileoption_used: Option<unsafe extern "C" fn()>,
+    pub(crate) create_function_v2: Option<unsafe extern "C" fn()>,
+    pub(crate) db_config: Option<unsafe extern "C" fn()>,
+    pub(crate) db_mutex: Option<unsafe extern "C" fn()>,
+    pub(crate) db_status: Option<unsafe extern "C" fn()>,
+    pub(crate) extended_errcode: Option<unsafe extern "C" fn()>,
+    pub(crate) log: Option<unsafe extern "C" fn()>,
+    pub(crate) soft_heap_limit64: Option<unsafe extern "C" fn()>,
+    pub(crate) sourceid: Option<unsafe extern "C" fn()>,
+    pub(crate) stmt_status: Option<unsafe extern "C" fn()>,
+    pub(crate) strnicmp: Option<unsafe extern "C" fn()>,
+    pub(crate) unlock_notify: Option<unsafe extern "C" fn()>,
+    pub(crate) wal_autocheckpoint: Option<unsafe extern "C" fn()>,
+    pub(crate) wal_checkpoint: Option<unsafe extern "C" fn()>,
+    pub(crate) wal_hook: Option<unsafe extern "C" fn()>,
+    pub(crate) blob_reopen: Option<unsafe extern "C" fn()>,
+    pub(crate) vtab_config: Option<unsafe extern "C" fn()>,
+    pub(crate) vtab_on_conflict: Option<unsafe extern "C" fn()>,
+    pub(crate) close_v2: Option<unsafe extern "C" fn()>,
+    pub(crate) db_filename: Option<unsafe extern "C" fn()>,
+    pub(crate) db_readonly: Option<unsafe extern "C" fn()>,
+    pub(crate) db_release_memory: Option<unsafe extern "C" fn()>,
+    pub(crate) errstr: Option<unsafe extern "C" fn()>,
+    pub(crate) stmt_busy: Option<unsafe extern "C" fn()>,
+    pub(crate) stmt_readonly: Option<unsafe extern "C" fn()>,
+    pub(crate) stricmp: Option<unsafe extern "C" fn()>,
+    pub(crate) uri_boolean: Option<unsafe extern "C" fn()>,
+    pub(crate) uri_int64: Option<unsafe extern "C" fn()>,
+    pub(crate) uri_parameter: Option<unsafe extern "C" fn()>,
+    pub(crate) xvsnprintf: Option<unsafe extern "C" fn()>,
+    pub(crate) wal_checkpoint_v2: Option<unsafe extern "C" fn()>,
+    pub(crate) auto_extension: Option<unsafe extern "C" fn()>,
+    pub(crate) bind_blob64: Option<unsafe extern "C" fn()>,
+    pub(crate) bind_text64: Option<unsafe extern "C" fn()>,
+    pub(crate) cancel_auto_extension: Option<unsafe extern "C" fn()>,
+    pub(crate) load_extension: Option<unsafe extern "C" fn()>,
+    pub(crate) malloc64: Option<unsafe extern "C" fn()>,
+    pub(crate) msize: Option<unsafe extern "C" fn()>,
+    pub(crate) realloc64: Option<unsafe extern "C" fn()>,
+    pub(crate) reset_auto_extension: Option<unsafe extern "C" fn()>,
+    pub(crate) result_blob64: Option<unsafe extern "C" fn()>,
+    pub(crate) result_text64: Option<unsafe extern "C" fn()>,
+    pub(crate) strglob: Option<unsafe extern "C" fn()>,
+    pub(crate) value_dup: Option<unsafe extern "C" fn()>,
+    pub(crate) value_free: Option<unsafe extern "C" fn()>,
+    pub(crate) result_zeroblob64: Option<unsafe extern "C" fn()>,
+    pub(crate) bind_zeroblob64: Option<unsafe extern "C" fn()>,
+    pub(crate) value_subtype: Option<unsafe extern "C" fn()>,
+    pub(crate) result_subtype: Option<unsafe extern "C" fn()>,
+    pub(crate) status64: Option<unsafe extern "C" fn()>,
+    pub(crate) strlike: Option<unsafe extern "C" fn()>,
+    pub(crate) db_cacheflush: Option<unsafe extern "C" fn()>,
+    pub(crate) system_errno: Option<unsafe extern "C" fn()>,
+    pub(crate) trace_v2: Option<unsafe extern "C" fn()>,
+    pub(crate) expanded_sql: Option<unsafe extern "C" fn()>,
+    pub(crate) set_last_insert_rowid: Option<unsafe extern "C" fn()>,
+    pub(crate) prepare_v3: Option<unsafe extern "C" fn()>,
+    pub(crate) prepare16_v3: Option<unsafe extern "C" fn()>,
+    pub(crate) bind_pointer: Option<unsafe extern "C" fn()>,
+    pub(crate) result_pointer: Option<unsafe extern "C" fn()>,
+    pub(crate) value_pointer: Option<unsafe extern "C" fn()>,
+    pub(crate) vtab_nochange: Option<unsafe extern "C" fn()>,
+    pub(crate) value_nochange: Option<unsafe extern "C" fn()>,
+    pub(crate) vtab_collation: Option<unsafe extern "C" fn()>,
+    pub(crate) keyword_count: Option<unsafe extern "C" fn()>,
+    pub(crate) keyword_name: Option<unsafe extern "C" fn()>,
+    pub(crate) keyword_check: Option<unsafe extern "C" fn()>,
+    pub(crate) str_new: Option<unsafe extern "C" fn()>,
+    pub(crate) str_finish: Option<unsafe extern "C" fn()>,
+    pub(crate) str_appendf: Option<unsafe extern "C" fn()>,
+    pub(crate) str_vappendf: Option<unsafe extern "C" fn()>,
+    pub(crate) str_append: Option<unsafe extern "C" fn()>,
+    pub(crate) str_appendall: Option<unsafe extern "C" fn()>,
+    pub(crate) str_appendchar: Option<unsafe extern "C" fn()>,
+    pub(crate) str_reset: Option<unsafe extern "C" fn()>,
+    pub(crate) str_errcode: Option<unsafe extern "C" fn()>,
+    pub(crate) str_length: Option<unsafe extern "C" fn()>,
+    pub(crate) str_value: Option<unsafe extern "C" fn()>,
+    pub(crate) create_window_function: Option<unsafe extern "C" fn()>,
+    pub(crate) normalized_sql: Option<unsafe extern "C" fn()>,
+    pub(crate) stmt_isexplain: Option<unsafe extern "C" fn()>,
+    pub(crate) value_frombind: Option<unsafe extern "C" fn()>,
+    pub(crate) drop_modules: Option<unsafe extern "C" fn()>,
+    pub(crate) hard_heap_limit64: Option<unsafe extern "C" fn()>,
+    pub(crate) uri_key: Option<unsafe extern "C" fn()>,
+    pub(crate) filename_database: Option<unsafe extern "C" fn()>,
+    pub(crate) filename_journal: Option<unsafe extern "C" fn()>,
+    pub(crate) filename_wal: Option<unsafe extern "C" fn()>,
+    pub(crate) create_filename: Option<unsafe extern "C" fn()>,
+    pub(crate) free_filename: Option<unsafe extern "C" fn()>,
+    pub(crate) database_file_object: Option<unsafe extern "C" fn()>,
+    pub(crate) txn_state: Option<unsafe extern "C" fn()>,
+    pub(crate) changes64: Option<unsafe extern "C" fn()>,
+    pub(crate) total_changes64: Option<unsafe extern "C" fn()>,
+    pub(crate) autovacuum_pages: Option<unsafe extern "C" fn()>,
+    pub(crate) error_offset: Option<unsafe extern "C" fn()>,
+    pub(crate) vtab_rhs_value: Option<unsafe extern "C" fn()>,
+    pub(crate) vtab_distinct: Option<unsafe extern "C" fn()>,
+    pub(crate) vtab_in: Option<unsafe extern "C" fn()>,
+    pub(crate) vtab_in_first: Option<unsafe extern "C" fn()>,
+    pub(crate) vtab_in_next: Option<unsafe extern "C" fn()>,
+    pub(crate) deserialize: Option<unsafe extern "C" fn()>,
+    pub(crate) serialize: Option<unsafe extern "C" fn()>,
+    pub(crate) db_name: Option<unsafe extern "C" fn()>,
+    pub(crate) value_encoding: Option<unsafe extern "C" fn()>,
+    pub(crate) is_interrupted: Option<unsafe extern "C" fn()>,
+}
+
+/// Binder of SQLITE_INTEGER from C source
+pub(crate) const COLUMN_INTEGER: u32 = 1;
+
+/// Binder of SQLITE_FLOAT from C source
+pub(crate) const COLUMN_FLOAT: u32 = 2;
+
+/// Binder of SQLITE_TEXT from C source
+pub(crate) const COLUMN_TEXT: u32 = 3;
+
+/// Binder of SQLITE_BLOB from C source
+pub(crate) const COLUMN_BLOB: u32 = 4;
+
 /// Binder of SQLITE_NULL from C source
 pub(crate) const COLUMN_NULL: u32 = 5;
 
+/// Binder of SQLITE_DELETE from C source
+pub(crate) const SQLITE_DELETE: i32 = 9;
+
+/// Binder of SQLITE_INSERT from C source
+pub(crate) const SQLITE_INSERT: i32 = 18;
+
+/// Binder of SQLITE_UPDATE from C source
+pub(crate) const SQLITE_UPDATE: i32 = 23;
+
+/// Binder of SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION from C source
+pub(crate) const SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION: i32 = 1005;
+
 #[inline(always)]
 pub fn sqlite_transient() -> Option<unsafe extern "C" fn(lifetime: *mut os::raw::c_void)> {
-    Some(unsafe { mem::transmute(-1_isize) })
+    Some(unsafe {
+        mem::transmute::<isize, unsafe extern "C" fn(lifetime: *mut os::raw::c_void)>(-1_isize)
+    })
 }
 
 #[inline(always)]
@@ -324,13 +1110,106 @@ pub fn sqlite_static() -> Option<unsafe extern "C" fn(lifetime: *mut os::raw::c_
     None
 }
 
-extern "C" {
+/// Routines of the SQLite library which loaded the extension, stored by
+/// `extension::init_extension` as `SQLITE_EXTENSION_INIT2` does.
+#[cfg(feature = "loadable-extension")]
+pub(crate) static API_ROUTINES: std::sync::atomic::AtomicPtr<sqlite3_api_routines> =
+    std::sync::atomic::AtomicPtr::new(std::ptr::null_mut());
+
+/// Binder of SQLITE_MISUSE from C source
+#[cfg(feature = "loadable-extension")]
+const SQLITE_MISUSE: os::raw::c_int = 21;
+
+/// Returns the routine of the loading SQLite library selected by `field`.
+///
+/// # Panics
+/// If no extension is initialized yet, or the library doesn't provide the
+/// routine.
+#[cfg(feature = "loadable-extension")]
+#[inline]
+fn api_routine(
+    field: impl FnOnce(&sqlite3_api_routines) -> Option<unsafe extern "C" fn()>,
+    name: &str,
+) -> unsafe extern "C" fn() {
+    let api = unsafe {
+        API_ROUTINES
+            .load(std::sync::atomic::Ordering::Acquire)
+            .as_ref()
+    }
+    .expect("SQLite API routines aren't initialized by the extension entry point");
+
+    field(api).unwrap_or_else(|| panic!("{} isn't provided by the loading SQLite library", name))
+}
+
+/// Declares the SQLite functions that are in `sqlite3_api_routines`.
+///
+/// They're linked from `libmin_sqlite3_sys`, or with the
+/// `loadable-extension` feature, called through the routines of the
+/// loading SQLite library, like the macros of `sqlite3ext.h` do.
+macro_rules! api_routines {
+    ($($vis:vis fn $name:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)? => $field:ident;)*) => {
+        #[cfg(not(feature = "loadable-extension"))]
+        extern "C" {
+            $($vis fn $name($($arg: $ty),*) $(-> $ret)?;)*
+        }
+
+        $(
+            #[cfg(feature = "loadable-extension")]
+            #[inline]
+            #[allow(clippy::missing_safety_doc, clippy::too_many_arguments)]
+            $vis unsafe fn $name($($arg: $ty),*) $(-> $ret)? {
+                let routine = api_routine(|api| api.$field, stringify!($name));
+                let routine = mem::transmute::<
+                    unsafe extern "C" fn(),
+                    unsafe extern "C" fn($($ty),*) $(-> $ret)?,
+                >(routine);
+                routine($($arg),*)
+            }
+        )*
+    };
+}
+
+/// Declares the SQLite functions that aren't in `sqlite3_api_routines`.
+///
+/// With the `loadable-extension` feature, they can't be reached and
+/// return the given fallback instead.
+macro_rules! linked_only {
+    ($($vis:vis fn $name:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)? => $fallback:expr;)*) => {
+        #[cfg(not(feature = "loadable-extension"))]
+        extern "C" {
+            $($vis fn $name($($arg: $ty),*) $(-> $ret)?;)*
+        }
+
+        $(
+            #[cfg(feature = "loadable-extension")]
+            #[inline]
+            #[allow(clippy::missing_safety_doc, clippy::too_many_arguments)]
+            $vis unsafe fn $name($(_: $ty),*) $(-> $ret)? {
+                $fallback
+            }
+        )*
+    };
+}
+
+api_routines! {
     pub(crate) fn sqlite3_open(
         file_path: *const os::raw::c_char,
         db: *mut *mut sqlite3,
-    ) -> os::raw::c_int;
+    ) -> os::raw::c_int => open;
+
+    pub(crate) fn sqlite3_open_v2(
+        file_path: *const os::raw::c_char,
+        db: *mut *mut sqlite3,
+        flags: os::raw::c_int,
+        vfs: *const os::raw::c_char,
+    ) -> os::raw::c_int => open_v2;
+
+    pub(crate) fn sqlite3_close(db: *mut sqlite3) -> os::raw::c_int => close;
 
-    pub(crate) fn sqlite3_close(db: *mut sqlite3) -> os::raw::c_int;
+    pub(crate) fn sqlite3_close_v2(db: *mut sqlite3) -> os::raw::c_int => close_v2;
+
+    pub(crate) fn sqlite3_next_stmt(db: *mut sqlite3, stmt: *mut sqlite3_stmt)
+        -> *mut sqlite3_stmt => next_stmt;
 
     pub(crate) fn sqlite3_exec(
         db: *mut sqlite3,
@@ -345,7 +1224,7 @@ extern "C" {
         >,
         callback_a: *mut os::raw::c_void,
         errmsg: *mut *mut os::raw::c_char,
-    ) -> os::raw::c_int;
+    ) -> os::raw::c_int => exec;
 
     pub(crate) fn sqlite3_prepare_v2(
         db: *mut sqlite3,
@@ -353,33 +1232,40 @@ extern "C" {
         n_byte: os::raw::c_int,
         pp_stmt: *mut *mut sqlite3_stmt,
         pz_tail: *mut *const os::raw::c_char,
-    ) -> os::raw::c_int;
+    ) -> os::raw::c_int => prepare_v2;
+
+    pub(crate) fn sqlite3_step(stmt: *mut sqlite3_stmt) -> os::raw::c_int => step;
 
-    pub(crate) fn sqlite3_step(stmt: *mut sqlite3_stmt) -> os::raw::c_int;
+    pub(crate) fn sqlite3_finalize(smtm: *mut sqlite3_stmt) -> os::raw::c_int => finalize;
 
-    pub(crate) fn sqlite3_finalize(smtm: *mut sqlite3_stmt) -> os::raw::c_int;
+    pub(crate) fn sqlite3_reset(smtm: *mut sqlite3_stmt) -> os::raw::c_int => reset;
+
+    pub(crate) fn sqlite3_column_name(
+        smtm: *mut sqlite3_stmt,
+        col_index: os::raw::c_int,
+    ) -> *const os::raw::c_char => column_name;
 
     pub(crate) fn sqlite3_column_blob(
         smtm: *mut sqlite3_stmt,
         col_index: os::raw::c_int,
-    ) -> *const os::raw::c_void;
+    ) -> *const os::raw::c_void => column_blob;
 
-    pub(crate) fn sqlite3_column_double(smtm: *mut sqlite3_stmt, col_index: os::raw::c_int) -> f64;
+    pub(crate) fn sqlite3_column_double(smtm: *mut sqlite3_stmt, col_index: os::raw::c_int) -> f64 => column_double;
 
     pub(crate) fn sqlite3_column_text(
         stmt: *mut sqlite3_stmt,
         col_index: os::raw::c_int,
-    ) -> *const os::raw::c_uchar;
+    ) -> *const os::raw::c_uchar => column_text;
 
     pub(crate) fn sqlite3_column_int64(
         stmt: *mut sqlite3_stmt,
         col_index: os::raw::c_int,
-    ) -> os::raw::c_longlong;
+    ) -> os::raw::c_longlong => column_int64;
 
     pub(crate) fn sqlite3_column_bytes(
         stmt: *mut sqlite3_stmt,
         col_index: os::raw::c_int,
-    ) -> os::raw::c_int;
+    ) -> os::raw::c_int => column_bytes;
 
     pub fn sqlite3_bind_blob(
         stmt: *mut sqlite3_stmt,
@@ -387,19 +1273,27 @@ extern "C" {
         val: *const os::raw::c_void,
         val_bytes: os::raw::c_int,
         val_lifetime: Option<unsafe extern "C" fn(lifetime: *mut os::raw::c_void)>,
-    ) -> os::raw::c_int;
+    ) -> os::raw::c_int => bind_blob;
 
     pub fn sqlite3_bind_zeroblob64(
         stmt: *mut sqlite3_stmt,
         col_index: os::raw::c_int,
         val: os::raw::c_ulonglong,
-    ) -> os::raw::c_int;
+    ) -> os::raw::c_int => bind_zeroblob64;
+
+    pub(crate) fn sqlite3_bind_pointer(
+        stmt: *mut sqlite3_stmt,
+        col_index: os::raw::c_int,
+        val: *mut os::raw::c_void,
+        type_name: *const os::raw::c_char,
+        destructor: Option<unsafe extern "C" fn(val: *mut os::raw::c_void)>,
+    ) -> os::raw::c_int => bind_pointer;
 
     pub fn sqlite3_bind_double(
         stmt: *mut sqlite3_stmt,
         col_index: os::raw::c_int,
         val: f64,
-    ) -> os::raw::c_int;
+    ) -> os::raw::c_int => bind_double;
 
     pub fn sqlite3_bind_text(
         stmt: *mut sqlite3_stmt,
@@ -407,18 +1301,459 @@ extern "C" {
         val: *const os::raw::c_char,
         val_bytes: os::raw::c_int,
         val_lifetime: Option<unsafe extern "C" fn(lifetime: *mut os::raw::c_void)>,
-    ) -> os::raw::c_int;
+    ) -> os::raw::c_int => bind_text;
 
     pub fn sqlite3_bind_int64(
         stmt: *mut sqlite3_stmt,
         col_index: os::raw::c_int,
         val: os::raw::c_longlong,
-    ) -> os::raw::c_int;
+    ) -> os::raw::c_int => bind_int64;
 
-    pub fn sqlite3_bind_null(stmt: *mut sqlite3_stmt, col_index: os::raw::c_int) -> os::raw::c_int;
+    pub fn sqlite3_bind_null(stmt: *mut sqlite3_stmt, col_index: os::raw::c_int) -> os::raw::c_int => bind_null;
 
     pub fn sqlite3_column_type(
         stmt: *mut sqlite3_stmt,
         col_index: os::raw::c_int,
-    ) -> os::raw::c_int;
+    ) -> os::raw::c_int => column_type;
+
+    pub(crate) fn sqlite3_errmsg(db: *mut sqlite3) -> *const os::raw::c_char => errmsg;
+
+    pub(crate) fn sqlite3_value_type(val: *mut sqlite3_value) -> os::raw::c_int => value_type;
+
+    pub(crate) fn sqlite3_value_int64(val: *mut sqlite3_value) -> os::raw::c_longlong => value_int64;
+
+    pub(crate) fn sqlite3_value_double(val: *mut sqlite3_value) -> f64 => value_double;
+
+    pub(crate) fn sqlite3_value_text(val: *mut sqlite3_value) -> *const os::raw::c_uchar => value_text;
+
+    pub(crate) fn sqlite3_value_blob(val: *mut sqlite3_value) -> *const os::raw::c_void => value_blob;
+
+    pub(crate) fn sqlite3_value_bytes(val: *mut sqlite3_value) -> os::raw::c_int => value_bytes;
+
+    pub(crate) fn sqlite3_value_pointer(
+        val: *mut sqlite3_value,
+        type_name: *const os::raw::c_char,
+    ) -> *mut os::raw::c_void => value_pointer;
+
+    pub(crate) fn sqlite3_set_authorizer(
+        db: *mut sqlite3,
+        callback: Option<
+            unsafe extern "C" fn(
+                ctx: *mut os::raw::c_void,
+                action: os::raw::c_int,
+                arg1: *const os::raw::c_char,
+                arg2: *const os::raw::c_char,
+                db_name: *const os::raw::c_char,
+                accessor: *const os::raw::c_char,
+            ) -> os::raw::c_int,
+        >,
+        ctx: *mut os::raw::c_void,
+    ) -> os::raw::c_int => set_authorizer;
+
+    pub(crate) fn sqlite3_progress_handler(
+        db: *mut sqlite3,
+        n_ops: os::raw::c_int,
+        callback: Option<unsafe extern "C" fn(ctx: *mut os::raw::c_void) -> os::raw::c_int>,
+        ctx: *mut os::raw::c_void,
+    ) => progress_handler;
+
+    pub(crate) fn sqlite3_interrupt(db: *mut sqlite3) => interruptx;
+
+    pub(crate) fn sqlite3_trace_v2(
+        db: *mut sqlite3,
+        mask: os::raw::c_uint,
+        callback: Option<
+            unsafe extern "C" fn(
+                event_code: os::raw::c_uint,
+                ctx: *mut os::raw::c_void,
+                p: *mut os::raw::c_void,
+                x: *mut os::raw::c_void,
+            ) -> os::raw::c_int,
+        >,
+        ctx: *mut os::raw::c_void,
+    ) -> os::raw::c_int => trace_v2;
+
+    pub(crate) fn sqlite3_sql(stmt: *mut sqlite3_stmt) -> *const os::raw::c_char => sql;
+
+    pub(crate) fn sqlite3_expanded_sql(stmt: *mut sqlite3_stmt) -> *mut os::raw::c_char => expanded_sql;
+
+    pub(crate) fn sqlite3_free(ptr: *mut os::raw::c_void) => free;
+
+    pub(crate) fn sqlite3_errcode(db: *mut sqlite3) -> os::raw::c_int => errcode;
+
+    pub(crate) fn sqlite3_backup_init(
+        dest: *mut sqlite3,
+        dest_name: *const os::raw::c_char,
+        source: *mut sqlite3,
+        source_name: *const os::raw::c_char,
+    ) -> *mut sqlite3_backup => backup_init;
+
+    pub(crate) fn sqlite3_backup_step(
+        backup: *mut sqlite3_backup,
+        n_page: os::raw::c_int,
+    ) -> os::raw::c_int => backup_step;
+
+    pub(crate) fn sqlite3_backup_finish(backup: *mut sqlite3_backup) -> os::raw::c_int => backup_finish;
+
+    pub(crate) fn sqlite3_backup_remaining(backup: *mut sqlite3_backup) -> os::raw::c_int => backup_remaining;
+
+    pub(crate) fn sqlite3_backup_pagecount(backup: *mut sqlite3_backup) -> os::raw::c_int => backup_pagecount;
+
+    pub(crate) fn sqlite3_blob_open(
+        db: *mut sqlite3,
+        db_name: *const os::raw::c_char,
+        table_name: *const os::raw::c_char,
+        column_name: *const os::raw::c_char,
+        rowid: os::raw::c_longlong,
+        flags: os::raw::c_int,
+        blob: *mut *mut sqlite3_blob,
+    ) -> os::raw::c_int => blob_open;
+
+    pub(crate) fn sqlite3_blob_reopen(
+        blob: *mut sqlite3_blob,
+        rowid: os::raw::c_longlong,
+    ) -> os::raw::c_int => blob_reopen;
+
+    pub(crate) fn sqlite3_blob_close(blob: *mut sqlite3_blob) -> os::raw::c_int => blob_close;
+
+    pub(crate) fn sqlite3_blob_bytes(blob: *mut sqlite3_blob) -> os::raw::c_int => blob_bytes;
+
+    pub(crate) fn sqlite3_blob_read(
+        blob: *mut sqlite3_blob,
+        buffer: *mut os::raw::c_void,
+        n: os::raw::c_int,
+        offset: os::raw::c_int,
+    ) -> os::raw::c_int => blob_read;
+
+    pub(crate) fn sqlite3_blob_write(
+        blob: *mut sqlite3_blob,
+        buffer: *const os::raw::c_void,
+        n: os::raw::c_int,
+        offset: os::raw::c_int,
+    ) -> os::raw::c_int => blob_write;
+
+    pub(crate) fn sqlite3_malloc64(size: os::raw::c_ulonglong) -> *mut os::raw::c_void => malloc64;
+
+    pub(crate) fn sqlite3_serialize(
+        db: *mut sqlite3,
+        schema: *const os::raw::c_char,
+        size: *mut os::raw::c_longlong,
+        flags: os::raw::c_uint,
+    ) -> *mut os::raw::c_uchar => serialize;
+
+    pub(crate) fn sqlite3_deserialize(
+        db: *mut sqlite3,
+        schema: *const os::raw::c_char,
+        data: *mut os::raw::c_uchar,
+        db_size: os::raw::c_longlong,
+        buffer_size: os::raw::c_longlong,
+        flags: os::raw::c_uint,
+    ) -> os::raw::c_int => deserialize;
+
+    pub(crate) fn sqlite3_wal_checkpoint_v2(
+        db: *mut sqlite3,
+        db_name: *const os::raw::c_char,
+        mode: os::raw::c_int,
+        log_frames: *mut os::raw::c_int,
+        checkpointed_frames: *mut os::raw::c_int,
+    ) -> os::raw::c_int => wal_checkpoint_v2;
+
+    pub(crate) fn sqlite3_wal_autocheckpoint(db: *mut sqlite3, n: os::raw::c_int)
+        -> os::raw::c_int => wal_autocheckpoint;
+
+    pub(crate) fn sqlite3_wal_hook(
+        db: *mut sqlite3,
+        callback: Option<
+            unsafe extern "C" fn(
+                ctx: *mut os::raw::c_void,
+                db: *mut sqlite3,
+                db_name: *const os::raw::c_char,
+                pages: os::raw::c_int,
+            ) -> os::raw::c_int,
+        >,
+        ctx: *mut os::raw::c_void,
+    ) -> *mut os::raw::c_void => wal_hook;
+
+    pub(crate) fn sqlite3_column_count(stmt: *mut sqlite3_stmt) -> os::raw::c_int => column_count;
+
+    pub(crate) fn sqlite3_table_column_metadata(
+        db: *mut sqlite3,
+        db_name: *const os::raw::c_char,
+        table_name: *const os::raw::c_char,
+        column_name: *const os::raw::c_char,
+        data_type: *mut *const os::raw::c_char,
+        coll_seq: *mut *const os::raw::c_char,
+        not_null: *mut os::raw::c_int,
+        primary_key: *mut os::raw::c_int,
+        autoinc: *mut os::raw::c_int,
+    ) -> os::raw::c_int => table_column_metadata;
+
+    pub(crate) fn sqlite3_last_insert_rowid(db: *mut sqlite3) -> os::raw::c_longlong => last_insert_rowid;
+
+    pub(crate) fn sqlite3_changes64(db: *mut sqlite3) -> os::raw::c_longlong => changes64;
+
+    pub(crate) fn sqlite3_total_changes64(db: *mut sqlite3) -> os::raw::c_longlong => total_changes64;
+
+    pub(crate) fn sqlite3_db_handle(stmt: *mut sqlite3_stmt) -> *mut sqlite3 => db_handle;
+
+    pub(crate) fn sqlite3_create_module_v2(
+        db: *mut sqlite3,
+        name: *const os::raw::c_char,
+        module: *const sqlite3_module,
+        aux: *mut os::raw::c_void,
+        destroy: Option<unsafe extern "C" fn(aux: *mut os::raw::c_void)>,
+    ) -> os::raw::c_int => create_module_v2;
+
+    pub(crate) fn sqlite3_declare_vtab(
+        db: *mut sqlite3,
+        sql: *const os::raw::c_char,
+    ) -> os::raw::c_int => declare_vtab;
+
+    pub(crate) fn sqlite3_result_null(ctx: *mut sqlite3_context) => result_null;
+
+    pub(crate) fn sqlite3_result_int64(ctx: *mut sqlite3_context, value: os::raw::c_longlong) => result_int64;
+
+    pub(crate) fn sqlite3_result_double(ctx: *mut sqlite3_context, value: f64) => result_double;
+
+    pub(crate) fn sqlite3_result_text64(
+        ctx: *mut sqlite3_context,
+        value: *const os::raw::c_char,
+        len: os::raw::c_ulonglong,
+        destructor: Option<unsafe extern "C" fn(lifetime: *mut os::raw::c_void)>,
+        encoding: os::raw::c_uchar,
+    ) => result_text64;
+
+    pub(crate) fn sqlite3_result_blob64(
+        ctx: *mut sqlite3_context,
+        value: *const os::raw::c_void,
+        len: os::raw::c_ulonglong,
+        destructor: Option<unsafe extern "C" fn(lifetime: *mut os::raw::c_void)>,
+    ) => result_blob64;
+
+    pub(crate) fn sqlite3_result_error(
+        ctx: *mut sqlite3_context,
+        message: *const os::raw::c_char,
+        len: os::raw::c_int,
+    ) => result_error;
+
+    pub(crate) fn sqlite3_load_extension(
+        db: *mut sqlite3,
+        file: *const os::raw::c_char,
+        entry_point: *const os::raw::c_char,
+        err_msg: *mut *mut os::raw::c_char,
+    ) -> os::raw::c_int => load_extension;
+
+    pub(crate) fn sqlite3_vfs_find(name: *const os::raw::c_char) -> *mut sqlite3_vfs => vfs_find;
+
+    pub(crate) fn sqlite3_vfs_register(
+        vfs: *mut sqlite3_vfs,
+        make_default: os::raw::c_int,
+    ) -> os::raw::c_int => vfs_register;
+}
+
+linked_only! {
+    pub(crate) fn sqlite3_preupdate_hook(
+        db: *mut sqlite3,
+        callback: Option<
+            unsafe extern "C" fn(
+                ctx: *mut os::raw::c_void,
+                db: *mut sqlite3,
+                op: os::raw::c_int,
+                db_name: *const os::raw::c_char,
+                table_name: *const os::raw::c_char,
+                old_rowid: os::raw::c_longlong,
+                new_rowid: os::raw::c_longlong,
+            ),
+        >,
+        ctx: *mut os::raw::c_void,
+    ) -> *mut os::raw::c_void => std::ptr::null_mut();
+
+    pub(crate) fn sqlite3_preupdate_old(
+        db: *mut sqlite3,
+        col_index: os::raw::c_int,
+        val: *mut *mut sqlite3_value,
+    ) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3_preupdate_new(
+        db: *mut sqlite3,
+        col_index: os::raw::c_int,
+        val: *mut *mut sqlite3_value,
+    ) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3_preupdate_count(db: *mut sqlite3) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3_preupdate_depth(db: *mut sqlite3) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3session_create(
+        db: *mut sqlite3,
+        db_name: *const os::raw::c_char,
+        session: *mut *mut sqlite3_session,
+    ) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3session_delete(session: *mut sqlite3_session) => ();
+
+    pub(crate) fn sqlite3session_attach(
+        session: *mut sqlite3_session,
+        table: *const os::raw::c_char,
+    ) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3session_enable(
+        session: *mut sqlite3_session,
+        enable: os::raw::c_int,
+    ) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3session_indirect(
+        session: *mut sqlite3_session,
+        indirect: os::raw::c_int,
+    ) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3session_isempty(session: *mut sqlite3_session) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3session_changeset(
+        session: *mut sqlite3_session,
+        n_changeset: *mut os::raw::c_int,
+        changeset: *mut *mut os::raw::c_void,
+    ) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3session_patchset(
+        session: *mut sqlite3_session,
+        n_patchset: *mut os::raw::c_int,
+        patchset: *mut *mut os::raw::c_void,
+    ) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3changeset_start(
+        iter: *mut *mut sqlite3_changeset_iter,
+        n_changeset: os::raw::c_int,
+        changeset: *mut os::raw::c_void,
+    ) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3changeset_next(iter: *mut sqlite3_changeset_iter) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3changeset_op(
+        iter: *mut sqlite3_changeset_iter,
+        table: *mut *const os::raw::c_char,
+        n_column: *mut os::raw::c_int,
+        op: *mut os::raw::c_int,
+        indirect: *mut os::raw::c_int,
+    ) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3changeset_pk(
+        iter: *mut sqlite3_changeset_iter,
+        primary_key: *mut *mut os::raw::c_uchar,
+        n_column: *mut os::raw::c_int,
+    ) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3changeset_old(
+        iter: *mut sqlite3_changeset_iter,
+        col_index: os::raw::c_int,
+        value: *mut *mut sqlite3_value,
+    ) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3changeset_new(
+        iter: *mut sqlite3_changeset_iter,
+        col_index: os::raw::c_int,
+        value: *mut *mut sqlite3_value,
+    ) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3changeset_conflict(
+        iter: *mut sqlite3_changeset_iter,
+        col_index: os::raw::c_int,
+        value: *mut *mut sqlite3_value,
+    ) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3changeset_finalize(iter: *mut sqlite3_changeset_iter) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3changeset_invert(
+        n_in: os::raw::c_int,
+        input: *const os::raw::c_void,
+        n_out: *mut os::raw::c_int,
+        output: *mut *mut os::raw::c_void,
+    ) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3changeset_concat(
+        n_a: os::raw::c_int,
+        a: *mut os::raw::c_void,
+        n_b: os::raw::c_int,
+        b: *mut os::raw::c_void,
+        n_out: *mut os::raw::c_int,
+        output: *mut *mut os::raw::c_void,
+    ) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3changeset_apply(
+        db: *mut sqlite3,
+        n_changeset: os::raw::c_int,
+        changeset: *mut os::raw::c_void,
+        filter: Option<
+            unsafe extern "C" fn(
+                ctx: *mut os::raw::c_void,
+                table: *const os::raw::c_char,
+            ) -> os::raw::c_int,
+        >,
+        conflict: Option<
+            unsafe extern "C" fn(
+                ctx: *mut os::raw::c_void,
+                conflict: os::raw::c_int,
+                iter: *mut sqlite3_changeset_iter,
+            ) -> os::raw::c_int,
+        >,
+        ctx: *mut os::raw::c_void,
+    ) -> os::raw::c_int => SQLITE_MISUSE;
+
+    pub(crate) fn sqlite3_rtree_query_callback(
+        db: *mut sqlite3,
+        name: *const os::raw::c_char,
+        query: Option<unsafe extern "C" fn(info: *mut sqlite3_rtree_query_info) -> os::raw::c_int>,
+        context: *mut os::raw::c_void,
+        destroy: Option<unsafe extern "C" fn(context: *mut os::raw::c_void)>,
+    ) -> os::raw::c_int => SQLITE_MISUSE;
+}
+
+#[cfg(not(feature = "loadable-extension"))]
+extern "C" {
+    pub(crate) fn sqlite3_initialize() -> os::raw::c_int;
+
+    pub(crate) fn sqlite3_shutdown() -> os::raw::c_int;
+
+    pub(crate) fn sqlite3_config(option: os::raw::c_int, ...) -> os::raw::c_int;
+
+    pub(crate) fn sqlite3_db_config(db: *mut sqlite3, op: os::raw::c_int, ...) -> os::raw::c_int;
+}
+
+/// Calls `sqlite3_db_config` of the loading SQLite library with an integer
+/// option, as variadic functions can't be declared in Rust.
+#[cfg(feature = "loadable-extension")]
+#[inline]
+pub(crate) unsafe fn sqlite3_db_config(
+    db: *mut sqlite3,
+    op: os::raw::c_int,
+    value: os::raw::c_int,
+    out: *mut os::raw::c_int,
+) -> os::raw::c_int {
+    let routine = api_routine(|api| api.db_config, "sqlite3_db_config");
+    let routine = mem::transmute::<
+        unsafe extern "C" fn(),
+        unsafe extern "C" fn(*mut sqlite3, os::raw::c_int, ...) -> os::raw::c_int,
+    >(routine);
+    routine(db, op, value, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SqlitePrimaryResult;
+
+    #[test]
+    fn result_codes_round_trip() {
+        for code in 0..=28 {
+            let status = SqlitePrimaryResult::from(code);
+            assert!(!matches!(status, SqlitePrimaryResult::Other(_)), "{}", code);
+            assert_eq!(i32::from(status), code);
+        }
+
+        // extended result codes are kept as they are
+        assert_eq!(
+            SqlitePrimaryResult::from(266),
+            SqlitePrimaryResult::Other(266)
+        );
+        assert_eq!(i32::from(SqlitePrimaryResult::Other(266)), 266);
+    }
 }
@@ -0,0 +1,303 @@
+//! This module contains data-types and functions to read and write
+//! BLOB columns incrementally, without loading them fully into memory.
+
+#![forbid(missing_docs)]
+
+use std::{
+    cmp,
+    ffi::CString,
+    io::{self, Read, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    os, ptr,
+};
+
+use crate::{
+    bindings::{
+        sqlite3_blob, sqlite3_blob_bytes, sqlite3_blob_close, sqlite3_blob_open, sqlite3_blob_read,
+        sqlite3_blob_reopen, sqlite3_blob_write,
+    },
+    ehandle::MinSqliteWrapperError,
+    prelude::*,
+};
+
+/// Binded instance of the sqlite3_blob. Provides `Read`, `Write` and
+/// `Seek` over a single BLOB value.
+///
+/// # Warning
+/// Writes can not change the size of the BLOB. Use `ZeroBlob` to
+/// reserve the space before opening the BLOB for writing.
+pub struct Blob<'a> {
+    bp: *mut sqlite3_blob,
+    position: usize,
+    _marker: PhantomData<&'a Database>,
+}
+
+impl<'a> Drop for Blob<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            sqlite3_blob_close(self.bp);
+        }
+    }
+}
+
+impl<'a> Blob<'a> {
+    /// Moves the handle to the row `rowid` of the same table and column,
+    /// and resets the position to the start of the BLOB.
+    pub fn reopen(&mut self, rowid: i64) -> SqlitePrimaryResult {
+        let status = unsafe { SqlitePrimaryResult::from(sqlite3_blob_reopen(self.bp, rowid)) };
+        self.position = 0;
+        status
+    }
+
+    /// Returns the size of the BLOB in bytes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        unsafe { sqlite3_blob_bytes(self.bp) as usize }
+    }
+
+    /// Returns `true` if the size of the BLOB is zero.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Closes the BLOB handle. Returns the error of the
+    /// pending write, if there is any.
+    #[inline]
+    pub fn close(self) -> SqlitePrimaryResult {
+        let status = unsafe { SqlitePrimaryResult::from(sqlite3_blob_close(self.bp)) };
+        std::mem::forget(self);
+        status
+    }
+}
+
+#[inline]
+// io::Error::other needs Rust 1.74
+#[allow(clippy::io_other_error)]
+fn to_io_error(status: SqlitePrimaryResult) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, format!("{:?}", status))
+}
+
+impl<'a> Read for Blob<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = cmp::min(buf.len(), self.len().saturating_sub(self.position));
+        if n == 0 {
+            return Ok(0);
+        }
+
+        let status = unsafe {
+            SqlitePrimaryResult::from(sqlite3_blob_read(
+                self.bp,
+                buf.as_mut_ptr() as *mut os::raw::c_void,
+                n as os::raw::c_int,
+                self.position as os::raw::c_int,
+            ))
+        };
+
+        if status != SqlitePrimaryResult::Ok {
+            return Err(to_io_error(status));
+        }
+
+        self.position += n;
+        Ok(n)
+    }
+}
+
+impl<'a> Write for Blob<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = cmp::min(buf.len(), self.len().saturating_sub(self.position));
+        if n == 0 {
+            return Ok(0);
+        }
+
+        let status = unsafe {
+            SqlitePrimaryResult::from(sqlite3_blob_write(
+                self.bp,
+                buf.as_ptr() as *const os::raw::c_void,
+                n as os::raw::c_int,
+                self.position as os::raw::c_int,
+            ))
+        };
+
+        if status != SqlitePrimaryResult::Ok {
+            return Err(to_io_error(status));
+        }
+
+        self.position += n;
+        Ok(n)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> Seek for Blob<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset as i64),
+            SeekFrom::End(offset) => (self.len() as i64).checked_add(offset),
+            SeekFrom::Current(offset) => (self.position as i64).checked_add(offset),
+        };
+
+        match position {
+            Some(position) if position >= 0 && position <= self.len() as i64 => {
+                self.position = position as usize;
+                Ok(position as u64)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a position outside of the BLOB",
+            )),
+        }
+    }
+}
+
+/// Specifies the incremental BLOB I/O functions of the SQLite connection.
+pub trait BlobOperations {
+    /// Opens a handle to the BLOB located in row `rowid`, column `column`,
+    /// table `table` of the database `db`.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let mut sql = db.prepare(
+    ///     String::from("INSERT INTO archives (id, content) VALUES (1, ?);"),
+    ///     None::<Box<dyn FnOnce(SqlitePrimaryResult, String)>>,
+    /// ).unwrap();
+    /// sql.bind_val(1, ZeroBlob(archive_size));
+    /// sql.execute_prepared();
+    ///
+    /// let mut blob = db.blob_open(DatabaseName::Main, "archives", "content", 1, false).unwrap();
+    /// std::io::copy(&mut archive_file, &mut blob).unwrap();
+    /// ```
+    fn blob_open<'a>(
+        &'a self,
+        db: DatabaseName,
+        table: &str,
+        column: &str,
+        rowid: i64,
+        read_only: bool,
+    ) -> Result<Blob<'a>, MinSqliteWrapperError<'a>>;
+}
+
+impl BlobOperations for Database {
+    fn blob_open<'a>(
+        &'a self,
+        db: DatabaseName,
+        table: &str,
+        column: &str,
+        rowid: i64,
+        read_only: bool,
+    ) -> Result<Blob<'a>, MinSqliteWrapperError<'a>> {
+        let db = db.to_cstring()?;
+        let table = CString::new(table)?;
+        let column = CString::new(column)?;
+        let mut bp = ptr::null_mut();
+
+        let status = unsafe {
+            SqlitePrimaryResult::from(sqlite3_blob_open(
+                self.rp,
+                db.as_ptr(),
+                table.as_ptr(),
+                column.as_ptr(),
+                rowid,
+                !read_only as os::raw::c_int,
+                &mut bp,
+            ))
+        };
+
+        if status != SqlitePrimaryResult::Ok {
+            let error = MinSqliteWrapperError::from_connection(self.rp);
+            unsafe {
+                sqlite3_blob_close(bp);
+            }
+            return Err(error);
+        }
+
+        Ok(Blob {
+            bp,
+            position: 0,
+            _marker: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    use crate::{prelude::*, test_support::open_memory};
+
+    fn open() -> Database {
+        open_memory(
+            "CREATE TABLE t(id INTEGER PRIMARY KEY, data BLOB);
+            INSERT INTO t VALUES (2, x'0102');",
+        )
+    }
+
+    #[test]
+    fn blob_is_written_read_and_sought() {
+        let db = open_memory("CREATE TABLE t(id INTEGER PRIMARY KEY, data BLOB);");
+        let mut sql = db
+            .prepare(
+                String::from("INSERT INTO t VALUES (1, ?);"),
+                None::<Box<dyn FnOnce(SqlitePrimaryResult, String)>>,
+            )
+            .unwrap();
+        sql.bind_val(1, ZeroBlob(8));
+        assert_eq!(sql.execute_prepared(), PreparedStatementStatus::Done);
+
+        let mut blob = db
+            .blob_open(DatabaseName::Main, "t", "data", 1, false)
+            .unwrap();
+        assert_eq!(blob.len(), 8);
+
+        // writes stop at the end of the BLOB, they can't grow it
+        assert_eq!(blob.write(b"0123456789").unwrap(), 8);
+        assert_eq!(blob.write(b"x").unwrap(), 0);
+
+        assert_eq!(blob.seek(SeekFrom::End(-3)).unwrap(), 5);
+        let mut tail = Vec::new();
+        blob.read_to_end(&mut tail).unwrap();
+        assert_eq!(tail, b"567");
+
+        assert!(blob.seek(SeekFrom::Current(1)).is_err());
+        assert!(blob.seek(SeekFrom::End(-9)).is_err());
+        assert_eq!(blob.close(), SqlitePrimaryResult::Ok);
+    }
+
+    #[test]
+    fn read_only_blob_rejects_writes() {
+        let db = open();
+
+        let mut blob = db
+            .blob_open(DatabaseName::Main, "t", "data", 2, true)
+            .unwrap();
+        let mut content = Vec::new();
+        blob.read_to_end(&mut content).unwrap();
+        assert_eq!(content, [1, 2]);
+
+        blob.seek(SeekFrom::Start(0)).unwrap();
+        assert!(blob.write(b"x").is_err());
+    }
+
+    #[test]
+    fn blob_open_fails_for_missing_rows() {
+        let db = open();
+
+        assert!(db
+            .blob_open(DatabaseName::Main, "t", "data", 3, true)
+            .is_err());
+        assert!(db
+            .blob_open(DatabaseName::Main, "t", "missing", 1, true)
+            .is_err());
+
+        let mut blob = db
+            .blob_open(DatabaseName::Main, "t", "data", 2, true)
+            .unwrap();
+        assert_ne!(blob.reopen(3), SqlitePrimaryResult::Ok);
+    }
+}
@@ -0,0 +1,170 @@
+//! This module contains process-wide configuration functions of SQLite.
+//!
+//! # Warning
+//! SQLite can only be configured before it's initialized or after it's shut
+//! down. Opening a database initializes SQLite automatically, so these
+//! functions should be called at the very beginning of the program. Otherwise
+//! they return `SqlitePrimaryResult::Misuse`.
+
+#![forbid(missing_docs)]
+
+use std::{
+    ffi::CStr,
+    os,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    sync::RwLock,
+};
+
+use crate::{
+    bindings::{sqlite3_config, sqlite3_initialize, sqlite3_shutdown},
+    prelude::*,
+};
+
+type LogFn = Box<dyn Fn(i32, &str) + Send + Sync>;
+
+/// Rust side of the SQLITE_CONFIG_LOG callback.
+static LOG_CALLBACK: RwLock<Option<LogFn>> = RwLock::new(None);
+
+/// Threading mode of SQLite.
+#[repr(i32)]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ThreadingMode {
+    /// All mutexes are disabled, SQLite is unsafe to use in more than a single thread at once.
+    SingleThread = 1,
+    /// SQLite can be used by multiple threads as long as no database connection
+    /// or any object derived from it is used in two or more threads at the same time.
+    MultiThread = 2,
+    /// SQLite can be safely used by multiple threads with no restriction.
+    Serialized = 3,
+}
+
+/// Initializes SQLite library. It's called automatically by `Database::open`,
+/// calling it more than once is a harmless no-op.
+///
+/// # Usage
+/// let status = min_sqlite3_sys::config::initialize();
+///
+/// if status != SqlitePrimaryResult::Ok {
+///     ...
+/// }
+/// ```
+#[inline]
+pub fn initialize() -> SqlitePrimaryResult {
+    unsafe { SqlitePrimaryResult::from(sqlite3_initialize()) }
+}
+
+/// Deallocates the resources that were allocated by `initialize`. All the
+/// database connections must be closed before calling this function.
+///
+/// # Usage
+/// let status = min_sqlite3_sys::config::shutdown();
+///
+/// if status != SqlitePrimaryResult::Ok {
+///     ...
+/// }
+/// ```
+#[inline]
+pub fn shutdown() -> SqlitePrimaryResult {
+    unsafe { SqlitePrimaryResult::from(sqlite3_shutdown()) }
+}
+
+/// Sets the threading mode of SQLite. Only available if SQLite is compiled
+/// with thread-safety enabled.
+///
+/// # Usage
+/// let status = min_sqlite3_sys::config::set_threading_mode(ThreadingMode::Serialized);
+///
+/// if status != SqlitePrimaryResult::Ok {
+///     ...
+/// }
+/// ```
+#[inline]
+pub fn set_threading_mode(mode: ThreadingMode) -> SqlitePrimaryResult {
+    unsafe { SqlitePrimaryResult::from(sqlite3_config(mode as os::raw::c_int)) }
+}
+
+/// Enables or disables the collection of memory allocation statistics.
+///
+/// # Usage
+/// let status = min_sqlite3_sys::config::set_memory_status(false);
+///
+/// if status != SqlitePrimaryResult::Ok {
+///     ...
+/// }
+/// ```
+#[inline]
+pub fn set_memory_status(enabled: bool) -> SqlitePrimaryResult {
+    unsafe {
+        SqlitePrimaryResult::from(sqlite3_config(
+            9, // SQLITE_CONFIG_MEMSTATUS
+            enabled as os::raw::c_int,
+        ))
+    }
+}
+
+/// Routes the error log of SQLite (SQLITE_CONFIG_LOG) into the given callback.
+/// The callback receives the extended result code and the log message, the
+/// primary result can be obtained by `SqlitePrimaryResult::from(code & 0xff)`.
+/// Passing `None` disables the logging.
+///
+/// # Warning
+/// The callback may be invoked from any thread and must not call
+/// any SQLite function.
+///
+/// # Usage
+/// let status = min_sqlite3_sys::config::set_log_callback(Some(|code: i32, message: &str| {
+///     eprintln!("sqlite ({}): {}", code, message);
+/// }));
+///
+/// if status != SqlitePrimaryResult::Ok {
+///     ...
+/// }
+/// ```
+pub fn set_log_callback<F>(callback: Option<F>) -> SqlitePrimaryResult
+where
+    F: Fn(i32, &str) + Send + Sync + 'static,
+{
+    let trampoline: Option<
+        unsafe extern "C" fn(*mut os::raw::c_void, os::raw::c_int, *const os::raw::c_char),
+    > = match callback {
+        Some(_) => Some(log_trampoline),
+        None => None,
+    };
+
+    // The lock must not be held during `sqlite3_config`, since
+    // SQLite may log the misuse of it through the trampoline.
+    let status = unsafe {
+        SqlitePrimaryResult::from(sqlite3_config(
+            16, // SQLITE_CONFIG_LOG
+            trampoline,
+            ptr::null_mut::<os::raw::c_void>(),
+        ))
+    };
+
+    if status == SqlitePrimaryResult::Ok {
+        let mut current = LOG_CALLBACK.write().unwrap_or_else(|e| e.into_inner());
+        *current = callback.map(|t| Box::new(t) as LogFn);
+    }
+
+    status
+}
+
+unsafe extern "C" fn log_trampoline(
+    _ctx: *mut os::raw::c_void,
+    code: os::raw::c_int,
+    message: *const os::raw::c_char,
+) {
+    let message = if message.is_null() {
+        ""
+    } else {
+        CStr::from_ptr(message).to_str().unwrap_or_default()
+    };
+
+    if let Ok(callback) = LOG_CALLBACK.read() {
+        if let Some(callback) = callback.as_ref() {
+            // unwinding into C is undefined behavior
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| callback(code, message)));
+        }
+    }
+}
@@ -3,12 +3,28 @@
 
 #![forbid(missing_docs)]
 
-use std::{ffi::CString, os::unix::prelude::OsStrExt, path::Path, ptr};
+use std::{
+    ffi::CString,
+    ops::{BitOr, BitOrAssign},
+    os::unix::prelude::OsStrExt,
+    path::Path,
+    ptr,
+    sync::{Arc, Mutex},
+};
 
 use crate::{
-    bindings::{sqlite3_close, sqlite3_open},
+    authorizer::AuthorizerFn,
+    bindings::{
+        sqlite3_close, sqlite3_close_v2, sqlite3_next_stmt, sqlite3_open, sqlite3_open_v2,
+        sqlite3_preupdate_hook, sqlite3_progress_handler, sqlite3_set_authorizer, sqlite3_trace_v2,
+        sqlite3_wal_hook,
+    },
     ehandle::MinSqliteWrapperError,
+    hooks::PreupdateHookFn,
+    interrupt::{InterruptTarget, ProgressHandlerFn},
     prelude::*,
+    trace::TraceFn,
+    wal::WalHookFn,
 };
 
 unsafe impl Send for Database {}
@@ -19,6 +35,178 @@ unsafe impl Sync for Database {}
 pub struct Database {
     /// Binded pointer of the sqlite3 instance.
     pub(crate) rp: *mut crate::bindings::sqlite3,
+    /// Registered preupdate hook, kept alive until it's replaced or the connection is closed.
+    pub(crate) preupdate_hook: Option<Box<PreupdateHookFn>>,
+    /// Registered authorizer, kept alive until it's replaced or the connection is closed.
+    pub(crate) authorizer: Option<Box<AuthorizerFn>>,
+    /// Registered progress handler, kept alive until it's replaced or the connection is closed.
+    pub(crate) progress_handler: Option<Box<ProgressHandlerFn>>,
+    /// Connection pointer shared with the `InterruptHandle`s.
+    pub(crate) interrupt_target: Arc<InterruptTarget>,
+    /// Registered trace callback, kept alive until it's replaced or the connection is closed.
+    pub(crate) trace: Option<Box<TraceFn>>,
+    /// Registered WAL hook, kept alive until it's replaced or the connection is closed.
+    pub(crate) wal_hook: Option<Box<WalHookFn>>,
+    /// Number of alive `LoadExtensionGuard`s, extension loading is enabled while it's not zero.
+    /// It's locked while extension loading is switched, so that guards that are created and
+    /// dropped concurrently can't leave it in the wrong state.
+    pub(crate) load_extension_guards: Mutex<usize>,
+    /// Whether the connection is opened with `OpenFlags::URI`, so that SQLite interprets the
+    /// filenames of attached databases as URIs.
+    pub(crate) uri_filenames: bool,
+}
+
+impl Database {
+    /// Wraps the connection handle `rp`, which is closed when the
+    /// `Database` is closed or dropped.
+    #[inline]
+    pub(crate) fn from_handle(rp: *mut crate::bindings::sqlite3) -> Self {
+        Database {
+            rp,
+            preupdate_hook: None,
+            authorizer: None,
+            progress_handler: None,
+            interrupt_target: InterruptTarget::new(rp),
+            trace: None,
+            wal_hook: None,
+            load_extension_guards: Mutex::new(0),
+            uri_filenames: false,
+        }
+    }
+
+    /// Unregisters the stored callbacks from SQLite before they're dropped,
+    /// and closes the connection with `sqlite3_close_v2`. Unfinalized
+    /// statements keep the connection alive until they're finalized, so
+    /// SQLite must not call into the freed callbacks from them.
+    ///
+    /// The trace callback stays registered through the close, so that it
+    /// receives `TraceEvent::Close`, unless unfinalized statements defer
+    /// the close.
+    fn release(&mut self) -> SqlitePrimaryResult {
+        self.interrupt_target.detach();
+        if self.rp.is_null() {
+            return SqlitePrimaryResult::Ok;
+        }
+
+        unsafe {
+            if self.preupdate_hook.is_some() {
+                sqlite3_preupdate_hook(self.rp, None, ptr::null_mut());
+            }
+            if self.authorizer.is_some() {
+                sqlite3_set_authorizer(self.rp, None, ptr::null_mut());
+            }
+            if self.progress_handler.is_some() {
+                sqlite3_progress_handler(self.rp, 0, None, ptr::null_mut());
+            }
+            if self.trace.is_some() && !sqlite3_next_stmt(self.rp, ptr::null_mut()).is_null() {
+                sqlite3_trace_v2(self.rp, 0, None, ptr::null_mut());
+            }
+            if self.wal_hook.is_some() {
+                sqlite3_wal_hook(self.rp, None, ptr::null_mut());
+            }
+        }
+
+        self.preupdate_hook = None;
+        self.authorizer = None;
+        self.progress_handler = None;
+        self.wal_hook = None;
+
+        let status = unsafe { SqlitePrimaryResult::from(sqlite3_close_v2(self.rp)) };
+        self.trace = None;
+        // so that `Drop` doesn't try to close the same connection again.
+        self.rp = ptr::null_mut();
+        status
+    }
+}
+
+/// Flags that specify how a database file is opened.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct OpenFlags(i32);
+
+impl OpenFlags {
+    /// Database is opened read-only.
+    pub const READ_ONLY: OpenFlags = OpenFlags(0x00000001);
+    /// Database is opened for reading and writing if possible.
+    pub const READ_WRITE: OpenFlags = OpenFlags(0x00000002);
+    /// Database is created if it doesn't exist. Must be combined with `READ_WRITE`.
+    pub const CREATE: OpenFlags = OpenFlags(0x00000004);
+    /// Filename is interpreted as URI.
+    pub const URI: OpenFlags = OpenFlags(0x00000040);
+    /// Database is opened as in-memory database.
+    pub const MEMORY: OpenFlags = OpenFlags(0x00000080);
+    /// Connection uses the multi-thread threading mode.
+    pub const NO_MUTEX: OpenFlags = OpenFlags(0x00008000);
+    /// Connection uses the serialized threading mode.
+    pub const FULL_MUTEX: OpenFlags = OpenFlags(0x00010000);
+    /// Database uses the shared cache.
+    pub const SHARED_CACHE: OpenFlags = OpenFlags(0x00020000);
+    /// Database uses a private cache.
+    pub const PRIVATE_CACHE: OpenFlags = OpenFlags(0x00040000);
+    /// Database filename is not allowed to be a symbolic link.
+    pub const NO_FOLLOW: OpenFlags = OpenFlags(0x01000000);
+
+    /// VFS only, the file is deleted when it's closed.
+    pub const DELETE_ON_CLOSE: OpenFlags = OpenFlags(0x00000008);
+    /// VFS only, the file is always created, opening fails if it exists.
+    pub const EXCLUSIVE: OpenFlags = OpenFlags(0x00000010);
+    /// VFS only, the file is a main database.
+    pub const MAIN_DB: OpenFlags = OpenFlags(0x00000100);
+    /// VFS only, the file is a temporary database.
+    pub const TEMP_DB: OpenFlags = OpenFlags(0x00000200);
+    /// VFS only, the file is a transient database.
+    pub const TRANSIENT_DB: OpenFlags = OpenFlags(0x00000400);
+    /// VFS only, the file is a rollback journal of a main database.
+    pub const MAIN_JOURNAL: OpenFlags = OpenFlags(0x00000800);
+    /// VFS only, the file is a rollback journal of a temporary database.
+    pub const TEMP_JOURNAL: OpenFlags = OpenFlags(0x00001000);
+    /// VFS only, the file is a statement journal.
+    pub const SUBJOURNAL: OpenFlags = OpenFlags(0x00002000);
+    /// VFS only, the file is a super-journal of a multi-database transaction.
+    pub const SUPER_JOURNAL: OpenFlags = OpenFlags(0x00004000);
+    /// VFS only, the file is a write-ahead log.
+    pub const WAL: OpenFlags = OpenFlags(0x00080000);
+
+    /// Creates the flags from their raw value.
+    #[inline]
+    pub fn from_bits(bits: i32) -> Self {
+        OpenFlags(bits)
+    }
+
+    /// Returns `true` if all of the flags in `other` are contained in `self`.
+    #[inline]
+    pub fn contains(&self, other: OpenFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the raw value of the flags.
+    #[inline]
+    pub fn bits(&self) -> i32 {
+        self.0
+    }
+}
+
+impl Default for OpenFlags {
+    /// Same as the flags of `Database::open`, `READ_WRITE | CREATE`.
+    #[inline]
+    fn default() -> Self {
+        OpenFlags::READ_WRITE | OpenFlags::CREATE
+    }
+}
+
+impl BitOr for OpenFlags {
+    type Output = OpenFlags;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self::Output {
+        OpenFlags(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for OpenFlags {
+    #[inline]
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
 }
 
 /// Specifies the core operations of the SQLite connection.
@@ -40,9 +228,11 @@ pub trait Connection<'a> {
         Self: Sized,
         T: AsRef<Path>;
 
-    /// The sqlite3_close() is destructor for the sqlite3 object. Returns
+    /// The sqlite3_close_v2() is destructor for the sqlite3 object. Returns
     /// SqlitePrimaryResult::Ok if the sqlite3 object is successfully destroyed
-    /// and all associated resources are deallocated.
+    /// and all associated resources are deallocated. If statements of the
+    /// connection aren't finalized yet, the registered hooks are removed and
+    /// the connection is closed when the last statement is finalized.
     ///
     /// # Usage
     /// let db_path = Path::new("./example.db");
@@ -54,6 +244,27 @@ pub trait Connection<'a> {
     /// }
     /// ```
     fn close(self) -> SqlitePrimaryResult;
+
+    /// Opens a database with the given `flags`, using the VFS registered as
+    /// `vfs` or the default VFS if `None`. Unlike `open`, it fails if the
+    /// database can't be opened.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open_with_flags(
+    ///     db_path,
+    ///     OpenFlags::READ_ONLY,
+    ///     Some("memory"),
+    /// ).unwrap();
+    /// ```
+    fn open_with_flags<T>(
+        path: T,
+        flags: OpenFlags,
+        vfs: Option<&str>,
+    ) -> Result<Self, MinSqliteWrapperError<'a>>
+    where
+        Self: Sized,
+        T: AsRef<Path>;
 }
 
 impl<'a> Connection<'a> for Database {
@@ -68,17 +279,52 @@ impl<'a> Connection<'a> for Database {
             sqlite3_open(path.as_ptr(), &mut rp);
         }
 
-        Ok(Database { rp })
+        Ok(Database::from_handle(rp))
+    }
+
+    #[inline]
+    fn close(mut self) -> SqlitePrimaryResult {
+        self.release()
     }
 
-    fn close(self) -> SqlitePrimaryResult {
-        sqlite_close(self.rp)
+    fn open_with_flags<T>(
+        db_path: T,
+        flags: OpenFlags,
+        vfs: Option<&str>,
+    ) -> Result<Self, MinSqliteWrapperError<'a>>
+    where
+        Self: Sized,
+        T: AsRef<Path>,
+    {
+        let mut rp = ptr::null_mut();
+        let path = CString::new(db_path.as_ref().as_os_str().as_bytes())?;
+        let vfs = vfs.map(CString::new).transpose()?;
+
+        let status = unsafe {
+            SqlitePrimaryResult::from(sqlite3_open_v2(
+                path.as_ptr(),
+                &mut rp,
+                flags.bits(),
+                vfs.as_ref().map_or(ptr::null(), |vfs| vfs.as_ptr()),
+            ))
+        };
+
+        if status != SqlitePrimaryResult::Ok {
+            let error = MinSqliteWrapperError::from_connection(rp);
+            // a handle is returned even if opening fails, unless it's out of memory
+            sqlite_close(rp);
+            return Err(error);
+        }
+
+        let mut db = Database::from_handle(rp);
+        db.uri_filenames = flags.contains(OpenFlags::URI);
+        Ok(db)
     }
 }
 
 impl Drop for Database {
     fn drop(&mut self) {
-        sqlite_close(self.rp);
+        self.release();
     }
 }
 
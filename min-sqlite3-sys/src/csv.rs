@@ -0,0 +1,796 @@
+//! This module contains the CSV virtual table and the functions to
+//! import and export CSV data.
+
+#![forbid(missing_docs)]
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{attach::quote_identifier, ehandle::MinSqliteWrapperError, prelude::*};
+
+/// Format of the CSV data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// Character that separates the fields, `,` by default.
+    pub delimiter: u8,
+    /// Character that quotes the fields, `"` by default.
+    pub quote: u8,
+    /// Whether the first record holds the column names, `true` by default.
+    pub header: bool,
+    /// Whether empty fields that aren't quoted stand for NULL, `false` by
+    /// default. Empty text is then exported as a quoted field.
+    pub empty_as_null: bool,
+    /// Whether the columns of the tables that are created by the import are
+    /// declared as NUMERIC, so that numbers are stored as INTEGER or REAL.
+    /// Otherwise they are declared as TEXT. `true` by default.
+    pub infer_types: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            quote: b'"',
+            header: true,
+            empty_as_null: false,
+            infer_types: true,
+        }
+    }
+}
+
+/// Field of a CSV record.
+struct CsvField {
+    text: String,
+    quoted: bool,
+}
+
+/// Streaming reader of RFC 4180 records. Quoted fields may contain
+/// delimiters, doubled quotes and line breaks. Blank lines are skipped.
+struct CsvReader<R: BufRead> {
+    input: R,
+    delimiter: u8,
+    quote: u8,
+    line: u64,
+    record_line: u64,
+    started: bool,
+}
+
+/// UTF-8 byte order mark that is written by some spreadsheet tools.
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+impl<R: BufRead> CsvReader<R> {
+    fn new(input: R, delimiter: u8, quote: u8) -> Self {
+        CsvReader {
+            input,
+            delimiter,
+            quote,
+            line: 1,
+            record_line: 1,
+            started: false,
+        }
+    }
+
+    #[inline]
+    fn peek_byte(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.input.fill_buf()?.first().copied())
+    }
+
+    #[inline]
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        let byte = self.peek_byte()?;
+        if byte.is_some() {
+            self.input.consume(1);
+        }
+        Ok(byte)
+    }
+
+    /// Consumes `\n` of a `\r\n` line break.
+    #[inline]
+    fn end_line(&mut self) -> io::Result<()> {
+        if self.peek_byte()? == Some(b'\n') {
+            self.input.consume(1);
+        }
+        self.line += 1;
+        Ok(())
+    }
+
+    fn error(&self, line: u64, reason: &str) -> MinSqliteWrapperError<'static> {
+        MinSqliteWrapperError {
+            kind: "sqlite3:Csv",
+            reason: format!("line {}: {}", line, reason),
+        }
+    }
+
+    fn push_field(
+        &self,
+        record: &mut Vec<CsvField>,
+        field: &mut Vec<u8>,
+        quoted: &mut bool,
+        line: u64,
+    ) -> Result<(), MinSqliteWrapperError<'static>> {
+        let text = String::from_utf8(std::mem::take(field))
+            .map_err(|_| self.error(line, "field is not valid UTF-8"))?;
+
+        record.push(CsvField {
+            text,
+            quoted: *quoted,
+        });
+        *quoted = false;
+        Ok(())
+    }
+
+    /// Reads the next record, `None` at the end of the input.
+    fn read_record(&mut self) -> Result<Option<Vec<CsvField>>, MinSqliteWrapperError<'static>> {
+        if !self.started {
+            self.started = true;
+            if self.input.fill_buf()?.starts_with(BOM) {
+                self.input.consume(BOM.len());
+            }
+        }
+
+        loop {
+            match self.peek_byte()? {
+                None => return Ok(None),
+                Some(b'\n') | Some(b'\r') => {
+                    self.input.consume(1);
+                    self.end_line()?;
+                }
+                Some(_) => break,
+            }
+        }
+
+        let line = self.line;
+        self.record_line = line;
+        let mut record = Vec::new();
+        let mut field = Vec::new();
+        let mut quoted = false;
+
+        loop {
+            match self.next_byte()? {
+                None => {
+                    self.push_field(&mut record, &mut field, &mut quoted, line)?;
+                    return Ok(Some(record));
+                }
+                Some(byte) if byte == self.quote && field.is_empty() && !quoted => {
+                    quoted = true;
+                    loop {
+                        match self.next_byte()? {
+                            None => return Err(self.error(line, "unterminated quoted field")),
+                            Some(byte) if byte == self.quote => {
+                                if self.peek_byte()? != Some(self.quote) {
+                                    break;
+                                }
+                                self.input.consume(1);
+                                field.push(byte);
+                            }
+                            Some(byte) => {
+                                if byte == b'\n' {
+                                    self.line += 1;
+                                }
+                                field.push(byte);
+                            }
+                        }
+                    }
+                }
+                Some(byte) if byte == self.delimiter => {
+                    self.push_field(&mut record, &mut field, &mut quoted, line)?;
+                }
+                Some(b'\n') => {
+                    self.line += 1;
+                    self.push_field(&mut record, &mut field, &mut quoted, line)?;
+                    return Ok(Some(record));
+                }
+                Some(b'\r') => {
+                    self.end_line()?;
+                    self.push_field(&mut record, &mut field, &mut quoted, line)?;
+                    return Ok(Some(record));
+                }
+                Some(byte) => field.push(byte),
+            }
+        }
+    }
+}
+
+/// Writes `field`, quoting it if it contains a special character or if
+/// `force_quote` is set.
+fn write_field<W: Write>(
+    writer: &mut W,
+    field: &[u8],
+    options: &CsvOptions,
+    force_quote: bool,
+) -> io::Result<()> {
+    let needs_quote = force_quote
+        || field
+            .iter()
+            .any(|&b| b == options.delimiter || b == options.quote || b == b'\n' || b == b'\r');
+
+    if !needs_quote {
+        return writer.write_all(field);
+    }
+
+    writer.write_all(&[options.quote])?;
+    for chunk in field.split_inclusive(|&b| b == options.quote) {
+        writer.write_all(chunk)?;
+        if chunk.last() == Some(&options.quote) {
+            writer.write_all(&[options.quote])?;
+        }
+    }
+    writer.write_all(&[options.quote])
+}
+
+/// Source of the CSV virtual table.
+#[derive(Clone)]
+enum CsvSource {
+    File(PathBuf),
+    Data(Arc<[u8]>),
+}
+
+impl CsvSource {
+    /// Opens the source for a new scan, skipping the header if `header`
+    /// is set.
+    fn reader(
+        &self,
+        header: bool,
+    ) -> Result<CsvReader<Box<dyn BufRead + Send>>, MinSqliteWrapperError<'static>> {
+        let input: Box<dyn BufRead + Send> = match self {
+            CsvSource::File(path) => Box::new(BufReader::new(File::open(path)?)),
+            CsvSource::Data(data) => Box::new(io::Cursor::new(data.clone())),
+        };
+
+        let mut reader = CsvReader::new(input, b',', b'"');
+        if header {
+            reader.read_record()?;
+        }
+        Ok(reader)
+    }
+}
+
+/// Table of the `csv` module.
+struct CsvTable {
+    source: CsvSource,
+    header: bool,
+}
+
+/// Cursor of the `csv` module.
+struct CsvCursor {
+    source: CsvSource,
+    header: bool,
+    reader: Option<CsvReader<Box<dyn BufRead + Send>>>,
+    record: Vec<CsvField>,
+    rowid: i64,
+    eof: bool,
+}
+
+/// Removes the quotes around a `CREATE VIRTUAL TABLE` argument.
+fn unquote_argument(value: &str) -> String {
+    let value = value.trim();
+    for quote in ['\'', '"'] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            let doubled = format!("{}{}", quote, quote);
+            return value[1..value.len() - 1].replace(&doubled, &quote.to_string());
+        }
+    }
+    value.to_owned()
+}
+
+fn csv_error(reason: String) -> MinSqliteWrapperError<'static> {
+    MinSqliteWrapperError {
+        kind: "sqlite3:Csv",
+        reason,
+    }
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, MinSqliteWrapperError<'static>> {
+    match value.to_ascii_lowercase().as_str() {
+        "yes" | "on" | "true" | "1" => Ok(true),
+        "no" | "off" | "false" | "0" => Ok(false),
+        _ => Err(csv_error(format!("invalid boolean for {}: {}", key, value))),
+    }
+}
+
+impl VTab for CsvTable {
+    type Aux = ();
+    type Cursor = CsvCursor;
+
+    fn connect(
+        _aux: &Self::Aux,
+        args: &[&str],
+    ) -> Result<(String, Self), MinSqliteWrapperError<'static>> {
+        let mut source = None;
+        let mut header = false;
+        let mut columns = None;
+        let mut schema = None;
+
+        for argument in args.iter().skip(3) {
+            let (key, value) = argument
+                .split_once('=')
+                .ok_or_else(|| csv_error(format!("invalid argument: {}", argument)))?;
+            let key = key.trim().to_ascii_lowercase();
+            let value = unquote_argument(value);
+
+            match key.as_str() {
+                "filename" | "data" if source.is_some() => {
+                    return Err(csv_error(String::from(
+                        "only one of filename and data can be given",
+                    )))
+                }
+                "filename" => source = Some(CsvSource::File(PathBuf::from(value))),
+                "data" => source = Some(CsvSource::Data(Arc::from(value.into_bytes()))),
+                "header" => header = parse_bool(&key, &value)?,
+                "columns" => {
+                    columns = Some(
+                        value
+                            .parse::<usize>()
+                            .ok()
+                            .filter(|&count| count > 0)
+                            .ok_or_else(|| csv_error(format!("invalid columns: {}", value)))?,
+                    )
+                }
+                "schema" => schema = Some(value),
+                _ => return Err(csv_error(format!("unknown parameter: {}", key))),
+            }
+        }
+
+        let source =
+            source.ok_or_else(|| csv_error(String::from("filename or data must be given")))?;
+
+        let table = CsvTable { source, header };
+        if let Some(schema) = schema {
+            return Ok((schema, table));
+        }
+
+        let first = table
+            .source
+            .reader(false)?
+            .read_record()?
+            .unwrap_or_default();
+        let names: Vec<String> = (0..columns.unwrap_or(first.len()).max(1))
+            .map(|i| match first.get(i) {
+                Some(field) if header => quote_identifier(&field.text),
+                _ => format!("c{}", i),
+            })
+            .collect();
+
+        Ok((
+            format!("CREATE TABLE x({} TEXT)", names.join(" TEXT, ")),
+            table,
+        ))
+    }
+
+    fn best_index(&self, info: &mut IndexInfo) -> Result<(), MinSqliteWrapperError<'static>> {
+        info.set_estimated_cost(1_000_000.0);
+        Ok(())
+    }
+
+    fn open(&self) -> Result<Self::Cursor, MinSqliteWrapperError<'static>> {
+        Ok(CsvCursor {
+            source: self.source.clone(),
+            header: self.header,
+            reader: None,
+            record: Vec::new(),
+            rowid: 0,
+            eof: true,
+        })
+    }
+}
+
+impl VTabCursor for CsvCursor {
+    fn filter(
+        &mut self,
+        _idx_num: i32,
+        _idx_str: Option<&str>,
+        _args: &Values,
+    ) -> Result<(), MinSqliteWrapperError<'static>> {
+        self.reader = Some(self.source.reader(self.header)?);
+        self.rowid = 0;
+        self.eof = false;
+        self.next()
+    }
+
+    fn next(&mut self) -> Result<(), MinSqliteWrapperError<'static>> {
+        let record = match self.reader.as_mut() {
+            Some(reader) => reader.read_record()?,
+            None => None,
+        };
+
+        match record {
+            Some(record) => {
+                self.record = record;
+                self.rowid += 1;
+            }
+            None => self.eof = true,
+        }
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.eof
+    }
+
+    fn column(&self, i: usize) -> Result<Value, MinSqliteWrapperError<'static>> {
+        Ok(self
+            .record
+            .get(i)
+            .map_or(Value::Null, |field| Value::Text(field.text.clone())))
+    }
+
+    fn rowid(&self) -> Result<i64, MinSqliteWrapperError<'static>> {
+        Ok(self.rowid)
+    }
+}
+
+/// Specifies the CSV functions of the SQLite connection.
+pub trait CsvOperations {
+    /// Registers the `csv` virtual table module, which reads CSV data as a
+    /// table. Parameters of `CREATE VIRTUAL TABLE t USING csv(...)`:
+    /// - `filename=PATH` or `data=TEXT` as the source of the data.
+    /// - `header=yes|no` whether the first record holds the column names.
+    /// - `columns=N` number of columns, read from the first record by default.
+    /// - `schema=SQL` `CREATE TABLE` statement that declares the columns.
+    ///
+    /// Columns are named `c0`, `c1`, ... if there isn't any header. Values are
+    /// read as text, and the table is read-only.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// db.register_csv_module().unwrap();
+    ///
+    /// let statement = String::from(
+    ///     "CREATE VIRTUAL TABLE temp.packages USING csv(filename='packages.csv', header=yes);"
+    /// );
+    /// db.execute(statement, None::<Box<dyn FnOnce(SqlitePrimaryResult, String)>>).unwrap();
+    ///
+    /// db.close();
+    /// ```
+    fn register_csv_module<'a>(&self) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>;
+
+    /// Inserts the records of the CSV file at `path` into `table` and returns
+    /// the number of inserted rows. The table is created if it doesn't exist,
+    /// with the columns from the header or `c0`, `c1`, ... without header.
+    ///
+    /// Fields are bound as text, so that the type affinity of the columns
+    /// decides how they are stored. Rows with fewer fields are padded with
+    /// NULL. The import is done in a savepoint, nothing is inserted if any
+    /// of the records fail.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let rows = db.import_csv("packages.csv", "packages", &CsvOptions::default()).unwrap();
+    /// println!("{} packages are imported", rows);
+    ///
+    /// db.close();
+    /// ```
+    fn import_csv<'a, P: AsRef<Path>>(
+        &self,
+        path: P,
+        table: &str,
+        options: &CsvOptions,
+    ) -> Result<u64, MinSqliteWrapperError<'a>>;
+
+    /// Runs `query` and writes the rows it returns to `writer` as CSV,
+    /// with the column names as header if `options.header` is set. Returns
+    /// the number of written rows, header excluded.
+    ///
+    /// NULL is written as an empty field, and blobs are written as raw bytes.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let file = File::create("packages.csv").unwrap();
+    /// db.export_csv("SELECT name, version FROM packages;", file, &CsvOptions::default()).unwrap();
+    ///
+    /// db.close();
+    /// ```
+    fn export_csv<'a, W: Write>(
+        &self,
+        query: &str,
+        writer: W,
+        options: &CsvOptions,
+    ) -> Result<u64, MinSqliteWrapperError<'a>>;
+}
+
+impl Database {
+    fn insert_csv<R: BufRead>(
+        &self,
+        reader: &mut CsvReader<R>,
+        table: &str,
+        options: &CsvOptions,
+    ) -> Result<u64, MinSqliteWrapperError<'static>> {
+        let first = match reader.read_record()? {
+            Some(record) => record,
+            None => return Ok(0),
+        };
+
+        let (columns, mut pending) = if options.header {
+            let columns: Vec<String> = first
+                .iter()
+                .map(|field| quote_identifier(&field.text))
+                .collect();
+            (columns, None)
+        } else {
+            let columns = (0..first.len()).map(|i| format!("c{}", i)).collect();
+            (columns, Some(first))
+        };
+
+        let declared_type = if options.infer_types {
+            "NUMERIC"
+        } else {
+            "TEXT"
+        };
+        let table = quote_identifier(table);
+
+        self.execute_checked(&format!(
+            "CREATE TABLE IF NOT EXISTS {}({} {});",
+            table,
+            columns.join(&format!(" {}, ", declared_type)),
+            declared_type
+        ))?;
+
+        let parameters = vec!["?"; columns.len()].join(", ");
+        let statement = if options.header {
+            format!(
+                "INSERT INTO {}({}) VALUES({});",
+                table,
+                columns.join(", "),
+                parameters
+            )
+        } else {
+            format!("INSERT INTO {} VALUES({});", table, parameters)
+        };
+        let mut sql = self.prepare_checked(&statement)?;
+
+        let mut rows = 0;
+        loop {
+            let record = match pending.take() {
+                Some(record) => record,
+                None => match reader.read_record()? {
+                    Some(record) => record,
+                    None => return Ok(rows),
+                },
+            };
+
+            if record.len() > columns.len() {
+                return Err(reader.error(
+                    reader.record_line,
+                    &format!("expected {} fields, found {}", columns.len(), record.len()),
+                ));
+            }
+
+            for i in 0..columns.len() {
+                let value = match record.get(i) {
+                    Some(field)
+                        if options.empty_as_null && field.text.is_empty() && !field.quoted =>
+                    {
+                        Value::Null
+                    }
+                    Some(field) => Value::Text(field.text.clone()),
+                    None => Value::Null,
+                };
+
+                let status = sql.bind_val(i + 1, value);
+                if status != SqlitePrimaryResult::Ok {
+                    return Err(status.into());
+                }
+            }
+
+            if sql.execute_prepared() != PreparedStatementStatus::Done {
+                return Err(MinSqliteWrapperError::from_connection(self.rp));
+            }
+            sql.reset();
+            rows += 1;
+        }
+    }
+}
+
+impl CsvOperations for Database {
+    #[inline]
+    fn register_csv_module<'a>(&self) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>> {
+        self.create_module("csv", Module::<CsvTable>::new(()))
+    }
+
+    fn import_csv<'a, P: AsRef<Path>>(
+        &self,
+        path: P,
+        table: &str,
+        options: &CsvOptions,
+    ) -> Result<u64, MinSqliteWrapperError<'a>> {
+        let file = File::open(path)?;
+        let mut reader = CsvReader::new(BufReader::new(file), options.delimiter, options.quote);
+
+        self.execute_checked("SAVEPOINT import_csv;")?;
+        match self.insert_csv(&mut reader, table, options) {
+            Ok(rows) => {
+                self.execute_checked("RELEASE import_csv;")?;
+                Ok(rows)
+            }
+            Err(error) => {
+                let _ = self.execute_checked("ROLLBACK TO import_csv; RELEASE import_csv;");
+                Err(error)
+            }
+        }
+    }
+
+    fn export_csv<'a, W: Write>(
+        &self,
+        query: &str,
+        writer: W,
+        options: &CsvOptions,
+    ) -> Result<u64, MinSqliteWrapperError<'a>> {
+        let mut writer = BufWriter::new(writer);
+        let mut sql = self.prepare_checked(query)?;
+        let count = sql.column_count();
+
+        if options.header {
+            for i in 0..count {
+                if i > 0 {
+                    writer.write_all(&[options.delimiter])?;
+                }
+                let name = sql.column_name(i).unwrap_or_default();
+                write_field(&mut writer, name.as_bytes(), options, false)?;
+            }
+            writer.write_all(b"\r\n")?;
+        }
+
+        let mut rows = 0;
+        loop {
+            match sql.execute_prepared() {
+                PreparedStatementStatus::FoundRow => {
+                    for i in 0..count {
+                        if i > 0 {
+                            writer.write_all(&[options.delimiter])?;
+                        }
+
+                        match sql.get_owned_data::<Value>(i)? {
+                            Value::Null => {}
+                            Value::Integer(t) => write!(writer, "{}", t)?,
+                            Value::Real(t) => write!(writer, "{:?}", t)?,
+                            Value::Text(t) => write_field(
+                                &mut writer,
+                                t.as_bytes(),
+                                options,
+                                options.empty_as_null && t.is_empty(),
+                            )?,
+                            Value::Blob(t) => write_field(&mut writer, &t, options, true)?,
+                        }
+                    }
+                    writer.write_all(b"\r\n")?;
+                    rows += 1;
+                }
+                PreparedStatementStatus::Done => break,
+                _ => return Err(MinSqliteWrapperError::from_connection(self.rp)),
+            }
+        }
+
+        writer.flush()?;
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use crate::{
+        prelude::*,
+        test_support::{remove_file, temp_path},
+    };
+
+    fn write_csv(name: &str, content: &str) -> PathBuf {
+        let path = temp_path(&format!("{}.csv", name));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn csv_is_imported_and_exported() {
+        let path = write_csv(
+            "import",
+            "name,version\r\ncore,2\r\n\"multi\nline, \"\"quoted\"\"\",3.5\r\n",
+        );
+        let db = Database::open(":memory:").unwrap();
+
+        assert_eq!(
+            db.import_csv(&path, "packages", &CsvOptions::default())
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            db.query_values("SELECT typeof(version) FROM packages;")
+                .unwrap(),
+            [
+                [Value::Text(String::from("integer"))],
+                [Value::Text(String::from("real"))]
+            ]
+        );
+
+        let mut output = vec![];
+        assert_eq!(
+            db.export_csv(
+                "SELECT name, version FROM packages;",
+                &mut output,
+                &CsvOptions::default()
+            )
+            .unwrap(),
+            2
+        );
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "name,version\r\ncore,2\r\n\"multi\nline, \"\"quoted\"\"\",3.5\r\n"
+        );
+
+        db.close();
+        remove_file(&path);
+    }
+
+    #[test]
+    fn failed_import_inserts_nothing() {
+        let path = write_csv(
+            "failed_import",
+            "name,version\r\ncore,2\r\nextra,1,field\r\n",
+        );
+        let db = Database::open(":memory:").unwrap();
+
+        let error = db
+            .import_csv(&path, "packages", &CsvOptions::default())
+            .unwrap_err();
+        assert!(
+            error.reason.contains("expected 2 fields"),
+            "{}",
+            error.reason
+        );
+        assert!(db.query_values("SELECT * FROM packages;").is_err());
+
+        assert!(db
+            .import_csv(
+                path.with_extension("missing"),
+                "packages",
+                &CsvOptions::default()
+            )
+            .is_err());
+        assert!(db
+            .export_csv("SELECT * FROM missing;", vec![], &CsvOptions::default())
+            .is_err());
+
+        db.close();
+        remove_file(&path);
+    }
+
+    #[test]
+    fn csv_module_reads_the_data() {
+        let db = Database::open(":memory:").unwrap();
+        db.register_csv_module().unwrap();
+
+        db.execute_checked(
+            "CREATE VIRTUAL TABLE temp.t USING csv(data='id,name\n1,core\n2,extra', header=yes);",
+        )
+        .unwrap();
+        assert_eq!(
+            db.query_values("SELECT name FROM t WHERE id = '2';")
+                .unwrap(),
+            [[Value::Text(String::from("extra"))]]
+        );
+
+        let error = db
+            .execute_checked("CREATE VIRTUAL TABLE temp.u USING csv(data='a', unknown=1);")
+            .unwrap_err();
+        assert!(
+            error.reason.contains("unknown parameter"),
+            "{}",
+            error.reason
+        );
+        assert!(db
+            .execute_checked("CREATE VIRTUAL TABLE temp.u USING csv(header=yes);")
+            .is_err());
+
+        db.close();
+    }
+}
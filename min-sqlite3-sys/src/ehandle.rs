@@ -1,4 +1,10 @@
-use std::{ffi::NulError, str::Utf8Error};
+use std::{
+    ffi::{CStr, NulError},
+    io,
+    str::Utf8Error,
+};
+
+use crate::bindings::{sqlite3, sqlite3_errcode, sqlite3_errmsg, SqlitePrimaryResult};
 
 /// Error type that covers all kinds of errors
 /// that might occur on some of the wrapped functions.
@@ -6,7 +12,8 @@ use std::{ffi::NulError, str::Utf8Error};
 /// # Warning
 /// This type isn't for SQL errors. In order to deal with SQL
 /// errors, consider checking `SqlPrimaryResult` enum and
-/// callback functions.
+/// callback functions. Only the functions that return a value
+/// instead of a status wrap the `SqlPrimaryResult` with this type.
 #[derive(Debug, Clone)]
 pub struct MinSqliteWrapperError<'a> {
     /// defines type of the error
@@ -32,3 +39,36 @@ impl<'a> From<Utf8Error> for MinSqliteWrapperError<'a> {
         }
     }
 }
+
+impl<'a> From<io::Error> for MinSqliteWrapperError<'a> {
+    fn from(error: io::Error) -> Self {
+        MinSqliteWrapperError {
+            kind: "std:io:Error",
+            reason: error.to_string(),
+        }
+    }
+}
+
+impl<'a> From<SqlitePrimaryResult> for MinSqliteWrapperError<'a> {
+    fn from(status: SqlitePrimaryResult) -> Self {
+        MinSqliteWrapperError {
+            kind: "sqlite3:SqlitePrimaryResult",
+            reason: format!("{:?}", status),
+        }
+    }
+}
+
+impl<'a> MinSqliteWrapperError<'a> {
+    /// Creates an error from the most recent failure of the database connection.
+    pub(crate) fn from_connection(rp: *mut sqlite3) -> Self {
+        unsafe {
+            let status = SqlitePrimaryResult::from(sqlite3_errcode(rp));
+            let message = CStr::from_ptr(sqlite3_errmsg(rp)).to_string_lossy();
+
+            MinSqliteWrapperError {
+                kind: "sqlite3:SqlitePrimaryResult",
+                reason: format!("{:?}: {}", status, message),
+            }
+        }
+    }
+}
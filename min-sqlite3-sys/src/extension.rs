@@ -0,0 +1,421 @@
+//! This module contains the guard and functions to load SQLite extensions
+//! at runtime. With the `loadable-extension` feature, it also contains the
+//! helpers to build SQLite extensions in Rust, that call SQLite through the
+//! routines of the program which loads them instead of linking
+//! `libmin_sqlite3_sys`.
+
+#![forbid(missing_docs)]
+
+use std::{
+    ffi::{CStr, CString},
+    os::{self, unix::prelude::OsStrExt},
+    path::Path,
+    ptr,
+    sync::MutexGuard,
+};
+
+use crate::{
+    bindings::{
+        sqlite3, sqlite3_db_config, sqlite3_free, sqlite3_load_extension,
+        SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION,
+    },
+    ehandle::MinSqliteWrapperError,
+    prelude::*,
+};
+
+/// Enables the extension loading of the connection for its lifetime, and
+/// disables it again when the last guard of the connection is dropped.
+///
+/// Extension loading is disabled by default, so that the SQL statements
+/// can't load arbitrary shared libraries. The guard only enables the C API,
+/// the `load_extension()` SQL function stays disabled.
+pub struct LoadExtensionGuard<'a> {
+    db: &'a Database,
+}
+
+/// Locks the number of alive `LoadExtensionGuard`s of `db`.
+#[inline]
+fn lock_guards(db: &Database) -> MutexGuard<'_, usize> {
+    db.load_extension_guards
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// Enables or disables `sqlite3_load_extension` on the connection `rp`
+/// with `SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION`, which unlike
+/// `sqlite3_enable_load_extension` leaves the SQL function disabled.
+#[inline]
+fn set_load_extension(rp: *mut sqlite3, enabled: bool) -> SqlitePrimaryResult {
+    unsafe {
+        SqlitePrimaryResult::from(sqlite3_db_config(
+            rp,
+            SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION,
+            enabled as os::raw::c_int,
+            ptr::null_mut::<os::raw::c_int>(),
+        ))
+    }
+}
+
+impl<'a> LoadExtensionGuard<'a> {
+    /// Enables the extension loading of `db`.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// {
+    ///     let _guard = LoadExtensionGuard::new(&db).unwrap();
+    ///     db.load_extension("/usr/lib/sqlite3/pcre.so", None).unwrap();
+    /// }
+    ///
+    /// db.close();
+    /// ```
+    pub fn new<'b>(db: &'a Database) -> Result<Self, MinSqliteWrapperError<'b>> {
+        let mut guards = lock_guards(db);
+        if *guards == 0 {
+            let status = set_load_extension(db.rp, true);
+            if status != SqlitePrimaryResult::Ok {
+                return Err(status.into());
+            }
+        }
+
+        *guards += 1;
+        Ok(LoadExtensionGuard { db })
+    }
+}
+
+impl<'a> Drop for LoadExtensionGuard<'a> {
+    fn drop(&mut self) {
+        let mut guards = lock_guards(self.db);
+        *guards -= 1;
+        if *guards == 0 {
+            set_load_extension(self.db.rp, false);
+        }
+    }
+}
+
+/// Specifies the extension loading functions of the SQLite connection.
+pub trait Extensions {
+    /// Loads the SQLite extension from the shared library at `path`, and
+    /// initializes it on the connection with `entry_point`. If `entry_point`
+    /// is `None`, SQLite derives it from the file name, e.g.
+    /// `sqlite3_pcre_init` for `pcre.so`, falling back to
+    /// `sqlite3_extension_init`.
+    ///
+    /// Fails without calling SQLite unless a `LoadExtensionGuard` of the
+    /// connection is alive.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let guard = LoadExtensionGuard::new(&db).unwrap();
+    /// db.load_extension("./libmy_extension.so", Some("sqlite3_myextension_init")).unwrap();
+    /// drop(guard);
+    ///
+    /// db.close();
+    /// ```
+    fn load_extension<'a, P: AsRef<Path>>(
+        &self,
+        path: P,
+        entry_point: Option<&str>,
+    ) -> Result<(), MinSqliteWrapperError<'a>>;
+}
+
+impl Extensions for Database {
+    fn load_extension<'a, P: AsRef<Path>>(
+        &self,
+        path: P,
+        entry_point: Option<&str>,
+    ) -> Result<(), MinSqliteWrapperError<'a>> {
+        if *lock_guards(self) == 0 {
+            return Err(MinSqliteWrapperError {
+                kind: "sqlite3:Extension",
+                reason: String::from("extension loading is not enabled by a LoadExtensionGuard"),
+            });
+        }
+
+        let path = CString::new(path.as_ref().as_os_str().as_bytes())?;
+        let entry_point = entry_point.map(CString::new).transpose()?;
+        let mut err_msg: *mut os::raw::c_char = ptr::null_mut();
+
+        let status = unsafe {
+            SqlitePrimaryResult::from(sqlite3_load_extension(
+                self.rp,
+                path.as_ptr(),
+                entry_point
+                    .as_ref()
+                    .map_or(ptr::null(), |entry| entry.as_ptr()),
+                &mut err_msg,
+            ))
+        };
+
+        if err_msg.is_null() {
+            return match status {
+                SqlitePrimaryResult::Ok => Ok(()),
+                status => Err(status.into()),
+            };
+        }
+
+        let reason = unsafe {
+            let reason = CStr::from_ptr(err_msg).to_string_lossy().into_owned();
+            sqlite3_free(err_msg as *mut os::raw::c_void);
+            reason
+        };
+
+        Err(MinSqliteWrapperError {
+            kind: "sqlite3:Extension",
+            reason,
+        })
+    }
+}
+
+#[cfg(feature = "loadable-extension")]
+pub use loadable::init_extension;
+
+#[cfg(feature = "loadable-extension")]
+mod loadable {
+    use std::{
+        mem::{self, ManuallyDrop},
+        os,
+        panic::{self, AssertUnwindSafe},
+        ptr,
+        sync::atomic::Ordering,
+    };
+
+    use crate::{
+        bindings::{sqlite3, sqlite3_api_routines, API_ROUTINES},
+        ehandle::MinSqliteWrapperError,
+        prelude::*,
+        vtab::sqlite_string,
+    };
+
+    /// Binder of SQLITE_ERROR from C source
+    const SQLITE_ERROR: os::raw::c_int = 1;
+
+    /// The oldest SQLite version that provides all the routines the crate
+    /// calls, 3.39.0 added `sqlite3_serialize` and `sqlite3_deserialize`.
+    const MIN_VERSION_NUMBER: os::raw::c_int = 3_039_000;
+
+    /// Copies `text` into memory that is allocated by `malloc` of `api`,
+    /// so that SQLite can free it.
+    unsafe fn api_string(api: &sqlite3_api_routines, text: &str) -> *mut os::raw::c_char {
+        type Malloc = unsafe extern "C" fn(os::raw::c_int) -> *mut os::raw::c_void;
+        let malloc = match api.malloc {
+            Some(malloc) => mem::transmute::<unsafe extern "C" fn(), Malloc>(malloc),
+            None => return ptr::null_mut(),
+        };
+
+        let data = malloc(text.len() as os::raw::c_int + 1) as *mut u8;
+        if data.is_null() {
+            return ptr::null_mut();
+        }
+
+        ptr::copy_nonoverlapping(text.as_ptr(), data, text.len());
+        *data.add(text.len()) = 0;
+        data as *mut os::raw::c_char
+    }
+
+    /// Returns the version number of the SQLite library of `api`.
+    unsafe fn version_number(api: &sqlite3_api_routines) -> os::raw::c_int {
+        type LibversionNumber = unsafe extern "C" fn() -> os::raw::c_int;
+        match api.libversion_number {
+            Some(routine) => mem::transmute::<unsafe extern "C" fn(), LibversionNumber>(routine)(),
+            None => 0,
+        }
+    }
+
+    /// Initializes the extension on the connection `db` with `init`, and
+    /// reports its error to SQLite. It's called from the entry point that
+    /// is generated by `extension_entry_point!`.
+    ///
+    /// Like `SQLITE_EXTENSION_INIT2`, it stores `api` so that the crate calls
+    /// SQLite through the routines of the loading library, e.g. the `sqlite3`
+    /// shell or a statically linked SQLite. The library must be at least
+    /// SQLite 3.39.0. The functions that aren't in `sqlite3_api_routines`
+    /// can't be called from an extension, so the preupdate hooks, sessions
+    /// and R*Tree query callbacks fail with `SqlitePrimaryResult::Misuse`.
+    ///
+    /// # Safety
+    /// Must only be called with the arguments that SQLite passes to the
+    /// entry point of the extension.
+    pub unsafe fn init_extension<F>(
+        db: *mut sqlite3,
+        err_msg: *mut *mut os::raw::c_char,
+        api: *const sqlite3_api_routines,
+        init: F,
+    ) -> os::raw::c_int
+    where
+        F: FnOnce(&Database) -> Result<(), MinSqliteWrapperError<'static>>,
+    {
+        let routines = match api.as_ref() {
+            Some(routines) => routines,
+            None => return SQLITE_ERROR,
+        };
+
+        // the routines added after the version of the library are beyond
+        // the end of `api`, so they must not be read
+        let version = version_number(routines);
+        if version < MIN_VERSION_NUMBER {
+            if !err_msg.is_null() {
+                let reason = format!(
+                    "extension requires SQLite {} or later, it's loaded by SQLite {}",
+                    MIN_VERSION_NUMBER, version
+                );
+                *err_msg = api_string(routines, &reason);
+            }
+            return SQLITE_ERROR;
+        }
+
+        API_ROUTINES.store(api as *mut sqlite3_api_routines, Ordering::Release);
+
+        // the connection is owned by the loading process, it must not be
+        // closed when the `Database` goes out of scope.
+        let db = ManuallyDrop::new(Database::from_handle(db));
+
+        // unwinding into C is undefined behavior
+        let result = panic::catch_unwind(AssertUnwindSafe(|| init(&db))).unwrap_or_else(|_| {
+            Err(MinSqliteWrapperError {
+                kind: "sqlite3:Extension",
+                reason: String::from("extension initialization panicked"),
+            })
+        });
+
+        match result {
+            Ok(()) => 0,
+            Err(error) => {
+                if !err_msg.is_null() {
+                    *err_msg = sqlite_string(&error.reason);
+                }
+                SQLITE_ERROR
+            }
+        }
+    }
+}
+
+/// Generates the entry point of a loadable SQLite extension, that runs
+/// the initialization function on the connection that loads it. The
+/// entry point is named `sqlite3_extension_init` unless a name is given.
+///
+/// The crate must be built as `cdylib` with the `loadable-extension`
+/// feature of `min-sqlite3-sys`, see `init_extension` for the SQLite
+/// functions that are available to the extension.
+///
+/// # Usage
+/// fn init(db: &Database) -> Result<(), MinSqliteWrapperError<'static>> {
+///     db.register_csv_module()?;
+///     Ok(())
+/// }
+///
+/// min_sqlite3_sys::extension_entry_point!(init);
+/// // or
+/// min_sqlite3_sys::extension_entry_point!(sqlite3_versions_init, init);
+/// ```
+#[cfg(feature = "loadable-extension")]
+#[macro_export]
+macro_rules! extension_entry_point {
+    ($init:path) => {
+        $crate::extension_entry_point!(sqlite3_extension_init, $init);
+    };
+    ($name:ident, $init:path) => {
+        /// Entry point of the extension, called by SQLite when it's loaded.
+        ///
+        /// # Safety
+        /// Must only be called by SQLite.
+        #[no_mangle]
+        pub unsafe extern "C" fn $name(
+            db: *mut $crate::bindings::sqlite3,
+            err_msg: *mut *mut ::std::os::raw::c_char,
+            api: *const $crate::bindings::sqlite3_api_routines,
+        ) -> ::std::os::raw::c_int {
+            $crate::extension::init_extension(db, err_msg, api, $init)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os;
+
+    use crate::{
+        bindings::{sqlite3_db_config, SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION},
+        prelude::*,
+    };
+
+    const MISSING: &str = "/nonexistent/libmissing_extension.so";
+
+    /// Returns `true` if `load_extension` passed the guard check and
+    /// reached the loader of SQLite.
+    fn reaches_loader(db: &Database) -> bool {
+        let error = db.load_extension(MISSING, None).unwrap_err();
+        !error.reason.contains("LoadExtensionGuard")
+    }
+
+    /// Returns whether SQLite has the extension loading enabled on `db`.
+    fn is_enabled(db: &Database) -> bool {
+        let mut enabled: os::raw::c_int = -1;
+        unsafe {
+            sqlite3_db_config(
+                db.rp,
+                SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION,
+                -1 as os::raw::c_int,
+                &mut enabled as *mut os::raw::c_int,
+            );
+        }
+        enabled == 1
+    }
+
+    #[test]
+    fn loading_requires_a_guard() {
+        let db = Database::open(":memory:").unwrap();
+
+        let error = db.load_extension(MISSING, None).unwrap_err();
+        assert_eq!(
+            error.reason,
+            "extension loading is not enabled by a LoadExtensionGuard"
+        );
+
+        let guard = LoadExtensionGuard::new(&db).unwrap();
+        let error = db
+            .load_extension(MISSING, Some("sqlite3_missing_init"))
+            .unwrap_err();
+        assert!(
+            error.reason.contains("libmissing_extension"),
+            "{}",
+            error.reason
+        );
+        drop(guard);
+
+        assert!(!reaches_loader(&db));
+    }
+
+    #[test]
+    fn guard_keeps_the_sql_function_disabled() {
+        let db = Database::open(":memory:").unwrap();
+        let _guard = LoadExtensionGuard::new(&db).unwrap();
+
+        let error = db
+            .query_values(&format!("SELECT load_extension('{}');", MISSING))
+            .unwrap_err();
+        assert!(error.reason.contains("not authorized"), "{}", error.reason);
+    }
+
+    #[test]
+    fn nested_guards_disable_loading_with_the_last_one() {
+        let db = Database::open(":memory:").unwrap();
+
+        let outer = LoadExtensionGuard::new(&db).unwrap();
+        let inner = LoadExtensionGuard::new(&db).unwrap();
+        drop(inner);
+        assert!(is_enabled(&db));
+        assert!(reaches_loader(&db));
+
+        drop(outer);
+        assert!(!is_enabled(&db));
+        assert!(!reaches_loader(&db));
+
+        let _again = LoadExtensionGuard::new(&db).unwrap();
+        assert!(is_enabled(&db));
+        assert!(reaches_loader(&db));
+    }
+}
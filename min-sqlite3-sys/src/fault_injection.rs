@@ -0,0 +1,590 @@
+//! This module contains a VFS that wraps the `unix` VFS and injects I/O
+//! faults by a script, so that the error handling and the recovery of the
+//! applications can be tested.
+
+#![forbid(missing_docs)]
+
+use std::{
+    collections::HashMap,
+    os,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use crate::{ehandle::MinSqliteWrapperError, prelude::*, vfs::VfsFileName};
+
+/// I/O operation of the files that faults are injected into.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum FaultOperation {
+    /// `VfsFile::read`
+    Read,
+    /// `VfsFile::write`
+    Write,
+    /// `VfsFile::sync`
+    Sync,
+    /// `VfsFile::lock`
+    Lock,
+}
+
+impl FaultOperation {
+    #[inline]
+    fn index(&self) -> usize {
+        match self {
+            FaultOperation::Read => 0,
+            FaultOperation::Write => 1,
+            FaultOperation::Sync => 2,
+            FaultOperation::Lock => 3,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum FaultAction {
+    Fail(SqlitePrimaryResult),
+    TruncateWrite(usize),
+    PowerLoss,
+}
+
+#[derive(Debug)]
+struct FaultRule {
+    operation: FaultOperation,
+    nth: usize,
+    action: FaultAction,
+}
+
+/// Writes of a file since its last sync.
+#[derive(Debug)]
+struct Unsynced {
+    /// Size of the file at its last sync.
+    synced_size: u64,
+    /// Offsets and the overwritten content of the writes and truncations.
+    undo: Vec<(u64, Vec<u8>)>,
+}
+
+#[derive(Debug, Default)]
+struct FaultState {
+    rules: Vec<FaultRule>,
+    counts: [usize; 4],
+    disk_space: Option<u64>,
+    powered_off: bool,
+    injected: usize,
+    unsynced: HashMap<String, Unsynced>,
+}
+
+impl Unsynced {
+    /// Reverts the writes of the file at `path` through the `unix` VFS.
+    /// Unlike a file that is opened by `std::fs`, it shares the file
+    /// descriptors of the files that the connections keep open, so closing
+    /// it doesn't release their POSIX locks.
+    fn revert(&self, vfs: &PassthroughVfs, path: &str) -> VfsResult<()> {
+        let raw = format!("{}\0\0\0", path);
+        let name = VfsFileName::new(raw.as_ptr() as *const os::raw::c_char, path);
+        let (mut file, _) = vfs.open(Some(name), OpenFlags::READ_WRITE | OpenFlags::MAIN_DB)?;
+
+        for (offset, content) in self.undo.iter().rev() {
+            file.write(content, *offset)?;
+        }
+        file.truncate(self.synced_size)
+    }
+}
+
+impl FaultState {
+    /// Reverts the unsynced writes of all files, and fails the I/O until
+    /// the power is restored.
+    fn lose_power(&mut self) {
+        if let Ok(vfs) = PassthroughVfs::new(Some("unix")) {
+            for (path, unsynced) in self.unsynced.drain() {
+                // deleted files stay deleted, since they can't be opened
+                let _ = unsynced.revert(&vfs, &path);
+            }
+        }
+
+        self.unsynced.clear();
+        self.powered_off = true;
+    }
+}
+
+/// Script of the faults that a `FaultVfs` injects. Clones share the same
+/// script, so it can be changed while the VFS is registered.
+///
+/// Operations are counted from 1 across all files of the VFS, and each
+/// fault is injected once. Temporary files are counted as well, but their
+/// unsynced writes aren't dropped on power loss.
+#[derive(Debug, Default, Clone)]
+pub struct FaultScript {
+    state: Arc<Mutex<FaultState>>,
+}
+
+impl FaultScript {
+    /// Creates a script without faults.
+    #[inline]
+    pub fn new() -> Self {
+        FaultScript::default()
+    }
+
+    #[inline]
+    fn state(&self) -> MutexGuard<'_, FaultState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[inline]
+    fn add_rule(self, operation: FaultOperation, nth: usize, action: FaultAction) -> Self {
+        self.state().rules.push(FaultRule {
+            operation,
+            nth,
+            action,
+        });
+        self
+    }
+
+    /// Fails the `nth` `operation` with `error`, e.g.
+    /// `SqlitePrimaryResult::IoErr` or `SqlitePrimaryResult::Other(266)`
+    /// for SQLITE_IOERR_READ.
+    #[inline]
+    pub fn fail(self, operation: FaultOperation, nth: usize, error: SqlitePrimaryResult) -> Self {
+        self.add_rule(operation, nth, FaultAction::Fail(error))
+    }
+
+    /// Writes only the first `len` bytes of the `nth` write, but reports
+    /// it as successful, like a torn write.
+    #[inline]
+    pub fn truncate_write(self, nth: usize, len: usize) -> Self {
+        self.add_rule(FaultOperation::Write, nth, FaultAction::TruncateWrite(len))
+    }
+
+    /// Simulates a power loss instead of the `nth` `operation`, see
+    /// `power_loss`.
+    #[inline]
+    pub fn power_loss_at(self, operation: FaultOperation, nth: usize) -> Self {
+        self.add_rule(operation, nth, FaultAction::PowerLoss)
+    }
+
+    /// Fails the writes with `SqlitePrimaryResult::Full` once the files
+    /// grew by `bytes` in total. Truncations don't free up space.
+    #[inline]
+    pub fn disk_full_after(self, bytes: u64) -> Self {
+        self.state().disk_space = Some(bytes);
+        self
+    }
+
+    /// Drops the data that is written since the last sync of each file,
+    /// and fails all I/O with `SqlitePrimaryResult::IoErr` until
+    /// `power_on` is called. Connections that were open must be closed,
+    /// reopening the database recovers it from the hot journal.
+    #[inline]
+    pub fn power_loss(&self) {
+        self.state().lose_power();
+    }
+
+    /// Restores the power after `power_loss`.
+    #[inline]
+    pub fn power_on(&self) {
+        self.state().powered_off = false;
+    }
+
+    /// Returns the number of the `operation`s so far.
+    #[inline]
+    pub fn count(&self, operation: FaultOperation) -> usize {
+        self.state().counts[operation.index()]
+    }
+
+    /// Returns the number of the faults that are injected so far.
+    #[inline]
+    pub fn injected(&self) -> usize {
+        self.state().injected
+    }
+
+    /// Removes the remaining faults and the disk limit, resets the
+    /// counters and restores the power. Unsynced writes are kept.
+    pub fn reset(&self) {
+        let mut state = self.state();
+        state.rules.clear();
+        state.counts = [0; 4];
+        state.disk_space = None;
+        state.powered_off = false;
+        state.injected = 0;
+    }
+
+    /// Fails if the power is off.
+    #[inline]
+    fn check_power(&self) -> VfsResult<()> {
+        if self.state().powered_off {
+            return Err(SqlitePrimaryResult::IoErr);
+        }
+
+        Ok(())
+    }
+
+    /// Counts the `operation` and injects its fault. Returns the length
+    /// that the write is truncated to.
+    fn next(&self, operation: FaultOperation) -> VfsResult<Option<usize>> {
+        let mut state = self.state();
+        if state.powered_off {
+            return Err(SqlitePrimaryResult::IoErr);
+        }
+
+        state.counts[operation.index()] += 1;
+        let count = state.counts[operation.index()];
+        let rule = match state
+            .rules
+            .iter()
+            .position(|rule| rule.operation == operation && rule.nth == count)
+        {
+            Some(position) => state.rules.remove(position),
+            None => return Ok(None),
+        };
+
+        state.injected += 1;
+        match rule.action {
+            FaultAction::Fail(error) => Err(error),
+            FaultAction::TruncateWrite(len) => Ok(Some(len)),
+            FaultAction::PowerLoss => {
+                state.lose_power();
+                Err(SqlitePrimaryResult::IoErr)
+            }
+        }
+    }
+
+    /// Takes `bytes` of the disk space.
+    fn allocate(&self, bytes: u64) -> VfsResult<()> {
+        let mut state = self.state();
+        match state.disk_space {
+            Some(space) if space < bytes => Err(SqlitePrimaryResult::Full),
+            Some(space) => {
+                state.disk_space = Some(space - bytes);
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Records the `content` at `offset` of `path` before it's changed.
+    fn record(&self, path: &str, size: u64, offset: u64, content: Vec<u8>) {
+        self.state()
+            .unsynced
+            .entry(path.to_owned())
+            .or_insert_with(|| Unsynced {
+                synced_size: size,
+                undo: Vec::new(),
+            })
+            .undo
+            .push((offset, content));
+    }
+
+    #[inline]
+    fn synced(&self, path: &str) {
+        self.state().unsynced.remove(path);
+    }
+}
+
+/// VFS that wraps the `unix` VFS and injects the faults of its
+/// `FaultScript`.
+///
+/// # Usage
+/// let script = FaultScript::new()
+///     .fail(FaultOperation::Write, 3, SqlitePrimaryResult::IoErr)
+///     .power_loss_at(FaultOperation::Sync, 2);
+///
+/// min_sqlite3_sys::vfs::register_vfs("faulty", FaultVfs::new(script.clone()).unwrap(), false).unwrap();
+///
+/// let db = Database::open_with_flags("packages.db", OpenFlags::default(), Some("faulty")).unwrap();
+/// // ...
+/// db.close();
+///
+/// script.power_on();
+/// ```
+pub struct FaultVfs {
+    inner: PassthroughVfs,
+    script: FaultScript,
+}
+
+impl FaultVfs {
+    /// Creates the VFS that injects the faults of `script`.
+    #[inline]
+    pub fn new<'a>(script: FaultScript) -> Result<Self, MinSqliteWrapperError<'a>> {
+        Ok(FaultVfs {
+            inner: PassthroughVfs::new(Some("unix"))?,
+            script,
+        })
+    }
+
+    /// Returns the script of the VFS.
+    #[inline]
+    pub fn script(&self) -> &FaultScript {
+        &self.script
+    }
+}
+
+/// File of `FaultVfs`.
+pub struct FaultFile {
+    inner: PassthroughFile,
+    /// Path of the file, `None` for the temporary files.
+    path: Option<String>,
+    script: FaultScript,
+}
+
+impl FaultFile {
+    /// Records the content of the file in `offset..end` before it's
+    /// overwritten or truncated.
+    fn record(&mut self, offset: u64, end: u64, size: u64) -> VfsResult<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let mut content = vec![0_u8; end.min(size).saturating_sub(offset) as usize];
+        if !content.is_empty() {
+            let read = self.inner.read(&mut content, offset)?;
+            content.truncate(read);
+        }
+
+        self.script.record(path, size, offset, content);
+        Ok(())
+    }
+}
+
+impl VfsFile for FaultFile {
+    fn read(&mut self, buf: &mut [u8], offset: u64) -> VfsResult<usize> {
+        self.script.next(FaultOperation::Read)?;
+        self.inner.read(buf, offset)
+    }
+
+    fn write(&mut self, buf: &[u8], offset: u64) -> VfsResult<()> {
+        let truncated = self.script.next(FaultOperation::Write)?;
+        let buf = match truncated {
+            Some(len) => &buf[..len.min(buf.len())],
+            None => buf,
+        };
+
+        let size = self.inner.file_size()?;
+        let end = offset + buf.len() as u64;
+        self.script.allocate(end.saturating_sub(size))?;
+
+        self.record(offset, end, size)?;
+        self.inner.write(buf, offset)
+    }
+
+    fn truncate(&mut self, size: u64) -> VfsResult<()> {
+        self.script.check_power()?;
+
+        let current = self.inner.file_size()?;
+        self.record(size, current, current)?;
+        self.inner.truncate(size)
+    }
+
+    fn sync(&mut self, flags: SyncFlags) -> VfsResult<()> {
+        self.script.next(FaultOperation::Sync)?;
+        self.inner.sync(flags)?;
+
+        if let Some(path) = &self.path {
+            self.script.synced(path);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn file_size(&mut self) -> VfsResult<u64> {
+        self.script.check_power()?;
+        self.inner.file_size()
+    }
+
+    #[inline]
+    fn lock(&mut self, level: LockLevel) -> VfsResult<()> {
+        self.script.next(FaultOperation::Lock)?;
+        self.inner.lock(level)
+    }
+
+    #[inline]
+    fn unlock(&mut self, level: LockLevel) -> VfsResult<()> {
+        self.inner.unlock(level)
+    }
+
+    #[inline]
+    fn check_reserved_lock(&mut self) -> VfsResult<bool> {
+        self.script.check_power()?;
+        self.inner.check_reserved_lock()
+    }
+
+    #[inline]
+    fn file_control(&mut self, op: i32, arg: *mut os::raw::c_void) -> VfsResult<()> {
+        self.inner.file_control(op, arg)
+    }
+
+    #[inline]
+    fn sector_size(&mut self) -> i32 {
+        self.inner.sector_size()
+    }
+
+    #[inline]
+    fn device_characteristics(&mut self) -> i32 {
+        self.inner.device_characteristics()
+    }
+
+    #[inline]
+    fn has_shared_memory(&self) -> bool {
+        self.inner.has_shared_memory()
+    }
+
+    #[inline]
+    fn shm_map(&mut self, region: usize, size: usize, extend: bool) -> VfsResult<*mut u8> {
+        self.script.check_power()?;
+        self.inner.shm_map(region, size, extend)
+    }
+
+    #[inline]
+    fn shm_lock(&mut self, offset: usize, n: usize, flags: i32) -> VfsResult<()> {
+        self.inner.shm_lock(offset, n, flags)
+    }
+
+    #[inline]
+    fn shm_barrier(&mut self) {
+        self.inner.shm_barrier()
+    }
+
+    #[inline]
+    fn shm_unmap(&mut self, delete: bool) -> VfsResult<()> {
+        self.inner.shm_unmap(delete)
+    }
+}
+
+impl Vfs for FaultVfs {
+    type File = FaultFile;
+
+    fn open(
+        &self,
+        name: Option<VfsFileName>,
+        flags: OpenFlags,
+    ) -> VfsResult<(FaultFile, OpenFlags)> {
+        self.script
+            .check_power()
+            .map_err(|_| SqlitePrimaryResult::CantOpen)?;
+
+        let path = name.map(|name| name.as_str().to_owned());
+        let (inner, flags) = self.inner.open(name, flags)?;
+
+        let file = FaultFile {
+            inner,
+            path,
+            script: self.script.clone(),
+        };
+
+        Ok((file, flags))
+    }
+
+    fn delete(&self, name: &str, sync_dir: bool) -> VfsResult<()> {
+        self.script.check_power()?;
+        self.inner.delete(name, sync_dir)?;
+        self.script.synced(name);
+        Ok(())
+    }
+
+    #[inline]
+    fn access(&self, name: &str, check: AccessCheck) -> VfsResult<bool> {
+        self.script.check_power()?;
+        self.inner.access(name, check)
+    }
+
+    #[inline]
+    fn full_pathname(&self, name: &str) -> VfsResult<String> {
+        self.inner.full_pathname(name)
+    }
+
+    #[inline]
+    fn max_pathname(&self) -> usize {
+        self.inner.max_pathname()
+    }
+
+    #[inline]
+    fn randomness(&self, buf: &mut [u8]) -> usize {
+        self.inner.randomness(buf)
+    }
+
+    #[inline]
+    fn sleep(&self, duration: Duration) -> Duration {
+        self.inner.sleep(duration)
+    }
+
+    #[inline]
+    fn current_time(&self) -> i64 {
+        self.inner.current_time()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_support::{remove_file, temp_path},
+        vfs::register_vfs,
+    };
+
+    fn count(db: &Database) -> Vec<Vec<Value>> {
+        db.query_values("SELECT count(*) FROM t;").unwrap()
+    }
+
+    #[test]
+    fn injected_faults_fail_once() {
+        let script = FaultScript::new().fail(FaultOperation::Write, 1, SqlitePrimaryResult::IoErr);
+        register_vfs(
+            "test_fault_write",
+            FaultVfs::new(script.clone()).unwrap(),
+            false,
+        )
+        .unwrap();
+        let path = temp_path("fault_write.db");
+
+        let db = Database::open_with_flags(&path, OpenFlags::default(), Some("test_fault_write"))
+            .unwrap();
+        let error = db.execute_checked("CREATE TABLE t(x);").unwrap_err();
+        assert!(error.reason.starts_with("IoErr"), "{}", error.reason);
+        assert_eq!(script.injected(), 1);
+
+        db.execute_checked("CREATE TABLE t(x); INSERT INTO t VALUES (1);")
+            .unwrap();
+        assert_eq!(count(&db), [[Value::Integer(1)]]);
+        assert!(script.count(FaultOperation::Write) > 1);
+
+        db.close();
+        remove_file(&path);
+    }
+
+    #[test]
+    fn power_loss_drops_the_unsynced_writes() {
+        let script = FaultScript::new();
+        register_vfs(
+            "test_fault_power",
+            FaultVfs::new(script.clone()).unwrap(),
+            false,
+        )
+        .unwrap();
+        let path = temp_path("fault_power.db");
+
+        let db = Database::open_with_flags(&path, OpenFlags::default(), Some("test_fault_power"))
+            .unwrap();
+        db.execute_checked("CREATE TABLE t(x); INSERT INTO t VALUES (1);")
+            .unwrap();
+        db.execute_checked("PRAGMA synchronous = OFF; INSERT INTO t VALUES (2);")
+            .unwrap();
+        assert_eq!(count(&db), [[Value::Integer(2)]]);
+
+        script.power_loss();
+        assert!(db.query_values("SELECT count(*) FROM t;").is_err());
+        db.close();
+        assert!(
+            Database::open_with_flags(&path, OpenFlags::default(), Some("test_fault_power"))
+                .is_err()
+        );
+
+        script.power_on();
+        let db = Database::open_with_flags(&path, OpenFlags::default(), Some("test_fault_power"))
+            .unwrap();
+        assert_eq!(count(&db), [[Value::Integer(1)]]);
+        assert_eq!(
+            db.query_values("PRAGMA integrity_check;").unwrap(),
+            [[Value::Text(String::from("ok"))]]
+        );
+
+        db.close();
+        remove_file(&path);
+    }
+}
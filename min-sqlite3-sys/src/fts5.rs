@@ -0,0 +1,807 @@
+//! This module contains data-types and functions for the full-text search
+//! of the FTS5 extension, and for registering FTS5 tokenizers.
+
+#![forbid(missing_docs)]
+#![allow(clippy::not_unsafe_ptr_arg_deref)] // same as operations module, `bind_val` is driven by `SqlStatement`.
+
+use std::{
+    ffi::{CStr, CString},
+    marker::PhantomData,
+    ops::Range,
+    os,
+    panic::{self, AssertUnwindSafe},
+    ptr, slice, str,
+};
+
+use crate::{
+    attach::quote_identifier,
+    bindings::{
+        fts5_api, fts5_tokenizer, sqlite3_bind_pointer, sqlite3_stmt, Fts5Tokenizer, XToken,
+    },
+    ehandle::MinSqliteWrapperError,
+    operations::ColumnCapabilities,
+    prelude::*,
+};
+
+/// Type tag of the pointer that receives `fts5_api` from `SELECT fts5(?)`.
+const FTS5_API_TYPE: &[u8] = b"fts5_api_ptr\0";
+
+/// Binder of FTS5_TOKENIZE_QUERY from C source
+const TOKENIZE_QUERY: os::raw::c_int = 0x0001;
+
+/// Binder of FTS5_TOKENIZE_PREFIX from C source
+const TOKENIZE_PREFIX: os::raw::c_int = 0x0002;
+
+/// Binder of FTS5_TOKENIZE_AUX from C source
+const TOKENIZE_AUX: os::raw::c_int = 0x0008;
+
+/// Binder of FTS5_TOKEN_COLOCATED from C source
+const TOKEN_COLOCATED: os::raw::c_int = 0x0001;
+
+/// Binder of SQLITE_ERROR from C source
+const SQLITE_ERROR: os::raw::c_int = 1;
+
+/// Detail level of the full-text index.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Fts5Detail {
+    /// Stores the columns and the offsets of the tokens, default of FTS5.
+    Full,
+    /// Stores only the columns of the tokens, phrase and NEAR queries are
+    /// not supported.
+    Column,
+    /// Stores only the rows of the tokens, column filters are not
+    /// supported either.
+    None,
+}
+
+impl Fts5Detail {
+    #[inline]
+    fn as_str(&self) -> &'static str {
+        match self {
+            Fts5Detail::Full => "full",
+            Fts5Detail::Column => "column",
+            Fts5Detail::None => "none",
+        }
+    }
+}
+
+/// Definition of an FTS5 table that is created with `create`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fts5Table {
+    name: String,
+    columns: Vec<String>,
+    options: Vec<(&'static str, String)>,
+}
+
+impl Fts5Table {
+    /// Creates the definition of the FTS5 table `name`.
+    #[inline]
+    pub fn new<N: Into<String>>(name: N) -> Self {
+        Fts5Table {
+            name: name.into(),
+            columns: Vec::new(),
+            options: Vec::new(),
+        }
+    }
+
+    /// Adds an indexed column.
+    #[inline]
+    pub fn column(mut self, name: &str) -> Self {
+        self.columns.push(quote_identifier(name));
+        self
+    }
+
+    /// Adds a column that is stored but not indexed, e.g. an id to join.
+    #[inline]
+    pub fn unindexed_column(mut self, name: &str) -> Self {
+        self.columns
+            .push(format!("{} UNINDEXED", quote_identifier(name)));
+        self
+    }
+
+    /// Sets the tokenizer and its arguments, e.g. `porter unicode61` or
+    /// the name of a tokenizer registered with `Fts5::register_tokenizer`.
+    #[inline]
+    pub fn tokenize(mut self, tokenizer: &str) -> Self {
+        self.options.push(("tokenize", tokenizer.to_owned()));
+        self
+    }
+
+    /// Builds prefix indexes for the given prefix lengths in characters,
+    /// which makes the prefix queries like `lod*` faster.
+    #[inline]
+    pub fn prefix(mut self, lengths: &[u32]) -> Self {
+        let lengths: Vec<String> = lengths.iter().map(u32::to_string).collect();
+        self.options.push(("prefix", lengths.join(" ")));
+        self
+    }
+
+    /// Indexes the rows of the `table` instead of storing a copy of them.
+    /// Pass an empty name for a contentless table.
+    #[inline]
+    pub fn content(mut self, table: &str) -> Self {
+        self.options.push(("content", table.to_owned()));
+        self
+    }
+
+    /// Sets the rowid column of the external content table.
+    #[inline]
+    pub fn content_rowid(mut self, column: &str) -> Self {
+        self.options.push(("content_rowid", column.to_owned()));
+        self
+    }
+
+    /// Sets the detail level of the index.
+    #[inline]
+    pub fn detail(mut self, detail: Fts5Detail) -> Self {
+        self.options.push(("detail", detail.as_str().to_owned()));
+        self
+    }
+
+    /// Returns the `CREATE VIRTUAL TABLE` statement of the table.
+    pub fn sql(&self) -> String {
+        let arguments: Vec<String> = self
+            .columns
+            .iter()
+            .cloned()
+            .chain(self.options.iter().map(|(key, value)| {
+                format!("{}={}", key, Value::Text(value.clone()).to_sql_literal())
+            }))
+            .collect();
+
+        format!(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS {} USING fts5({});",
+            quote_identifier(&self.name),
+            arguments.join(", ")
+        )
+    }
+
+    /// Creates the table if it doesn't exist.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// Fts5Table::new("package_search")
+    ///     .column("name")
+    ///     .column("description")
+    ///     .unindexed_column("package_id")
+    ///     .tokenize("porter unicode61")
+    ///     .prefix(&[2, 3])
+    ///     .create(&db)
+    ///     .unwrap();
+    ///
+    /// db.close();
+    /// ```
+    #[inline]
+    pub fn create<'a>(&self, db: &Database) -> Result<(), MinSqliteWrapperError<'a>> {
+        db.execute_checked(&self.sql())
+    }
+}
+
+/// Row that matches an `Fts5Query`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fts5Match {
+    /// Rowid of the row.
+    pub rowid: i64,
+    /// bm25 score of the row, lower is a better match.
+    pub score: f64,
+    /// Text of the highlighted column, if requested.
+    pub highlight: Option<String>,
+    /// Snippet of the matching text, if requested.
+    pub snippet: Option<String>,
+}
+
+/// Full-text query on an FTS5 table, ordered by the bm25 score.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fts5Query {
+    table: String,
+    query: String,
+    weights: Vec<f64>,
+    highlight: Option<(usize, String, String)>,
+    snippet: Option<(Option<usize>, String, String, String, u8)>,
+    limit: Option<u32>,
+    offset: u32,
+}
+
+impl Fts5Query {
+    /// Creates a query that matches `query` on the FTS5 `table`. `query`
+    /// uses the FTS5 query syntax, see `phrase` to search for user input.
+    #[inline]
+    pub fn new<T: Into<String>, Q: Into<String>>(table: T, query: Q) -> Self {
+        Fts5Query {
+            table: table.into(),
+            query: query.into(),
+            weights: Vec::new(),
+            highlight: None,
+            snippet: None,
+            limit: None,
+            offset: 0,
+        }
+    }
+
+    /// Quotes `text` as a single FTS5 phrase, so that the operators and
+    /// special characters in it are searched as plain text.
+    #[inline]
+    pub fn phrase(text: &str) -> String {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    }
+
+    /// Sets the bm25 weights of the columns in order, 1.0 by default.
+    #[inline]
+    pub fn weights(mut self, weights: &[f64]) -> Self {
+        self.weights = weights.to_vec();
+        self
+    }
+
+    /// Returns the text of `column` with the matches wrapped by `open` and
+    /// `close`, e.g. `<b>` and `</b>`.
+    #[inline]
+    pub fn highlight(mut self, column: usize, open: &str, close: &str) -> Self {
+        self.highlight = Some((column, open.to_owned(), close.to_owned()));
+        self
+    }
+
+    /// Returns a snippet of up to `tokens` tokens (at most 64) from `column`,
+    /// or from the best matching column if `None`. Matches are wrapped by
+    /// `open` and `close`, and the cut text is marked with `ellipsis`.
+    #[inline]
+    pub fn snippet(
+        mut self,
+        column: Option<usize>,
+        open: &str,
+        close: &str,
+        ellipsis: &str,
+        tokens: u8,
+    ) -> Self {
+        self.snippet = Some((
+            column,
+            open.to_owned(),
+            close.to_owned(),
+            ellipsis.to_owned(),
+            tokens.min(64),
+        ));
+        self
+    }
+
+    /// Returns at most `limit` rows.
+    #[inline]
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Skips the first `offset` rows.
+    #[inline]
+    pub fn offset(mut self, offset: u32) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Runs the query and returns the matching rows, best match first.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let matches = Fts5Query::new("package_search", Fts5Query::phrase(input))
+    ///     .weights(&[10.0, 1.0])
+    ///     .snippet(Some(1), "[", "]", "...", 16)
+    ///     .limit(20)
+    ///     .run(&db)
+    ///     .unwrap();
+    ///
+    /// for m in matches {
+    ///     println!("{} {}", m.rowid, m.snippet.unwrap_or_default());
+    /// }
+    ///
+    /// db.close();
+    /// ```
+    pub fn run<'a>(&self, db: &Database) -> Result<Vec<Fts5Match>, MinSqliteWrapperError<'a>> {
+        let table = quote_identifier(&self.table);
+        let mut params = Vec::new();
+
+        let weights: String = self
+            .weights
+            .iter()
+            .map(|weight| format!(", {}", Value::Real(*weight).to_sql_literal()))
+            .collect();
+        let mut columns = format!("rowid, bm25({}{}) AS score", table, weights);
+
+        if let Some((column, open, close)) = &self.highlight {
+            columns.push_str(&format!(", highlight({}, {}, ?, ?)", table, column));
+            params.push(Value::Text(open.clone()));
+            params.push(Value::Text(close.clone()));
+        }
+
+        if let Some((column, open, close, ellipsis, tokens)) = &self.snippet {
+            let column = column.map_or(-1, |column| column as i64);
+            columns.push_str(&format!(
+                ", snippet({}, {}, ?, ?, ?, {})",
+                table, column, tokens
+            ));
+            params.push(Value::Text(open.clone()));
+            params.push(Value::Text(close.clone()));
+            params.push(Value::Text(ellipsis.clone()));
+        }
+
+        params.push(Value::Text(self.query.clone()));
+        params.push(Value::Integer(self.limit.map_or(-1, i64::from)));
+        params.push(Value::Integer(self.offset.into()));
+
+        let statement = format!(
+            "SELECT {} FROM {} WHERE {} MATCH ? ORDER BY score LIMIT ? OFFSET ?;",
+            columns, table, table
+        );
+
+        let highlight = self.highlight.is_some();
+        let snippet = self.snippet.is_some();
+        let mut sql = db.prepare_checked(&statement)?;
+        let rows = sql.collect_rows(
+            &params,
+            |row| -> Result<Fts5Match, MinSqliteWrapperError<'static>> {
+                let text = |i: usize| -> Result<Option<String>, MinSqliteWrapperError<'static>> {
+                    match row.0.get_owned_data::<Value>(i)? {
+                        Value::Text(t) => Ok(Some(t)),
+                        _ => Ok(None),
+                    }
+                };
+
+                Ok(Fts5Match {
+                    rowid: row.0.get_owned_data(0)?,
+                    score: row.0.get_owned_data(1)?,
+                    highlight: if highlight { text(2)? } else { None },
+                    snippet: if snippet {
+                        text(2 + highlight as usize)?
+                    } else {
+                        None
+                    },
+                })
+            },
+        )?;
+
+        rows.rows.into_iter().collect()
+    }
+}
+
+/// Reason of the tokenization.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum TokenizeReason {
+    /// Text of a document is inserted or deleted.
+    Document,
+    /// Text of a query is tokenized. `prefix` is set if the token is
+    /// followed by `*` in the query.
+    Query {
+        /// Whether the query is a prefix query.
+        prefix: bool,
+    },
+    /// Text is tokenized for an auxiliary function, e.g. `highlight`.
+    Aux,
+}
+
+impl From<os::raw::c_int> for TokenizeReason {
+    fn from(flags: os::raw::c_int) -> Self {
+        if flags & TOKENIZE_QUERY != 0 {
+            TokenizeReason::Query {
+                prefix: flags & TOKENIZE_PREFIX != 0,
+            }
+        } else if flags & TOKENIZE_AUX != 0 {
+            TokenizeReason::Aux
+        } else {
+            TokenizeReason::Document
+        }
+    }
+}
+
+/// Receives the tokens from `Tokenizer::tokenize`.
+pub struct Tokens<'a> {
+    ctx: *mut os::raw::c_void,
+    x_token: XToken,
+    status: os::raw::c_int,
+    _marker: PhantomData<&'a ()>,
+}
+
+impl<'a> Tokens<'a> {
+    fn emit(
+        &mut self,
+        flags: os::raw::c_int,
+        token: &str,
+        range: Range<usize>,
+    ) -> Result<(), MinSqliteWrapperError<'static>> {
+        let status = unsafe {
+            (self.x_token)(
+                self.ctx,
+                flags,
+                token.as_ptr() as *const os::raw::c_char,
+                token.len() as os::raw::c_int,
+                range.start as os::raw::c_int,
+                range.end as os::raw::c_int,
+            )
+        };
+
+        if status != 0 {
+            self.status = status;
+            return Err(SqlitePrimaryResult::from(status).into());
+        }
+        Ok(())
+    }
+
+    /// Adds `token` that is found at the byte `range` of the text. If it
+    /// fails, the tokenizer must stop and return the error.
+    #[inline]
+    pub fn push(
+        &mut self,
+        token: &str,
+        range: Range<usize>,
+    ) -> Result<(), MinSqliteWrapperError<'static>> {
+        self.emit(0, token, range)
+    }
+
+    /// Adds `token` at the same position as the previous token, e.g. a
+    /// synonym of it. If it fails, the tokenizer must stop and return the
+    /// error.
+    #[inline]
+    pub fn push_colocated(
+        &mut self,
+        token: &str,
+        range: Range<usize>,
+    ) -> Result<(), MinSqliteWrapperError<'static>> {
+        self.emit(TOKEN_COLOCATED, token, range)
+    }
+}
+
+/// FTS5 tokenizer implementation. An instance is created for each table
+/// that uses the tokenizer.
+pub trait Tokenizer: Sized + Send + 'static {
+    /// Data of the tokenizer that is shared by all of its instances, given
+    /// to `Fts5::register_tokenizer` on registration.
+    type Aux: Send + 'static;
+
+    /// Creates the tokenizer with the arguments that follow its name in the
+    /// `tokenize` option, e.g. `["remove_diacritics", "1"]` for
+    /// `tokenize='name remove_diacritics 1'`.
+    fn create(aux: &Self::Aux, args: &[&str]) -> Result<Self, MinSqliteWrapperError<'static>>;
+
+    /// Splits `text` into the tokens and pushes them to `tokens` in order.
+    fn tokenize(
+        &self,
+        reason: TokenizeReason,
+        text: &str,
+        tokens: &mut Tokens,
+    ) -> Result<(), MinSqliteWrapperError<'static>>;
+}
+
+/// Pointer that receives `fts5_api` on binding.
+struct ApiPointer(*mut *mut fts5_api);
+
+impl<'a> ColumnCapabilities<'a> for ApiPointer {
+    fn get_data(_stmt: *mut sqlite3_stmt, _i: usize) -> Result<Self, MinSqliteWrapperError<'a>>
+    where
+        Self: Sized,
+    {
+        unimplemented!()
+    }
+
+    fn bind_val(self, stmt: *mut sqlite3_stmt, i: usize) -> SqlitePrimaryResult
+    where
+        Self: Sized,
+    {
+        unsafe {
+            SqlitePrimaryResult::from(sqlite3_bind_pointer(
+                stmt,
+                i as os::raw::c_int,
+                self.0 as *mut os::raw::c_void,
+                FTS5_API_TYPE.as_ptr() as *const os::raw::c_char,
+                None,
+            ))
+        }
+    }
+}
+
+/// Registered tokenizer, passed to SQLite as the client data.
+struct TokenizerData<T: Tokenizer> {
+    aux: T::Aux,
+    _marker: PhantomData<T>,
+}
+
+unsafe extern "C" fn free_tokenizer_data<T: Tokenizer>(data: *mut os::raw::c_void) {
+    drop(Box::from_raw(data as *mut TokenizerData<T>));
+}
+
+unsafe extern "C" fn create_trampoline<T: Tokenizer>(
+    aux: *mut os::raw::c_void,
+    argv: *mut *const os::raw::c_char,
+    argc: os::raw::c_int,
+    tokenizer: *mut *mut Fts5Tokenizer,
+) -> os::raw::c_int {
+    let data = &*(aux as *const TokenizerData<T>);
+
+    // unwinding into C is undefined behavior
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let args = (0..argc.max(0) as usize)
+            .map(|i| CStr::from_ptr(*argv.add(i)).to_str())
+            .collect::<Result<Vec<&str>, _>>()?;
+
+        T::create(&data.aux, &args)
+    }));
+
+    match result {
+        Ok(Ok(instance)) => {
+            *tokenizer = Box::into_raw(Box::new(instance)) as *mut Fts5Tokenizer;
+            0
+        }
+        _ => SQLITE_ERROR,
+    }
+}
+
+unsafe extern "C" fn delete_trampoline<T: Tokenizer>(tokenizer: *mut Fts5Tokenizer) {
+    drop(Box::from_raw(tokenizer as *mut T));
+}
+
+unsafe extern "C" fn tokenize_trampoline<T: Tokenizer>(
+    tokenizer: *mut Fts5Tokenizer,
+    ctx: *mut os::raw::c_void,
+    flags: os::raw::c_int,
+    text: *const os::raw::c_char,
+    n_text: os::raw::c_int,
+    x_token: Option<XToken>,
+) -> os::raw::c_int {
+    let instance = &*(tokenizer as *const T);
+    let x_token = match x_token {
+        Some(x_token) => x_token,
+        None => return SQLITE_ERROR,
+    };
+
+    let text = if text.is_null() || n_text <= 0 {
+        ""
+    } else {
+        match str::from_utf8(slice::from_raw_parts(text as *const u8, n_text as usize)) {
+            Ok(text) => text,
+            Err(_) => return SQLITE_ERROR,
+        }
+    };
+
+    let mut tokens = Tokens {
+        ctx,
+        x_token,
+        status: 0,
+        _marker: PhantomData,
+    };
+
+    // unwinding into C is undefined behavior
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        instance.tokenize(TokenizeReason::from(flags), text, &mut tokens)
+    }));
+
+    match result {
+        // errors of the callback must be returned as they are
+        _ if tokens.status != 0 => tokens.status,
+        Ok(Ok(())) => 0,
+        _ => SQLITE_ERROR,
+    }
+}
+
+/// Specifies the FTS5 functions of the SQLite connection.
+pub trait Fts5 {
+    /// Registers the FTS5 tokenizer `T` as `name`, so that it can be used
+    /// in the `tokenize` option of the FTS5 tables of this connection.
+    /// Registering another tokenizer with the same name replaces it.
+    ///
+    /// # Usage
+    /// struct Lowercase;
+    ///
+    /// impl Tokenizer for Lowercase {
+    ///     type Aux = ();
+    ///     ...
+    /// }
+    ///
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// db.register_tokenizer::<Lowercase>("lowercase", ()).unwrap();
+    ///
+    /// Fts5Table::new("package_search").column("name").tokenize("lowercase").create(&db).unwrap();
+    ///
+    /// db.close();
+    /// ```
+    fn register_tokenizer<'a, T: Tokenizer>(
+        &self,
+        name: &str,
+        aux: T::Aux,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>;
+}
+
+impl Database {
+    /// Returns the `fts5_api` of the connection.
+    fn fts5_api<'a>(&self) -> Result<*mut fts5_api, MinSqliteWrapperError<'a>> {
+        let mut api: *mut fts5_api = ptr::null_mut();
+        let mut sql = self.prepare_checked("SELECT fts5(?);")?;
+
+        let status = sql.bind_val(1, ApiPointer(&mut api));
+        if status != SqlitePrimaryResult::Ok {
+            return Err(status.into());
+        }
+
+        if sql.execute_prepared() != PreparedStatementStatus::FoundRow {
+            return Err(MinSqliteWrapperError::from_connection(self.rp));
+        }
+
+        if api.is_null() {
+            return Err(MinSqliteWrapperError {
+                kind: "sqlite3:Fts5",
+                reason: String::from("fts5_api is not available"),
+            });
+        }
+
+        Ok(api)
+    }
+}
+
+impl Fts5 for Database {
+    fn register_tokenizer<'a, T: Tokenizer>(
+        &self,
+        name: &str,
+        aux: T::Aux,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>> {
+        let name = CString::new(name)?;
+        let api = self.fts5_api()?;
+
+        let mut tokenizer = fts5_tokenizer {
+            x_create: Some(create_trampoline::<T>),
+            x_delete: Some(delete_trampoline::<T>),
+            x_tokenize: Some(tokenize_trampoline::<T>),
+        };
+
+        let data = Box::into_raw(Box::new(TokenizerData::<T> {
+            aux,
+            _marker: PhantomData,
+        }));
+
+        unsafe {
+            let create = match (*api).x_create_tokenizer {
+                Some(create) => create,
+                None => {
+                    drop(Box::from_raw(data));
+                    return Err(SqlitePrimaryResult::Misuse.into());
+                }
+            };
+
+            // FTS5 copies the methods, and calls `free_tokenizer_data` when
+            // the tokenizer is replaced or the connection is closed.
+            Ok(SqlitePrimaryResult::from(create(
+                api,
+                name.as_ptr(),
+                data as *mut os::raw::c_void,
+                &mut tokenizer,
+                Some(free_tokenizer_data::<T>),
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Splits the text on whitespace and lowercases the tokens.
+    struct Lowercase;
+
+    impl Tokenizer for Lowercase {
+        type Aux = ();
+
+        fn create(_aux: &Self::Aux, args: &[&str]) -> Result<Self, MinSqliteWrapperError<'static>> {
+            match args {
+                [] => Ok(Lowercase),
+                _ => Err(MinSqliteWrapperError {
+                    kind: "sqlite3:Fts5",
+                    reason: String::from("lowercase takes no arguments"),
+                }),
+            }
+        }
+
+        fn tokenize(
+            &self,
+            _reason: TokenizeReason,
+            text: &str,
+            tokens: &mut Tokens,
+        ) -> Result<(), MinSqliteWrapperError<'static>> {
+            let mut start = 0;
+            for word in text.split(' ') {
+                if !word.is_empty() {
+                    tokens.push(&word.to_lowercase(), start..start + word.len())?;
+                }
+                start += word.len() + 1;
+            }
+            Ok(())
+        }
+    }
+
+    fn open() -> Database {
+        let db = Database::open(":memory:").unwrap();
+        Fts5Table::new("docs")
+            .column("title")
+            .column("body")
+            .create(&db)
+            .unwrap();
+        db.execute_checked(
+            "INSERT INTO docs(rowid, title, body) VALUES \
+             (1, 'sqlite', 'a small database engine'), \
+             (2, 'rust', 'a language with a database crate');",
+        )
+        .unwrap();
+        db
+    }
+
+    #[test]
+    fn matches_are_ranked_and_highlighted() {
+        let db = open();
+
+        let matches = Fts5Query::new("docs", "database")
+            .weights(&[10.0, 1.0])
+            .highlight(1, "[", "]")
+            .limit(1)
+            .run(&db)
+            .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(
+            matches[0].highlight.as_deref(),
+            Some("a small [database] engine")
+        );
+
+        let matches = Fts5Query::new("docs", Fts5Query::phrase("database crate"))
+            .snippet(None, "<", ">", "...", 8)
+            .run(&db)
+            .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].rowid, 2);
+        assert!(matches[0]
+            .snippet
+            .as_deref()
+            .unwrap()
+            .contains("<database crate>"));
+    }
+
+    #[test]
+    fn invalid_queries_fail() {
+        let db = open();
+
+        assert!(Fts5Query::new("docs", "AND").run(&db).is_err());
+        assert!(Fts5Query::new("missing", "database").run(&db).is_err());
+        assert!(Fts5Query::new("docs", Fts5Query::phrase("AND"))
+            .run(&db)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn registered_tokenizer_is_used() {
+        let db = Database::open(":memory:").unwrap();
+        assert_eq!(
+            db.register_tokenizer::<Lowercase>("lowercase", ()).unwrap(),
+            SqlitePrimaryResult::Ok
+        );
+
+        Fts5Table::new("docs")
+            .column("body")
+            .tokenize("lowercase")
+            .create(&db)
+            .unwrap();
+        db.execute_checked("INSERT INTO docs(rowid, body) VALUES (1, 'Hello World');")
+            .unwrap();
+        assert_eq!(
+            Fts5Query::new("docs", "world").run(&db).unwrap()[0].rowid,
+            1
+        );
+
+        assert!(Fts5Table::new("other")
+            .column("body")
+            .tokenize("lowercase strict")
+            .create(&db)
+            .is_err());
+        assert!(Fts5Table::new("other")
+            .column("body")
+            .tokenize("missing")
+            .create(&db)
+            .is_err());
+    }
+}
@@ -0,0 +1,290 @@
+//! This module contains trait and functions for the data change
+//! hooks of SQLite database connection.
+
+#![forbid(missing_docs)]
+
+use std::{
+    ffi::CStr,
+    os,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use crate::{
+    bindings::{
+        sqlite3, sqlite3_preupdate_count, sqlite3_preupdate_depth, sqlite3_preupdate_hook,
+        sqlite3_preupdate_new, sqlite3_preupdate_old, SQLITE_DELETE, SQLITE_INSERT, SQLITE_UPDATE,
+    },
+    ehandle::MinSqliteWrapperError,
+    prelude::*,
+    value::ValueRef,
+};
+
+/// Boxed preupdate callback stored by the `Database`.
+pub(crate) type PreupdateHookFn = Box<dyn FnMut(&Preupdate) + Send>;
+
+/// Type of the row change that triggers the preupdate hook.
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum PreupdateOperation {
+    /// A row is about to be inserted.
+    Insert,
+    /// A row is about to be updated.
+    Update,
+    /// A row is about to be deleted.
+    Delete,
+}
+
+/// Describes the row change that is about to happen. Only valid
+/// inside of the preupdate hook callback.
+pub struct Preupdate<'a> {
+    db: *mut sqlite3,
+    /// Type of the change.
+    pub operation: PreupdateOperation,
+    /// Name of the database that is being modified. ("main", "temp" or the attached name)
+    pub database: &'a str,
+    /// Name of the table that is being modified.
+    pub table: &'a str,
+    /// Rowid of the row before the change. Undefined for `PreupdateOperation::Insert`.
+    pub old_rowid: i64,
+    /// Rowid of the row after the change. Undefined for `PreupdateOperation::Delete`.
+    pub new_rowid: i64,
+}
+
+impl<'a> Preupdate<'a> {
+    /// Returns the number of columns in the row that is being changed.
+    #[inline]
+    pub fn column_count(&self) -> usize {
+        unsafe { sqlite3_preupdate_count(self.db) as usize }
+    }
+
+    /// Returns 0 if the change is caused directly by the top-level SQL
+    /// statement, 1 if it's caused by a trigger fired by that statement,
+    /// 2 if it's caused by a trigger fired by a trigger, and so on.
+    #[inline]
+    pub fn depth(&self) -> usize {
+        unsafe { sqlite3_preupdate_depth(self.db) as usize }
+    }
+
+    /// Reads the value of the column at index `i` before the change.
+    ///
+    /// Fails with `SqlitePrimaryResult::Misuse` on `PreupdateOperation::Insert`
+    /// and with `SqlitePrimaryResult::Range` if the column index is out of range.
+    pub fn old_value(&self, i: usize) -> Result<ValueRef<'a>, MinSqliteWrapperError<'a>> {
+        let mut value = ptr::null_mut();
+        unsafe {
+            let status = SqlitePrimaryResult::from(sqlite3_preupdate_old(
+                self.db,
+                i as os::raw::c_int,
+                &mut value,
+            ));
+            if status != SqlitePrimaryResult::Ok {
+                return Err(status.into());
+            }
+
+            ValueRef::from_raw(value)
+        }
+    }
+
+    /// Reads the value of the column at index `i` after the change.
+    ///
+    /// Fails with `SqlitePrimaryResult::Misuse` on `PreupdateOperation::Delete`
+    /// and with `SqlitePrimaryResult::Range` if the column index is out of range.
+    pub fn new_value(&self, i: usize) -> Result<ValueRef<'a>, MinSqliteWrapperError<'a>> {
+        let mut value = ptr::null_mut();
+        unsafe {
+            let status = SqlitePrimaryResult::from(sqlite3_preupdate_new(
+                self.db,
+                i as os::raw::c_int,
+                &mut value,
+            ));
+            if status != SqlitePrimaryResult::Ok {
+                return Err(status.into());
+            }
+
+            ValueRef::from_raw(value)
+        }
+    }
+}
+
+/// Specifies the data change hooks of the SQLite connection.
+pub trait Hooks {
+    /// Registers a callback that is invoked prior to each INSERT, UPDATE
+    /// and DELETE operation on a rowid table. Old and new column values
+    /// can be read from the `Preupdate` argument. Passing `None` removes
+    /// the registered callback.
+    ///
+    /// # Warning
+    /// SQLite must be compiled with `SQLITE_ENABLE_PREUPDATE_HOOK`.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let mut db = Database::open(db_path).unwrap();
+    ///
+    /// db.preupdate_hook(Some(|case: &Preupdate| {
+    ///     if case.operation == PreupdateOperation::Update {
+    ///         println!(
+    ///             "{:?} -> {:?}",
+    ///             case.old_value(1).unwrap(),
+    ///             case.new_value(1).unwrap()
+    ///         );
+    ///     }
+    /// }));
+    ///
+    /// db.close();
+    /// ```
+    fn preupdate_hook<F>(&mut self, hook: Option<F>)
+    where
+        F: FnMut(&Preupdate) + Send + 'static;
+}
+
+impl Hooks for Database {
+    fn preupdate_hook<F>(&mut self, hook: Option<F>)
+    where
+        F: FnMut(&Preupdate) + Send + 'static,
+    {
+        match hook {
+            Some(hook) => {
+                let mut boxed: Box<PreupdateHookFn> = Box::new(Box::new(hook));
+                unsafe {
+                    sqlite3_preupdate_hook(
+                        self.rp,
+                        Some(preupdate_trampoline),
+                        &mut *boxed as *mut PreupdateHookFn as *mut os::raw::c_void,
+                    );
+                }
+                self.preupdate_hook = Some(boxed);
+            }
+            None => {
+                unsafe {
+                    sqlite3_preupdate_hook(self.rp, None, ptr::null_mut());
+                }
+                self.preupdate_hook = None;
+            }
+        }
+    }
+}
+
+unsafe extern "C" fn preupdate_trampoline(
+    ctx: *mut os::raw::c_void,
+    db: *mut sqlite3,
+    op: os::raw::c_int,
+    db_name: *const os::raw::c_char,
+    table_name: *const os::raw::c_char,
+    old_rowid: os::raw::c_longlong,
+    new_rowid: os::raw::c_longlong,
+) {
+    let operation = match op {
+        SQLITE_INSERT => PreupdateOperation::Insert,
+        SQLITE_UPDATE => PreupdateOperation::Update,
+        SQLITE_DELETE => PreupdateOperation::Delete,
+        _ => return,
+    };
+
+    let hook = &mut *(ctx as *mut PreupdateHookFn);
+    let case = Preupdate {
+        db,
+        operation,
+        database: CStr::from_ptr(db_name).to_str().unwrap_or_default(),
+        table: CStr::from_ptr(table_name).to_str().unwrap_or_default(),
+        old_rowid,
+        new_rowid,
+    };
+
+    // unwinding into C is undefined behavior
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| hook(&case)));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{prelude::*, test_support::open_memory};
+
+    fn open() -> Database {
+        open_memory("CREATE TABLE t(id INTEGER PRIMARY KEY, name TEXT);")
+    }
+
+    #[test]
+    fn preupdate_hook_reports_old_and_new_values() {
+        let mut db = open();
+        let changes = Arc::new(Mutex::new(Vec::new()));
+
+        let seen = changes.clone();
+        db.preupdate_hook(Some(move |case: &Preupdate| {
+            let old = case.old_value(1).map(Value::from).ok();
+            let new = case.new_value(1).map(Value::from).ok();
+            seen.lock()
+                .unwrap()
+                .push((case.operation, case.table.to_owned(), old, new));
+        }));
+
+        db.execute_changes(String::from("INSERT INTO t VALUES (1, 'a');"))
+            .unwrap();
+        db.execute_changes(String::from("UPDATE t SET name = 'b';"))
+            .unwrap();
+        db.execute_changes(String::from("DELETE FROM t;")).unwrap();
+
+        let text = |text: &str| Some(Value::Text(text.to_owned()));
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![
+                (
+                    PreupdateOperation::Insert,
+                    String::from("t"),
+                    None,
+                    text("a")
+                ),
+                (
+                    PreupdateOperation::Update,
+                    String::from("t"),
+                    text("a"),
+                    text("b")
+                ),
+                (
+                    PreupdateOperation::Delete,
+                    String::from("t"),
+                    text("b"),
+                    None
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn preupdate_hook_is_removed() {
+        let mut db = open();
+        let calls = Arc::new(Mutex::new(0));
+
+        let seen = calls.clone();
+        db.preupdate_hook(Some(move |_: &Preupdate| *seen.lock().unwrap() += 1));
+        db.execute_changes(String::from("INSERT INTO t VALUES (1, 'a');"))
+            .unwrap();
+
+        db.preupdate_hook(None::<fn(&Preupdate)>);
+        db.execute_changes(String::from("INSERT INTO t VALUES (2, 'b');"))
+            .unwrap();
+
+        assert_eq!(*calls.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn statements_outliving_the_connection_dont_call_the_hook() {
+        let mut db = open();
+        let calls = Arc::new(Mutex::new(0));
+
+        let seen = calls.clone();
+        db.preupdate_hook(Some(move |_: &Preupdate| *seen.lock().unwrap() += 1));
+
+        let mut sql = db
+            .prepare(
+                String::from("INSERT INTO t VALUES (1, 'a');"),
+                None::<Box<dyn FnOnce(SqlitePrimaryResult, String)>>,
+            )
+            .unwrap();
+        assert_eq!(db.close(), SqlitePrimaryResult::Ok);
+
+        assert_eq!(sql.execute_prepared(), PreparedStatementStatus::Done);
+        assert_eq!(*calls.lock().unwrap(), 0);
+    }
+}
@@ -0,0 +1,229 @@
+//! This module contains data-types and functions to cancel
+//! long-running SQL operations.
+
+#![forbid(missing_docs)]
+
+use std::{
+    os,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    bindings::{sqlite3, sqlite3_interrupt, sqlite3_progress_handler},
+    prelude::*,
+};
+
+/// Boxed progress handler stored by the `Database`.
+pub(crate) type ProgressHandlerFn = Box<dyn FnMut() -> bool + Send>;
+
+/// Connection pointer shared between `Database` and its `InterruptHandle`s.
+/// It's set to null once the connection is closed.
+pub(crate) struct InterruptTarget(Mutex<*mut sqlite3>);
+
+unsafe impl Send for InterruptTarget {}
+unsafe impl Sync for InterruptTarget {}
+
+impl InterruptTarget {
+    #[inline]
+    pub(crate) fn new(rp: *mut sqlite3) -> Arc<Self> {
+        Arc::new(Self(Mutex::new(rp)))
+    }
+
+    /// Detaches the connection so handles that outlive the
+    /// `Database` don't touch the closed connection.
+    #[inline]
+    pub(crate) fn detach(&self) {
+        let mut rp = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        *rp = ptr::null_mut();
+    }
+}
+
+/// Thread-safe handle that can interrupt the operations running on
+/// its `Database` from any thread. It's safe to keep the handle after
+/// the `Database` is closed, interrupting then simply does nothing.
+#[derive(Clone)]
+pub struct InterruptHandle {
+    target: Arc<InterruptTarget>,
+}
+
+impl InterruptHandle {
+    /// Interrupts the SQL operations that are currently running on the
+    /// connection. Interrupted `execute_prepared` calls return
+    /// `PreparedStatementStatus::Interrupted`.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let handle = db.interrupt_handle();
+    /// std::thread::spawn(move || {
+    ///     std::thread::sleep(std::time::Duration::from_secs(5));
+    ///     handle.interrupt();
+    /// });
+    /// ```
+    pub fn interrupt(&self) {
+        let rp = self.target.0.lock().unwrap_or_else(|e| e.into_inner());
+        if !rp.is_null() {
+            unsafe { sqlite3_interrupt(*rp) }
+        }
+    }
+}
+
+/// Specifies the cancellation functions of the SQLite connection.
+pub trait Interrupt {
+    /// Registers a callback that is invoked periodically, roughly after every
+    /// `n_ops` virtual machine instructions during long-running operations.
+    /// If the callback returns `true`, the operation is interrupted. Passing
+    /// `None` or zero `n_ops` removes the registered handler. Values above
+    /// `i32::MAX` are clamped to it.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let mut db = Database::open(db_path).unwrap();
+    ///
+    /// let started = std::time::Instant::now();
+    /// db.progress_handler(1000, Some(move || started.elapsed().as_secs() > 10));
+    ///
+    /// db.close();
+    /// ```
+    fn progress_handler<F>(&mut self, n_ops: u32, handler: Option<F>)
+    where
+        F: FnMut() -> bool + Send + 'static;
+
+    /// Returns a handle that can interrupt the operations of the connection
+    /// from another thread.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let handle = db.interrupt_handle();
+    /// handle.interrupt();
+    ///
+    /// db.close();
+    /// ```
+    fn interrupt_handle(&self) -> InterruptHandle;
+}
+
+impl Interrupt for Database {
+    fn progress_handler<F>(&mut self, n_ops: u32, handler: Option<F>)
+    where
+        F: FnMut() -> bool + Send + 'static,
+    {
+        match handler {
+            Some(handler) if n_ops > 0 => {
+                let mut boxed: Box<ProgressHandlerFn> = Box::new(Box::new(handler));
+                unsafe {
+                    sqlite3_progress_handler(
+                        self.rp,
+                        // larger values would wrap to <= 0, which disables the handler
+                        n_ops.min(os::raw::c_int::MAX as u32) as os::raw::c_int,
+                        Some(progress_trampoline),
+                        &mut *boxed as *mut ProgressHandlerFn as *mut os::raw::c_void,
+                    );
+                }
+                self.progress_handler = Some(boxed);
+            }
+            _ => {
+                unsafe {
+                    sqlite3_progress_handler(self.rp, 0, None, ptr::null_mut());
+                }
+                self.progress_handler = None;
+            }
+        }
+    }
+
+    #[inline]
+    fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle {
+            target: self.interrupt_target.clone(),
+        }
+    }
+}
+
+unsafe extern "C" fn progress_trampoline(ctx: *mut os::raw::c_void) -> os::raw::c_int {
+    let handler = &mut *(ctx as *mut ProgressHandlerFn);
+
+    // unwinding into C is undefined behavior, interrupt the operation instead
+    panic::catch_unwind(AssertUnwindSafe(handler)).unwrap_or(true) as os::raw::c_int
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use crate::{prelude::*, statement::SqlStatement};
+
+    const ENDLESS: &str =
+        "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT count(*) FROM c;";
+
+    fn prepare(db: &Database, statement: &str) -> SqlStatement {
+        db.prepare(
+            String::from(statement),
+            None::<Box<dyn FnOnce(SqlitePrimaryResult, String)>>,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn progress_handler_interrupts_the_statement() {
+        let mut db = Database::open(":memory:").unwrap();
+        db.progress_handler(1000, Some(|| true));
+
+        let mut sql = prepare(&db, ENDLESS);
+        assert_eq!(sql.execute_prepared(), PreparedStatementStatus::Interrupted);
+    }
+
+    #[test]
+    fn progress_handler_with_large_n_ops_lets_statements_run() {
+        let mut db = Database::open(":memory:").unwrap();
+        db.progress_handler(u32::MAX, Some(|| true));
+
+        let mut sql = prepare(&db, "SELECT 1;");
+        assert_eq!(sql.execute_prepared(), PreparedStatementStatus::FoundRow);
+    }
+
+    #[test]
+    fn interrupt_handle_interrupts_from_another_thread() {
+        let db = Database::open(":memory:").unwrap();
+        let handle = db.interrupt_handle();
+        let done = Arc::new(AtomicBool::new(false));
+
+        // prepared first, so that only the execution is interrupted
+        let mut sql = prepare(&db, ENDLESS);
+
+        let interrupter = {
+            let done = done.clone();
+            thread::spawn(move || {
+                while !done.load(Ordering::SeqCst) {
+                    handle.interrupt();
+                    thread::sleep(Duration::from_millis(10));
+                }
+            })
+        };
+
+        let status = sql.execute_prepared();
+        done.store(true, Ordering::SeqCst);
+        interrupter.join().unwrap();
+
+        assert_eq!(status, PreparedStatementStatus::Interrupted);
+    }
+
+    #[test]
+    fn interrupt_handle_outlives_the_connection() {
+        let db = Database::open(":memory:").unwrap();
+        let handle = db.interrupt_handle();
+        db.close();
+
+        handle.interrupt();
+    }
+}
@@ -0,0 +1,464 @@
+//! This module contains data-types and functions to store JSON documents
+//! and to query them with the JSON functions of SQLite.
+
+#![forbid(missing_docs)]
+#![allow(clippy::not_unsafe_ptr_arg_deref)] // same as operations module, `bind_val` is driven by `SqlStatement`.
+
+use std::{fmt, os, ptr};
+
+use crate::{
+    bindings::{
+        sqlite3_column_type, sqlite3_db_handle, sqlite3_prepare_v2, sqlite3_stmt, COLUMN_NULL,
+    },
+    ehandle::MinSqliteWrapperError,
+    operations::ColumnCapabilities,
+    prelude::*,
+    statement::SqlStatement,
+};
+
+/// JSON document that is stored as TEXT. Documents read from the database
+/// are validated with `json_valid`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Json(String);
+
+impl Json {
+    /// Wraps the JSON `text`. The text isn't validated until it's read
+    /// back from the database.
+    #[inline]
+    pub fn new<T: Into<String>>(text: T) -> Self {
+        Json(text.into())
+    }
+
+    /// Returns the JSON text.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the JSON text, consuming the document.
+    #[inline]
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<Json> for Value {
+    #[inline]
+    fn from(value: Json) -> Self {
+        Value::Text(value.0)
+    }
+}
+
+/// Returns `true` if `json_valid` accepts `text` on the connection of `stmt`.
+fn is_valid_json<'a>(
+    stmt: *mut sqlite3_stmt,
+    text: &str,
+) -> Result<bool, MinSqliteWrapperError<'a>> {
+    unsafe {
+        let db = sqlite3_db_handle(stmt);
+        let statement = b"SELECT json_valid(?);\0";
+        let mut check = ptr::null_mut();
+
+        let status: SqlitePrimaryResult = sqlite3_prepare_v2(
+            db,
+            statement.as_ptr() as *const os::raw::c_char,
+            -1,
+            &mut check,
+            ptr::null_mut(),
+        )
+        .into();
+        if status != SqlitePrimaryResult::Ok {
+            return Err(MinSqliteWrapperError::from_connection(db));
+        }
+
+        let mut check = SqlStatement::new(check);
+        let status = check.bind_val(1, text);
+        if status != SqlitePrimaryResult::Ok {
+            return Err(status.into());
+        }
+
+        match check.execute_prepared() {
+            PreparedStatementStatus::FoundRow => Ok(check.get_owned_data::<i64>(0)? == 1),
+            _ => Err(MinSqliteWrapperError::from_connection(db)),
+        }
+    }
+}
+
+impl<'a> ColumnCapabilities<'a> for Json {
+    fn get_data(stmt: *mut sqlite3_stmt, i: usize) -> Result<Self, MinSqliteWrapperError<'a>>
+    where
+        Self: Sized,
+    {
+        let invalid = || MinSqliteWrapperError {
+            kind: "sqlite3:Json",
+            reason: format!("column {} is not valid JSON", i),
+        };
+
+        if unsafe { sqlite3_column_type(stmt, i as os::raw::c_int) } as u32 == COLUMN_NULL {
+            return Err(invalid());
+        }
+
+        let text: String = ColumnCapabilities::get_data(stmt, i)?;
+        if !is_valid_json(stmt, &text)? {
+            return Err(invalid());
+        }
+
+        Ok(Json(text))
+    }
+
+    #[inline]
+    fn bind_val(self, stmt: *mut sqlite3_stmt, i: usize) -> SqlitePrimaryResult
+    where
+        Self: Sized,
+    {
+        self.0.bind_val(stmt, i)
+    }
+}
+
+/// JSON path like `$.dependencies[0].name` that is built without
+/// concatenating strings by hand.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JsonPath(String);
+
+impl Default for JsonPath {
+    #[inline]
+    fn default() -> Self {
+        JsonPath::root()
+    }
+}
+
+impl JsonPath {
+    /// Returns the path of the whole document, `$`.
+    #[inline]
+    pub fn root() -> Self {
+        JsonPath(String::from("$"))
+    }
+
+    /// Appends the object member `key`. Keys are quoted, so they may contain
+    /// dots and brackets. Fails if `key` contains a double quote, as SQLite
+    /// has no way to escape it in JSON paths.
+    pub fn key<'a>(mut self, key: &str) -> Result<Self, MinSqliteWrapperError<'a>> {
+        if key.contains('"') {
+            return Err(MinSqliteWrapperError {
+                kind: "sqlite3:Json",
+                reason: format!("JSON path key {} contains a double quote", key),
+            });
+        }
+
+        self.0.push_str(&format!(".\"{}\"", key));
+        Ok(self)
+    }
+
+    /// Appends the array element at `index`.
+    #[inline]
+    pub fn index(mut self, index: usize) -> Self {
+        self.0.push_str(&format!("[{}]", index));
+        self
+    }
+
+    /// Appends the array element at `offset` from the end, e.g. `1` for the
+    /// last element.
+    #[inline]
+    pub fn from_end(mut self, offset: usize) -> Self {
+        self.0.push_str(&format!("[#-{}]", offset));
+        self
+    }
+
+    /// Returns the path text.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for JsonPath {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Type of a JSON element.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum JsonType {
+    /// `null`
+    Null,
+    /// `true`
+    True,
+    /// `false`
+    False,
+    /// Number without fraction or exponent.
+    Integer,
+    /// Number with fraction or exponent.
+    Real,
+    /// String
+    Text,
+    /// Array
+    Array,
+    /// Object
+    Object,
+}
+
+impl JsonType {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "null" => Some(JsonType::Null),
+            "true" => Some(JsonType::True),
+            "false" => Some(JsonType::False),
+            "integer" => Some(JsonType::Integer),
+            "real" => Some(JsonType::Real),
+            "text" => Some(JsonType::Text),
+            "array" => Some(JsonType::Array),
+            "object" => Some(JsonType::Object),
+            _ => None,
+        }
+    }
+}
+
+/// Element of a JSON document, returned by `json_each` and `json_tree`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonEntry {
+    /// Member name for objects, index for arrays, NULL for the top element.
+    pub key: Value,
+    /// Value of the element, as JSON text for arrays and objects.
+    pub value: Value,
+    /// Type of the element.
+    pub kind: JsonType,
+    /// SQL value of the element, NULL for arrays and objects.
+    pub atom: Value,
+    /// Identifier of the element within the document.
+    pub id: i64,
+    /// Identifier of the parent element, only set by `json_tree`.
+    pub parent: Option<i64>,
+    /// Path of the element.
+    pub full_key: String,
+    /// Path of the container of the element.
+    pub path: String,
+}
+
+/// Specifies the JSON functions of the SQLite connection. Documents and
+/// paths are bound as parameters.
+pub trait JsonOperations {
+    /// Returns the value at `path` of `json` with `json_extract`. Arrays and
+    /// objects are returned as JSON text, missing elements as NULL.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let manifest = Json::new(r#"{"name": "lpm", "dependencies": ["sqlite3"]}"#);
+    /// let path = JsonPath::root().key("dependencies").unwrap().index(0);
+    ///
+    /// let dependency = db.json_extract(&manifest, &path).unwrap();
+    /// assert_eq!(dependency, Value::Text(String::from("sqlite3")));
+    ///
+    /// db.close();
+    /// ```
+    fn json_extract<'a, P: AsRef<str>>(
+        &self,
+        json: &Json,
+        path: P,
+    ) -> Result<Value, MinSqliteWrapperError<'a>>;
+
+    /// Returns the direct children of the element at `path` with `json_each`,
+    /// or of the top element if `None`.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// for entry in db.json_each(&manifest, Some(JsonPath::root().key("dependencies").unwrap())).unwrap() {
+    ///     println!("{:?}", entry.atom);
+    /// }
+    ///
+    /// db.close();
+    /// ```
+    fn json_each<'a, P: AsRef<str>>(
+        &self,
+        json: &Json,
+        path: Option<P>,
+    ) -> Result<Vec<JsonEntry>, MinSqliteWrapperError<'a>>;
+
+    /// Returns the element at `path` and all of its descendants with
+    /// `json_tree`, or the whole document if `None`.
+    fn json_tree<'a, P: AsRef<str>>(
+        &self,
+        json: &Json,
+        path: Option<P>,
+    ) -> Result<Vec<JsonEntry>, MinSqliteWrapperError<'a>>;
+}
+
+impl Database {
+    /// Runs the table-valued JSON `function` and collects its rows.
+    fn json_entries<'a>(
+        &self,
+        function: &str,
+        json: &Json,
+        path: Option<&str>,
+    ) -> Result<Vec<JsonEntry>, MinSqliteWrapperError<'a>> {
+        let mut params = vec![Value::Text(json.0.clone())];
+        if let Some(path) = path {
+            params.push(Value::Text(path.to_owned()));
+        }
+
+        let arguments = if path.is_some() { "?, ?" } else { "?" };
+        let statement = format!(
+            "SELECT key, value, type, atom, id, parent, fullkey, path FROM {}({});",
+            function, arguments
+        );
+
+        let mut sql = self.prepare_checked(&statement)?;
+        let rows = sql.collect_rows(&params, |row| {
+            let kind: String = row.0.get_owned_data(2)?;
+            Ok(JsonEntry {
+                key: row.0.get_owned_data(0)?,
+                value: row.0.get_owned_data(1)?,
+                kind: JsonType::from_name(&kind).ok_or_else(|| MinSqliteWrapperError {
+                    kind: "sqlite3:Json",
+                    reason: format!("unknown JSON type: {}", kind),
+                })?,
+                atom: row.0.get_owned_data(3)?,
+                id: row.0.get_owned_data(4)?,
+                parent: match row.0.get_owned_data::<Value>(5)? {
+                    Value::Integer(parent) => Some(parent),
+                    _ => None,
+                },
+                full_key: row.0.get_owned_data(6)?,
+                path: row.0.get_owned_data(7)?,
+            })
+        })?;
+
+        rows.rows.into_iter().collect()
+    }
+}
+
+impl JsonOperations for Database {
+    fn json_extract<'a, P: AsRef<str>>(
+        &self,
+        json: &Json,
+        path: P,
+    ) -> Result<Value, MinSqliteWrapperError<'a>> {
+        let params = [
+            Value::Text(json.0.clone()),
+            Value::Text(path.as_ref().to_owned()),
+        ];
+
+        let mut sql = self.prepare_checked("SELECT json_extract(?, ?);")?;
+        let rows = sql.collect_rows(&params, |row| row.0.get_owned_data::<Value>(0))?;
+
+        match rows.rows.into_iter().next() {
+            Some(value) => Ok(value?),
+            None => Ok(Value::Null),
+        }
+    }
+
+    #[inline]
+    fn json_each<'a, P: AsRef<str>>(
+        &self,
+        json: &Json,
+        path: Option<P>,
+    ) -> Result<Vec<JsonEntry>, MinSqliteWrapperError<'a>> {
+        self.json_entries("json_each", json, path.as_ref().map(AsRef::as_ref))
+    }
+
+    #[inline]
+    fn json_tree<'a, P: AsRef<str>>(
+        &self,
+        json: &Json,
+        path: Option<P>,
+    ) -> Result<Vec<JsonEntry>, MinSqliteWrapperError<'a>> {
+        self.json_entries("json_tree", json, path.as_ref().map(AsRef::as_ref))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    fn manifest() -> Json {
+        Json::new(r#"{"name": "lpm", "a.b": {"c[0]": 1}, "dependencies": ["sqlite3", "json"]}"#)
+    }
+
+    #[test]
+    fn paths_are_built_and_extracted() {
+        let db = Database::open(":memory:").unwrap();
+
+        let path = JsonPath::root().key("dependencies").unwrap().index(1);
+        assert_eq!(path.as_str(), "$.\"dependencies\"[1]");
+        assert_eq!(
+            db.json_extract(&manifest(), &path).unwrap(),
+            Value::Text(String::from("json"))
+        );
+
+        let path = JsonPath::root().key("a.b").unwrap().key("c[0]").unwrap();
+        assert_eq!(
+            db.json_extract(&manifest(), path).unwrap(),
+            Value::Integer(1)
+        );
+
+        let path = JsonPath::root().key("dependencies").unwrap().from_end(1);
+        assert_eq!(
+            db.json_extract(&manifest(), path).unwrap(),
+            Value::Text(String::from("json"))
+        );
+        assert_eq!(
+            db.json_extract(&manifest(), JsonPath::root().key("missing").unwrap())
+                .unwrap(),
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn invalid_keys_and_documents_are_rejected() {
+        let db = Database::open(":memory:").unwrap();
+
+        let error = JsonPath::root().key("say \"hi\"").unwrap_err();
+        assert_eq!(error.kind, "sqlite3:Json");
+
+        assert!(db
+            .json_extract(&Json::new("{not json"), JsonPath::root())
+            .is_err());
+        assert!(db.json_extract(&manifest(), "no path").is_err());
+
+        let mut sql = db.prepare_checked("SELECT 'not json';").unwrap();
+        sql.execute_prepared();
+        assert!(sql.get_owned_data::<Json>(0).is_err());
+    }
+
+    #[test]
+    fn elements_are_listed() {
+        let db = Database::open(":memory:").unwrap();
+
+        let entries = db
+            .json_each(
+                &manifest(),
+                Some(JsonPath::root().key("dependencies").unwrap()),
+            )
+            .unwrap();
+        let atoms: Vec<Value> = entries.into_iter().map(|entry| entry.atom).collect();
+        assert_eq!(
+            atoms,
+            [
+                Value::Text(String::from("sqlite3")),
+                Value::Text(String::from("json"))
+            ]
+        );
+
+        let tree = db.json_tree(&manifest(), None::<&str>).unwrap();
+        assert_eq!(tree[0].kind, JsonType::Object);
+        assert_eq!(tree[0].parent, None);
+        assert!(tree[1..].iter().all(|entry| entry.parent.is_some()));
+    }
+}
@@ -202,10 +202,39 @@
 
 #![allow(clippy::needless_doctest_main)]
 
+pub mod attach;
+pub mod authorizer;
+pub mod backup;
 pub mod bindings;
+pub mod blob;
+// process-wide configuration belongs to the program that loads an extension
+#[cfg(not(feature = "loadable-extension"))]
+pub mod config;
 pub mod connection;
+pub mod csv;
 pub mod ehandle;
+pub mod extension;
+pub mod fault_injection;
+pub mod fts5;
+pub mod hooks;
+pub mod interrupt;
+pub mod json;
+pub mod migrations;
 pub mod operations;
+pub mod pragma;
+pub mod rtree;
+pub mod schema;
+pub mod serialize;
+pub mod session;
 pub mod statement;
+pub mod table_functions;
+pub mod trace;
+pub mod value;
+pub mod vfs;
+pub mod vtab;
+pub mod wal;
 
 pub mod prelude;
+
+#[cfg(test)]
+mod test_support;
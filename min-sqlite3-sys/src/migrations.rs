@@ -0,0 +1,435 @@
+//! This module contains data-types and functions to keep the
+//! database schema up to date with ordered migration steps.
+
+#![forbid(missing_docs)]
+
+use std::collections::HashMap;
+
+use crate::{ehandle::MinSqliteWrapperError, prelude::*};
+
+/// Name of the table that keeps the checksums of the applied steps.
+const CHECKSUM_TABLE: &str = "_min_sqlite3_migrations";
+
+/// Boxed closure of the `MigrationStep::Function` steps.
+pub type MigrationFn =
+    Box<dyn Fn(&Database) -> Result<(), MinSqliteWrapperError<'static>> + Send + Sync>;
+
+/// Up-step of the migration.
+pub enum MigrationStep {
+    /// SQL statements that are executed like `Operations::execute`.
+    Sql(String),
+    /// Rust function that runs on the connection, for the changes
+    /// that can't be expressed in plain SQL.
+    Function(MigrationFn),
+}
+
+/// Named migration step. The version of the step is its position
+/// in the `Migrations`, starting from 1.
+pub struct Migration {
+    /// Name of the step, e.g. "create items table".
+    pub name: String,
+    /// What the step does.
+    pub step: MigrationStep,
+}
+
+impl Migration {
+    /// Returns the checksum that is stored in the bookkeeping table for
+    /// this step. SQL steps are identified by their statements and
+    /// function steps by their names.
+    pub fn checksum(&self) -> String {
+        let content = match &self.step {
+            MigrationStep::Sql(sql) => sql.as_bytes(),
+            MigrationStep::Function(_) => self.name.as_bytes(),
+        };
+
+        // FNV-1a, so that the checksums stay the same across Rust versions
+        let hash = content.iter().fold(0xcbf29ce484222325_u64, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        });
+
+        format!("{:016x}", hash)
+    }
+}
+
+/// Outcome of the `Migrations::apply` call.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct MigrationReport {
+    /// `user_version` of the database before the migration.
+    pub from_version: i32,
+    /// `user_version` of the database after the migration.
+    pub to_version: i32,
+}
+
+impl MigrationReport {
+    /// Returns the number of the steps that are applied.
+    #[inline]
+    pub fn applied(&self) -> usize {
+        self.to_version.saturating_sub(self.from_version).max(0) as usize
+    }
+}
+
+/// Ordered list of the migration steps. Progress is tracked with
+/// `PRAGMA user_version`, which holds the version of the last step
+/// that is applied.
+#[derive(Default)]
+pub struct Migrations {
+    steps: Vec<Migration>,
+    verify_checksums: bool,
+}
+
+#[inline]
+fn migration_error<'a>(reason: String) -> MinSqliteWrapperError<'a> {
+    MinSqliteWrapperError {
+        kind: "sqlite3:Migrations",
+        reason,
+    }
+}
+
+impl Migrations {
+    /// Creates an empty migration list.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a step that executes `sql`.
+    ///
+    /// # Usage
+    /// let migrations = Migrations::new()
+    ///     .sql("create items", "CREATE TABLE items(id INTEGER PRIMARY KEY, name TEXT);")
+    ///     .sql("add tag", "ALTER TABLE items ADD COLUMN tag TEXT;");
+    /// ```
+    pub fn sql<N, S>(mut self, name: N, sql: S) -> Self
+    where
+        N: Into<String>,
+        S: Into<String>,
+    {
+        self.steps.push(Migration {
+            name: name.into(),
+            step: MigrationStep::Sql(sql.into()),
+        });
+        self
+    }
+
+    /// Appends a step that runs `func` on the connection.
+    ///
+    /// # Usage
+    /// let migrations = Migrations::new()
+    ///     .sql("create items", "CREATE TABLE items(id INTEGER PRIMARY KEY, name TEXT);")
+    ///     .function("normalize names", |db: &Database| {
+    ///         let statement = String::from("UPDATE items SET name = lower(name);");
+    ///         let status = db.execute(statement, None::<Box<dyn FnOnce(SqlitePrimaryResult, String)>>)?;
+    ///
+    ///         if status != SqlitePrimaryResult::Ok {
+    ///             return Err(status.into());
+    ///         }
+    ///
+    ///         Ok(())
+    ///     });
+    /// ```
+    pub fn function<N, F>(mut self, name: N, func: F) -> Self
+    where
+        N: Into<String>,
+        F: Fn(&Database) -> Result<(), MinSqliteWrapperError<'static>> + Send + Sync + 'static,
+    {
+        self.steps.push(Migration {
+            name: name.into(),
+            step: MigrationStep::Function(Box::new(func)),
+        });
+        self
+    }
+
+    /// Enables storing the checksums of the applied steps in the
+    /// `_min_sqlite3_migrations` table and verifying them on each
+    /// `apply` call, so that the edited steps are detected.
+    #[inline]
+    pub fn verify_checksums(mut self, enabled: bool) -> Self {
+        self.verify_checksums = enabled;
+        self
+    }
+
+    /// Returns the registered steps.
+    #[inline]
+    pub fn steps(&self) -> &[Migration] {
+        &self.steps
+    }
+
+    /// Returns the version that the database will have after all of the
+    /// steps are applied.
+    #[inline]
+    pub fn latest_version(&self) -> i32 {
+        self.steps.len() as i32
+    }
+
+    /// Applies the pending steps inside of a single transaction. If any
+    /// of the steps or the commit fails, none of them are applied.
+    ///
+    /// Fails without changing anything if the database has a negative or
+    /// a newer version than the `latest_version()`, or if the checksum
+    /// verification is enabled and any of the applied steps has changed.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let report = Migrations::new()
+    ///     .sql("create items", "CREATE TABLE items(id INTEGER PRIMARY KEY, name TEXT);")
+    ///     .verify_checksums(true)
+    ///     .apply(&db)
+    ///     .unwrap();
+    ///
+    /// println!("{} -> {}", report.from_version, report.to_version);
+    ///
+    /// db.close();
+    /// ```
+    pub fn apply<'a>(&self, db: &Database) -> Result<MigrationReport, MinSqliteWrapperError<'a>> {
+        db.execute_checked("BEGIN IMMEDIATE;")?;
+
+        match self.apply_pending(db) {
+            Ok(report) => match db.execute_checked("COMMIT;") {
+                Ok(()) => Ok(report),
+                // e.g. deferred foreign key violations keep the transaction open
+                Err(error) => {
+                    let _ = db.execute_checked("ROLLBACK;");
+                    Err(error)
+                }
+            },
+            Err(error) => {
+                let _ = db.execute_checked("ROLLBACK;");
+                Err(error)
+            }
+        }
+    }
+
+    fn apply_pending<'a>(
+        &self,
+        db: &Database,
+    ) -> Result<MigrationReport, MinSqliteWrapperError<'a>> {
+        let from_version = db.user_version()?;
+        let latest_version = self.latest_version();
+
+        // versions count the applied steps, a negative one isn't written by
+        // the runner and would skip all of the steps.
+        if from_version < 0 {
+            return Err(migration_error(format!(
+                "Database version {} is negative, it isn't managed by the migrations",
+                from_version
+            )));
+        }
+
+        if from_version > latest_version {
+            return Err(migration_error(format!(
+                "Database version {} is newer than the latest migration version {}",
+                from_version, latest_version
+            )));
+        }
+
+        if self.verify_checksums {
+            db.execute_checked(&format!(
+                "CREATE TABLE IF NOT EXISTS {}(
+                    version     INTEGER PRIMARY KEY,
+                    name        TEXT NOT NULL,
+                    checksum    TEXT NOT NULL
+                );",
+                CHECKSUM_TABLE
+            ))?;
+
+            self.check_applied(db, from_version)?;
+        }
+
+        for (version, migration) in self
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(i, migration)| (i as i32 + 1, migration))
+            .skip(from_version as usize)
+        {
+            let result = match &migration.step {
+                MigrationStep::Sql(sql) => db.execute_checked(sql),
+                MigrationStep::Function(func) => func(db),
+            };
+
+            if let Err(error) = result {
+                return Err(migration_error(format!(
+                    "Migration {} ({}) failed: {}",
+                    version, migration.name, error.reason
+                )));
+            }
+
+            if self.verify_checksums {
+                db.execute_checked(&format!(
+                    "INSERT OR REPLACE INTO {}(version, name, checksum) VALUES({}, {}, {});",
+                    CHECKSUM_TABLE,
+                    version,
+                    Value::from(migration.name.as_str()).to_sql_literal(),
+                    Value::from(migration.checksum()).to_sql_literal(),
+                ))?;
+            }
+        }
+
+        let status = db.set_user_version(latest_version)?;
+        if status != SqlitePrimaryResult::Ok {
+            return Err(status.into());
+        }
+
+        Ok(MigrationReport {
+            from_version,
+            to_version: latest_version,
+        })
+    }
+
+    /// Compares the stored checksums of the steps up to `version` with the
+    /// registered ones. Steps that are applied before the checksums were
+    /// enabled have no stored checksum and are skipped.
+    fn check_applied<'a>(
+        &self,
+        db: &Database,
+        version: i32,
+    ) -> Result<(), MinSqliteWrapperError<'a>> {
+        let mut stored = HashMap::new();
+        let mut sql = db.prepare_checked(&format!(
+            "SELECT version, checksum FROM {} WHERE version <= {};",
+            CHECKSUM_TABLE, version
+        ))?;
+
+        loop {
+            match sql.execute_prepared() {
+                PreparedStatementStatus::FoundRow => {
+                    stored.insert(
+                        sql.get_owned_data::<i64>(0)?,
+                        sql.get_owned_data::<String>(1)?,
+                    );
+                }
+                PreparedStatementStatus::Done => break,
+                _ => return Err(MinSqliteWrapperError::from_connection(db.rp)),
+            }
+        }
+
+        for (i, migration) in self.steps.iter().take(version as usize).enumerate() {
+            let version = i as i64 + 1;
+            if let Some(checksum) = stored.get(&version) {
+                if *checksum != migration.checksum() {
+                    return Err(migration_error(format!(
+                        "Checksum of the applied migration {} ({}) doesn't match",
+                        version, migration.name
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{prelude::*, test_support::open_memory};
+
+    fn migrations() -> Migrations {
+        Migrations::new()
+            .sql(
+                "create items",
+                "CREATE TABLE items(id INTEGER PRIMARY KEY, name TEXT);",
+            )
+            .sql("add tag", "ALTER TABLE items ADD COLUMN tag TEXT;")
+            .verify_checksums(true)
+    }
+
+    fn tables(db: &Database) -> Vec<Vec<Value>> {
+        db.query_values("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name;")
+            .unwrap()
+    }
+
+    #[test]
+    fn pending_steps_are_applied_once() {
+        let db = Database::open(":memory:").unwrap();
+
+        let report = migrations().apply(&db).unwrap();
+        assert_eq!((report.from_version, report.to_version), (0, 2));
+        assert_eq!(report.applied(), 2);
+        assert_eq!(db.user_version().unwrap(), 2);
+
+        let report = migrations()
+            .function("fill items", |db: &Database| {
+                db.execute_changes(String::from("INSERT INTO items(name) VALUES ('a');"))?;
+                Ok(())
+            })
+            .apply(&db)
+            .unwrap();
+        assert_eq!((report.from_version, report.to_version), (2, 3));
+        assert_eq!(report.applied(), 1);
+        assert_eq!(
+            db.query_values("SELECT name, tag FROM items;").unwrap(),
+            vec![vec![Value::Text(String::from("a")), Value::Null]]
+        );
+    }
+
+    #[test]
+    fn failing_step_rolls_back_all_steps() {
+        let db = Database::open(":memory:").unwrap();
+
+        let error = migrations()
+            .sql("broken", "CREATE TABLE items(id);")
+            .apply(&db)
+            .unwrap_err();
+        assert_eq!(error.kind, "sqlite3:Migrations");
+        assert!(error.reason.starts_with("Migration 3 (broken) failed"));
+
+        assert_eq!(db.user_version().unwrap(), 0);
+        assert!(tables(&db).is_empty());
+    }
+
+    #[test]
+    fn changed_steps_are_detected() {
+        let db = Database::open(":memory:").unwrap();
+        migrations().apply(&db).unwrap();
+
+        let error = Migrations::new()
+            .sql(
+                "create items",
+                "CREATE TABLE items(id INTEGER PRIMARY KEY);",
+            )
+            .sql("add tag", "ALTER TABLE items ADD COLUMN tag TEXT;")
+            .verify_checksums(true)
+            .apply(&db)
+            .unwrap_err();
+        assert_eq!(error.kind, "sqlite3:Migrations");
+    }
+
+    #[test]
+    fn unmanaged_versions_are_rejected() {
+        let db = Database::open(":memory:").unwrap();
+
+        db.set_user_version(-1).unwrap();
+        assert!(migrations().apply(&db).is_err());
+        assert_eq!(db.user_version().unwrap(), -1);
+        assert!(tables(&db).is_empty());
+
+        db.set_user_version(3).unwrap();
+        assert!(migrations().apply(&db).is_err());
+        assert_eq!(db.user_version().unwrap(), 3);
+    }
+
+    #[test]
+    fn failing_commit_rolls_back_all_steps() {
+        let db = open_memory("PRAGMA foreign_keys = ON;");
+
+        let error = Migrations::new()
+            .sql(
+                "create tables",
+                "CREATE TABLE parents(id INTEGER PRIMARY KEY);
+                 CREATE TABLE children(
+                     parent INTEGER REFERENCES parents(id) DEFERRABLE INITIALLY DEFERRED
+                 );",
+            )
+            .sql("orphan", "INSERT INTO children VALUES (1);")
+            .apply(&db)
+            .unwrap_err();
+        assert!(error.reason.contains("FOREIGN KEY"), "{}", error.reason);
+
+        // the transaction is closed and nothing is applied
+        assert_eq!(db.user_version().unwrap(), 0);
+        assert!(db.query_values("SELECT * FROM children;").is_err());
+        db.execute_checked("BEGIN; COMMIT;").unwrap();
+    }
+}
@@ -11,8 +11,12 @@ use std::{
 };
 
 use crate::connection::Database;
+use crate::value::Value;
 use crate::{bindings::sqlite3_stmt, ehandle::MinSqliteWrapperError};
-use crate::{bindings::*, statement::SqlStatement};
+use crate::{
+    bindings::*,
+    statement::{PreparedStatementStatus, Row, SqlStatement},
+};
 
 /// Defines the helper functions that work on the columns of the data rows received.
 pub trait ColumnCapabilities<'a> {
@@ -111,6 +115,11 @@ pub trait ColumnCapabilities<'a> {
         Self: Sized;
 }
 
+/// BLOB filled with zeros of the given size in bytes. Useful for
+/// reserving the space that is going to be written incrementally.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct ZeroBlob(pub u64);
+
 /// Null type alias that equals to ()
 pub type SqliteNull = ();
 
@@ -941,6 +950,68 @@ impl<'a> ColumnCapabilities<'a> for SqliteNull {
     }
 }
 
+impl<'a> ColumnCapabilities<'a> for ZeroBlob {
+    fn get_data(_stmt: *mut sqlite3_stmt, _i: usize) -> Result<Self, MinSqliteWrapperError<'a>>
+    where
+        Self: Sized,
+    {
+        unimplemented!()
+    }
+
+    fn bind_val(self, stmt: *mut sqlite3_stmt, i: usize) -> SqlitePrimaryResult
+    where
+        Self: Sized,
+    {
+        unsafe {
+            SqlitePrimaryResult::from(sqlite3_bind_zeroblob64(
+                stmt,
+                i as os::raw::c_int,
+                self.0 as os::raw::c_ulonglong,
+            ))
+        }
+    }
+}
+
+impl<'a> ColumnCapabilities<'a> for Value {
+    fn get_data(stmt: *mut sqlite3_stmt, i: usize) -> Result<Self, MinSqliteWrapperError<'a>>
+    where
+        Self: Sized,
+    {
+        unsafe {
+            match sqlite3_column_type(stmt, i as os::raw::c_int) as u32 {
+                COLUMN_NULL => Ok(Value::Null),
+                COLUMN_INTEGER => Ok(Value::Integer(ColumnCapabilities::get_data(stmt, i)?)),
+                COLUMN_FLOAT => Ok(Value::Real(ColumnCapabilities::get_data(stmt, i)?)),
+                COLUMN_BLOB => Ok(Value::Blob(ColumnCapabilities::get_data(stmt, i)?)),
+                _ => Ok(Value::Text(ColumnCapabilities::get_data(stmt, i)?)),
+            }
+        }
+    }
+
+    fn bind_val(self, stmt: *mut sqlite3_stmt, i: usize) -> SqlitePrimaryResult
+    where
+        Self: Sized,
+    {
+        match self {
+            Value::Null => SQLITE_NULL.bind_val(stmt, i),
+            Value::Integer(t) => t.bind_val(stmt, i),
+            Value::Real(t) => t.bind_val(stmt, i),
+            Value::Text(t) => t.bind_val(stmt, i),
+            Value::Blob(t) => t.bind_val(stmt, i),
+        }
+    }
+}
+
+/// Rows returned by the `RETURNING` clause of a statement, along with
+/// the number of rows the statement changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Returning<T> {
+    /// Mapped rows in the order SQLite returned them.
+    pub rows: Vec<T>,
+    /// Number of rows that are inserted, updated or deleted.
+    pub changes: u64,
+}
+
 /// Defines SQL functions.
 pub trait Operations {
     /// A wrapper around prepare(), execute_prepared(), and kill(), that allows an
@@ -999,6 +1070,106 @@ pub trait Operations {
     ) -> Result<SqlStatement, MinSqliteWrapperError<'a>>
     where
         F: FnOnce(SqlitePrimaryResult, String);
+
+    /// Runs a single SQL statement and returns the number of rows that are
+    /// inserted, updated or deleted by it. Fails if `statement` contains
+    /// more than one statement.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let changes = db.execute_changes(String::from(
+    ///     "DELETE FROM example_table WHERE tag = 'outdated';"
+    /// )).unwrap();
+    ///
+    /// db.close();
+    /// ```
+    fn execute_changes<'a>(&self, statement: String) -> Result<u64, MinSqliteWrapperError<'a>>;
+
+    /// Returns the rowid of the most recent successful INSERT into a rowid
+    /// table on the connection, or 0 if there isn't any.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// db.execute(
+    ///     String::from("INSERT INTO example_table (name) VALUES ('Onur');"),
+    ///     None::<Box<dyn FnOnce(SqlitePrimaryResult, String)>>,
+    /// ).unwrap();
+    ///
+    /// let id = db.last_insert_rowid();
+    ///
+    /// db.close();
+    /// ```
+    fn last_insert_rowid(&self) -> i64;
+
+    /// Returns the number of rows that are inserted, updated or deleted by
+    /// the most recently completed INSERT, UPDATE or DELETE statement.
+    fn changes(&self) -> u64;
+
+    /// Returns the total number of rows that are inserted, updated or deleted
+    /// since the connection is opened.
+    fn total_changes(&self) -> u64;
+
+    /// Runs a single INSERT statement that has `RETURNING` clause with the
+    /// given parameters, and reads the first column of each returned row
+    /// as `T`.
+    ///
+    /// # Panics
+    /// - If the data type is incorrectly specified.
+    ///
+    /// # Warning
+    /// `T` must be an owned type, borrowed types like `&str` are not valid
+    /// after the statement is finished.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let inserted = db.insert_returning::<i64>(
+    ///     String::from("INSERT INTO example_table (name, tag) VALUES (?, ?) RETURNING id;"),
+    ///     &["Onur".into(), "stable".into()],
+    /// ).unwrap();
+    ///
+    /// let id = inserted.rows[0];
+    ///
+    /// db.close();
+    /// ```
+    fn insert_returning<'a, T>(
+        &self,
+        statement: String,
+        params: &[Value],
+    ) -> Result<Returning<T>, MinSqliteWrapperError<'a>>
+    where
+        T: ColumnCapabilities<'static> + 'static;
+
+    /// Runs a single SQL statement with the given parameters, and maps each
+    /// row it returns (e.g. with the `RETURNING` clause) with `map_row`.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let updated = db.execute_returning(
+    ///     String::from("UPDATE example_table SET tag = ? WHERE name = ? RETURNING id, name;"),
+    ///     &["stable".into(), "Onur".into()],
+    ///     |row: &Row| (row.get::<i64>(0).unwrap(), row.get::<String>(1).unwrap()),
+    /// ).unwrap();
+    ///
+    /// println!("{} rows are updated: {:?}", updated.changes, updated.rows);
+    ///
+    /// db.close();
+    /// ```
+    fn execute_returning<'a, R, F>(
+        &self,
+        statement: String,
+        params: &[Value],
+        map_row: F,
+    ) -> Result<Returning<R>, MinSqliteWrapperError<'a>>
+    where
+        F: FnMut(&Row) -> R;
 }
 
 impl Operations for Database {
@@ -1056,4 +1227,305 @@ impl Operations for Database {
 
         Ok(SqlStatement::new(stmt))
     }
+
+    #[inline]
+    fn execute_changes<'a>(&self, statement: String) -> Result<u64, MinSqliteWrapperError<'a>> {
+        self.prepare_single(&statement)?.execute_changes()
+    }
+
+    #[inline]
+    fn last_insert_rowid(&self) -> i64 {
+        unsafe { sqlite3_last_insert_rowid(self.rp) }
+    }
+
+    #[inline]
+    fn changes(&self) -> u64 {
+        unsafe { sqlite3_changes64(self.rp) as u64 }
+    }
+
+    #[inline]
+    fn total_changes(&self) -> u64 {
+        unsafe { sqlite3_total_changes64(self.rp) as u64 }
+    }
+
+    fn insert_returning<'a, T>(
+        &self,
+        statement: String,
+        params: &[Value],
+    ) -> Result<Returning<T>, MinSqliteWrapperError<'a>>
+    where
+        T: ColumnCapabilities<'static> + 'static,
+    {
+        let returning = self
+            .prepare_single(&statement)?
+            .collect_rows(params, |row: &Row| row.0.get_owned_data::<T>(0))?;
+
+        Ok(Returning {
+            rows: returning.rows.into_iter().collect::<Result<Vec<T>, _>>()?,
+            changes: returning.changes,
+        })
+    }
+
+    #[inline]
+    fn execute_returning<'a, R, F>(
+        &self,
+        statement: String,
+        params: &[Value],
+        map_row: F,
+    ) -> Result<Returning<R>, MinSqliteWrapperError<'a>>
+    where
+        F: FnMut(&Row) -> R,
+    {
+        self.prepare_single(&statement)?
+            .collect_rows(params, map_row)
+    }
+}
+
+impl Database {
+    /// Prepares the statement like `prepare`, but fails instead of returning
+    /// an empty statement if the SQL can not be compiled.
+    pub(crate) fn prepare_checked<'a>(
+        &self,
+        statement: &str,
+    ) -> Result<SqlStatement, MinSqliteWrapperError<'a>> {
+        let st = CString::new(statement)?;
+        let mut stmt = ptr::null_mut();
+
+        unsafe {
+            let status: SqlitePrimaryResult =
+                sqlite3_prepare_v2(self.rp, st.as_ptr(), -1, &mut stmt, ptr::null_mut()).into();
+
+            if status != SqlitePrimaryResult::Ok {
+                return Err(MinSqliteWrapperError::from_connection(self.rp));
+            }
+        }
+
+        Ok(SqlStatement::new(stmt))
+    }
+
+    /// Prepares the statement like `prepare_checked`, but fails if it's
+    /// followed by another statement, which would be silently skipped.
+    pub(crate) fn prepare_single<'a>(
+        &self,
+        statement: &str,
+    ) -> Result<SqlStatement, MinSqliteWrapperError<'a>> {
+        let st = CString::new(statement)?;
+        let mut stmt = ptr::null_mut();
+        let mut tail = ptr::null();
+
+        unsafe {
+            let status: SqlitePrimaryResult =
+                sqlite3_prepare_v2(self.rp, st.as_ptr(), -1, &mut stmt, &mut tail).into();
+
+            if status != SqlitePrimaryResult::Ok {
+                return Err(MinSqliteWrapperError::from_connection(self.rp));
+            }
+
+            let sql = SqlStatement::new(stmt);
+
+            // whitespace and comments compile to no statement, anything else
+            // in the tail is another statement, even if it doesn't compile yet
+            let mut rest = ptr::null_mut();
+            let status: SqlitePrimaryResult =
+                sqlite3_prepare_v2(self.rp, tail, -1, &mut rest, ptr::null_mut()).into();
+
+            if !rest.is_null() || status != SqlitePrimaryResult::Ok {
+                sqlite3_finalize(rest);
+                return Err(MinSqliteWrapperError {
+                    kind: "sqlite3:SqlitePrimaryResult",
+                    reason: String::from("Misuse: only a single statement can be executed"),
+                });
+            }
+
+            Ok(sql)
+        }
+    }
+
+    /// Runs the statement and collects all of the rows it returns.
+    pub(crate) fn query_values<'a>(
+        &self,
+        statement: &str,
+    ) -> Result<Vec<Vec<Value>>, MinSqliteWrapperError<'a>> {
+        let mut sql = self.prepare_checked(statement)?;
+        let mut rows = Vec::new();
+        loop {
+            match sql.execute_prepared() {
+                PreparedStatementStatus::FoundRow => {
+                    let row = (0..sql.column_count())
+                        .map(|i| sql.get_owned_data::<Value>(i))
+                        .collect::<Result<Vec<Value>, _>>()?;
+                    rows.push(row);
+                }
+                PreparedStatementStatus::Done => return Ok(rows),
+                _ => return Err(MinSqliteWrapperError::from_connection(self.rp)),
+            }
+        }
+    }
+
+    /// Executes the statements like `execute`, but fails with the error
+    /// message of the connection if any of them can not be executed.
+    pub(crate) fn execute_checked<'a>(
+        &self,
+        statement: &str,
+    ) -> Result<(), MinSqliteWrapperError<'a>> {
+        let st = CString::new(statement)?;
+
+        unsafe {
+            let status: SqlitePrimaryResult =
+                sqlite3_exec(self.rp, st.as_ptr(), None, ptr::null_mut(), ptr::null_mut()).into();
+
+            if status != SqlitePrimaryResult::Ok {
+                return Err(MinSqliteWrapperError::from_connection(self.rp));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{prelude::*, test_support::open_memory};
+
+    fn open() -> Database {
+        open_memory("CREATE TABLE t(id INTEGER PRIMARY KEY, name TEXT);")
+    }
+
+    #[test]
+    fn change_counters_follow_the_statements() {
+        let db = open();
+
+        assert_eq!(
+            db.execute_changes(String::from(
+                "INSERT INTO t(name) VALUES ('a'), ('b'), ('c');"
+            ))
+            .unwrap(),
+            3
+        );
+        assert_eq!(db.last_insert_rowid(), 3);
+        assert_eq!(db.changes(), 3);
+
+        assert_eq!(
+            db.execute_changes(String::from("UPDATE t SET name = 'x' WHERE id > 1;"))
+                .unwrap(),
+            2
+        );
+        assert_eq!(db.total_changes(), 5);
+    }
+
+    #[test]
+    fn statements_without_changes_report_zero() {
+        let db = open();
+        db.execute_changes(String::from("INSERT INTO t(name) VALUES ('a'), ('b');"))
+            .unwrap();
+
+        assert_eq!(
+            db.execute_changes(String::from("SELECT * FROM t;"))
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            db.execute_changes(String::from("CREATE TABLE u(x);"))
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            db.execute_changes(String::from("DELETE FROM t WHERE id > 10;"))
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn trailing_statements_are_rejected() {
+        let db = open();
+        db.execute_changes(String::from("INSERT INTO t(name) VALUES ('a'), ('b');"))
+            .unwrap();
+
+        let error = db
+            .execute_changes(String::from("DELETE FROM t WHERE id = 1; DELETE FROM t;"))
+            .unwrap_err();
+        assert_eq!(
+            error.reason,
+            "Misuse: only a single statement can be executed"
+        );
+        assert_eq!(db.total_changes(), 2);
+
+        // whitespace and comments after the statement are fine
+        assert_eq!(
+            db.execute_changes(String::from("DELETE FROM t WHERE id = 1; -- done\n"))
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn invalid_statements_fail() {
+        let db = open();
+
+        assert!(db
+            .execute_changes(String::from("INSERT INTO missing VALUES (1);"))
+            .is_err());
+        assert!(db
+            .execute_changes(String::from("INSERT INTO t(id) VALUES (1), (1);"))
+            .is_err());
+    }
+
+    #[test]
+    fn returning_rows_are_collected() {
+        let db = open();
+
+        let inserted = db
+            .insert_returning::<i64>(
+                String::from("INSERT INTO t(name) VALUES (?), (?) RETURNING id;"),
+                &[Value::from("a"), Value::from("b")],
+            )
+            .unwrap();
+        assert_eq!(inserted.rows, [1, 2]);
+        assert_eq!(inserted.changes, 2);
+
+        let updated = db
+            .execute_returning(
+                String::from("UPDATE t SET name = upper(name) WHERE id = ? RETURNING name;"),
+                &[Value::from(2)],
+                |row: &Row| row.get::<String>(0).unwrap(),
+            )
+            .unwrap();
+        assert_eq!(updated.rows, ["B"]);
+        assert_eq!(updated.changes, 1);
+    }
+
+    #[test]
+    fn returning_reports_zero_changes_for_queries() {
+        let db = open();
+        db.execute_changes(String::from("INSERT INTO t(name) VALUES ('a');"))
+            .unwrap();
+
+        let selected = db
+            .execute_returning(String::from("SELECT name FROM t;"), &[], |row: &Row| {
+                row.get::<String>(0).unwrap()
+            })
+            .unwrap();
+        assert_eq!(selected.rows, ["a"]);
+        assert_eq!(selected.changes, 0);
+    }
+
+    #[test]
+    fn returning_rejects_trailing_statements_and_extra_parameters() {
+        let db = open();
+
+        assert!(db
+            .insert_returning::<i64>(
+                String::from("INSERT INTO t(name) VALUES ('a') RETURNING id; DELETE FROM t;"),
+                &[],
+            )
+            .is_err());
+        assert!(db
+            .insert_returning::<i64>(
+                String::from("INSERT INTO t(name) VALUES (?) RETURNING id;"),
+                &[Value::from("a"), Value::from("b")],
+            )
+            .is_err());
+        assert_eq!(db.total_changes(), 0);
+    }
 }
@@ -0,0 +1,421 @@
+//! This module contains data-types and functions to query and
+//! update the PRAGMAs of SQLite connection.
+
+#![forbid(missing_docs)]
+
+use crate::{ehandle::MinSqliteWrapperError, prelude::*};
+
+/// Values of `PRAGMA synchronous`.
+#[repr(i64)]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Synchronous {
+    /// SQLite continues without syncing as soon as it has handed data off to the operating system.
+    Off = 0,
+    /// SQLite syncs at the most critical moments, but less often than in `Full` mode.
+    Normal = 1,
+    /// SQLite syncs to ensure that all content is safely written to the disk surface.
+    Full = 2,
+    /// Like `Full`, but also syncs the directory of the rollback journal.
+    Extra = 3,
+}
+
+/// Makes sure the pragma name can't be used for SQL injection.
+fn validate_pragma_name<'a>(name: &str) -> Result<(), MinSqliteWrapperError<'a>> {
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Ok(());
+    }
+
+    Err(MinSqliteWrapperError {
+        kind: "sqlite3:Pragma",
+        reason: format!("Invalid pragma name {:?}", name),
+    })
+}
+
+/// Returns the pragma name, qualified with the schema if it's given.
+#[inline]
+fn qualified_name(schema: Option<DatabaseName>, name: &str) -> String {
+    match schema {
+        Some(schema) => format!("{}.{}", schema.to_sql_identifier(), name),
+        None => name.to_owned(),
+    }
+}
+
+#[inline]
+fn expect_integer<'a>(name: &str, value: Value) -> Result<i64, MinSqliteWrapperError<'a>> {
+    match value {
+        Value::Integer(t) => Ok(t),
+        other => Err(MinSqliteWrapperError {
+            kind: "sqlite3:Pragma",
+            reason: format!("PRAGMA {} returned non-integer value {:?}", name, other),
+        }),
+    }
+}
+
+/// Specifies the PRAGMA functions of the SQLite connection.
+pub trait Pragma {
+    /// Returns the value of a pragma that returns a single value,
+    /// e.g. `user_version`. Pragmas that are specific to a database apply
+    /// to `schema`, or to the "main" database if it's `None`.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let encoding = db.pragma_query_value(None, "encoding").unwrap();
+    /// assert_eq!(encoding, Value::Text(String::from("UTF-8")));
+    ///
+    /// db.close();
+    /// ```
+    fn pragma_query_value<'a>(
+        &self,
+        schema: Option<DatabaseName>,
+        name: &str,
+    ) -> Result<Value, MinSqliteWrapperError<'a>>;
+
+    /// Returns all the rows of a pragma, optionally with an argument,
+    /// e.g. `table_info` of a table in `schema`.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let columns = db.pragma_query(Some(DatabaseName::Main), "table_info", Some("items".into())).unwrap();
+    /// for column in columns {
+    ///     println!("{:?}", column[1]);
+    /// }
+    ///
+    /// db.close();
+    /// ```
+    fn pragma_query<'a>(
+        &self,
+        schema: Option<DatabaseName>,
+        name: &str,
+        argument: Option<Value>,
+    ) -> Result<Vec<Vec<Value>>, MinSqliteWrapperError<'a>>;
+
+    /// Sets the value of a pragma on `schema`, or on the "main" database
+    /// if it's `None`. The value is quoted as SQL literal.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let status = db.pragma_update(None, "temp_store", "MEMORY".into()).unwrap();
+    ///
+    /// if status != SqlitePrimaryResult::Ok {
+    ///    ...
+    /// }
+    ///
+    /// db.close();
+    /// ```
+    fn pragma_update<'a>(
+        &self,
+        schema: Option<DatabaseName>,
+        name: &str,
+        value: Value,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>;
+
+    /// Returns `PRAGMA user_version` of the "main" database.
+    fn user_version<'a>(&self) -> Result<i32, MinSqliteWrapperError<'a>>;
+
+    /// Sets `PRAGMA user_version` of the "main" database.
+    fn set_user_version<'a>(
+        &self,
+        version: i32,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>;
+
+    /// Returns `PRAGMA application_id` of the "main" database.
+    fn application_id<'a>(&self) -> Result<i32, MinSqliteWrapperError<'a>>;
+
+    /// Sets `PRAGMA application_id` of the "main" database.
+    fn set_application_id<'a>(
+        &self,
+        id: i32,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>;
+
+    /// Returns `PRAGMA foreign_keys`.
+    fn foreign_keys<'a>(&self) -> Result<bool, MinSqliteWrapperError<'a>>;
+
+    /// Sets `PRAGMA foreign_keys`.
+    fn set_foreign_keys<'a>(
+        &self,
+        enabled: bool,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>;
+
+    /// Returns `PRAGMA synchronous` of the "main" database.
+    fn synchronous<'a>(&self) -> Result<Synchronous, MinSqliteWrapperError<'a>>;
+
+    /// Sets `PRAGMA synchronous` of the "main" database.
+    fn set_synchronous<'a>(
+        &self,
+        mode: Synchronous,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>;
+
+    /// Returns `PRAGMA cache_size` of the "main" database. Positive values are number of pages,
+    /// negative values are size in KiB.
+    fn cache_size<'a>(&self) -> Result<i64, MinSqliteWrapperError<'a>>;
+
+    /// Sets `PRAGMA cache_size` of the "main" database. Positive values are number of pages,
+    /// negative values are size in KiB.
+    fn set_cache_size<'a>(
+        &self,
+        size: i64,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>;
+
+    /// Returns `PRAGMA mmap_size` of the "main" database in bytes.
+    fn mmap_size<'a>(&self) -> Result<i64, MinSqliteWrapperError<'a>>;
+
+    /// Sets `PRAGMA mmap_size` of the "main" database in bytes.
+    fn set_mmap_size<'a>(
+        &self,
+        size: i64,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>;
+
+    /// Returns `PRAGMA busy_timeout` in milliseconds.
+    fn busy_timeout<'a>(&self) -> Result<i64, MinSqliteWrapperError<'a>>;
+
+    /// Sets `PRAGMA busy_timeout` in milliseconds.
+    fn set_busy_timeout<'a>(
+        &self,
+        milliseconds: i64,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>;
+}
+
+impl Pragma for Database {
+    fn pragma_query_value<'a>(
+        &self,
+        schema: Option<DatabaseName>,
+        name: &str,
+    ) -> Result<Value, MinSqliteWrapperError<'a>> {
+        validate_pragma_name(name)?;
+
+        let mut sql = self.prepare_checked(&format!("PRAGMA {};", qualified_name(schema, name)))?;
+        match sql.execute_prepared() {
+            PreparedStatementStatus::FoundRow => sql.get_owned_data::<Value>(0),
+            PreparedStatementStatus::Done => Err(MinSqliteWrapperError {
+                kind: "sqlite3:Pragma",
+                reason: format!("PRAGMA {} returned no value", name),
+            }),
+            _ => Err(MinSqliteWrapperError::from_connection(self.rp)),
+        }
+    }
+
+    fn pragma_query<'a>(
+        &self,
+        schema: Option<DatabaseName>,
+        name: &str,
+        argument: Option<Value>,
+    ) -> Result<Vec<Vec<Value>>, MinSqliteWrapperError<'a>> {
+        validate_pragma_name(name)?;
+
+        let name = qualified_name(schema, name);
+        let statement = match argument {
+            Some(argument) => format!("PRAGMA {}({});", name, argument.to_sql_literal()),
+            None => format!("PRAGMA {};", name),
+        };
+
+        self.query_values(&statement)
+    }
+
+    fn pragma_update<'a>(
+        &self,
+        schema: Option<DatabaseName>,
+        name: &str,
+        value: Value,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>> {
+        validate_pragma_name(name)?;
+
+        let mut sql = self.prepare_checked(&format!(
+            "PRAGMA {} = {};",
+            qualified_name(schema, name),
+            value.to_sql_literal()
+        ))?;
+        // some pragmas (e.g. journal_mode) return the new value as a row
+        loop {
+            match sql.execute_prepared() {
+                PreparedStatementStatus::FoundRow => continue,
+                PreparedStatementStatus::Done => return Ok(SqlitePrimaryResult::Ok),
+                PreparedStatementStatus::Other(other_id) => {
+                    return Ok(SqlitePrimaryResult::from(other_id))
+                }
+                PreparedStatementStatus::Interrupted => return Ok(SqlitePrimaryResult::Interrupt),
+            }
+        }
+    }
+
+    #[inline]
+    fn user_version<'a>(&self) -> Result<i32, MinSqliteWrapperError<'a>> {
+        Ok(expect_integer(
+            "user_version",
+            self.pragma_query_value(None, "user_version")?,
+        )? as i32)
+    }
+
+    #[inline]
+    fn set_user_version<'a>(
+        &self,
+        version: i32,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>> {
+        self.pragma_update(None, "user_version", version.into())
+    }
+
+    #[inline]
+    fn application_id<'a>(&self) -> Result<i32, MinSqliteWrapperError<'a>> {
+        Ok(expect_integer(
+            "application_id",
+            self.pragma_query_value(None, "application_id")?,
+        )? as i32)
+    }
+
+    #[inline]
+    fn set_application_id<'a>(
+        &self,
+        id: i32,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>> {
+        self.pragma_update(None, "application_id", id.into())
+    }
+
+    #[inline]
+    fn foreign_keys<'a>(&self) -> Result<bool, MinSqliteWrapperError<'a>> {
+        Ok(expect_integer(
+            "foreign_keys",
+            self.pragma_query_value(None, "foreign_keys")?,
+        )? != 0)
+    }
+
+    #[inline]
+    fn set_foreign_keys<'a>(
+        &self,
+        enabled: bool,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>> {
+        self.pragma_update(None, "foreign_keys", enabled.into())
+    }
+
+    fn synchronous<'a>(&self) -> Result<Synchronous, MinSqliteWrapperError<'a>> {
+        match expect_integer("synchronous", self.pragma_query_value(None, "synchronous")?)? {
+            0 => Ok(Synchronous::Off),
+            1 => Ok(Synchronous::Normal),
+            2 => Ok(Synchronous::Full),
+            3 => Ok(Synchronous::Extra),
+            other => Err(MinSqliteWrapperError {
+                kind: "sqlite3:Pragma",
+                reason: format!("Unknown synchronous mode {}", other),
+            }),
+        }
+    }
+
+    #[inline]
+    fn set_synchronous<'a>(
+        &self,
+        mode: Synchronous,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>> {
+        self.pragma_update(None, "synchronous", Value::Integer(mode as i64))
+    }
+
+    #[inline]
+    fn cache_size<'a>(&self) -> Result<i64, MinSqliteWrapperError<'a>> {
+        expect_integer("cache_size", self.pragma_query_value(None, "cache_size")?)
+    }
+
+    #[inline]
+    fn set_cache_size<'a>(
+        &self,
+        size: i64,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>> {
+        self.pragma_update(None, "cache_size", size.into())
+    }
+
+    #[inline]
+    fn mmap_size<'a>(&self) -> Result<i64, MinSqliteWrapperError<'a>> {
+        expect_integer("mmap_size", self.pragma_query_value(None, "mmap_size")?)
+    }
+
+    #[inline]
+    fn set_mmap_size<'a>(
+        &self,
+        size: i64,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>> {
+        self.pragma_update(None, "mmap_size", size.into())
+    }
+
+    #[inline]
+    fn busy_timeout<'a>(&self) -> Result<i64, MinSqliteWrapperError<'a>> {
+        expect_integer("timeout", self.pragma_query_value(None, "busy_timeout")?)
+    }
+
+    #[inline]
+    fn set_busy_timeout<'a>(
+        &self,
+        milliseconds: i64,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>> {
+        self.pragma_update(None, "busy_timeout", milliseconds.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{prelude::*, test_support::open_memory};
+
+    #[test]
+    fn typed_pragmas_are_read_back() {
+        let db = Database::open(":memory:").unwrap();
+
+        db.set_user_version(7).unwrap();
+        assert_eq!(db.user_version().unwrap(), 7);
+
+        db.set_application_id(0x4c504d).unwrap();
+        assert_eq!(db.application_id().unwrap(), 0x4c504d);
+
+        db.set_foreign_keys(true).unwrap();
+        assert!(db.foreign_keys().unwrap());
+
+        db.set_synchronous(Synchronous::Off).unwrap();
+        assert_eq!(db.synchronous().unwrap(), Synchronous::Off);
+
+        db.set_cache_size(-4096).unwrap();
+        assert_eq!(db.cache_size().unwrap(), -4096);
+
+        db.set_busy_timeout(250).unwrap();
+        assert_eq!(db.busy_timeout().unwrap(), 250);
+    }
+
+    #[test]
+    fn pragmas_are_queried_with_arguments() {
+        let db = open_memory("CREATE TABLE items(id INTEGER, name TEXT);");
+
+        assert_eq!(
+            db.pragma_query_value(None, "encoding").unwrap(),
+            Value::Text(String::from("UTF-8"))
+        );
+
+        let columns = db
+            .pragma_query(
+                Some(DatabaseName::Main),
+                "table_info",
+                Some(Value::Text(String::from("items"))),
+            )
+            .unwrap();
+        let names: Vec<Value> = columns.into_iter().map(|row| row[1].clone()).collect();
+        assert_eq!(
+            names,
+            vec![
+                Value::Text(String::from("id")),
+                Value::Text(String::from("name")),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_pragma_names_are_rejected() {
+        let db = Database::open(":memory:").unwrap();
+
+        for name in ["", "user_version; DROP TABLE t", "a.b"] {
+            let error = db.pragma_query_value(None, name).unwrap_err();
+            assert_eq!(error.kind, "sqlite3:Pragma");
+        }
+
+        // unknown pragmas are no-ops without a value
+        let error = db.pragma_query_value(None, "no_such_pragma").unwrap_err();
+        assert_eq!(error.reason, "PRAGMA no_such_pragma returned no value");
+    }
+}
@@ -1,8 +1,49 @@
 //! This module provides all the necessary modules as public to
 //! keep your `use` statements using `use min_sqlite3_sys::prelude::*;`.
 
+pub use crate::attach::{Attachments, DatabaseEntry, DatabaseName};
+pub use crate::authorizer::{
+    AccessControl, AuthAction, Authorization, ReadOnlyAuthorizer, TransactionOperation,
+};
+pub use crate::backup::{Backup, BackupOperations, BackupProgress, BackupStatus};
 pub use crate::bindings::SqlitePrimaryResult;
-pub use crate::connection::{Connection, Database};
+pub use crate::blob::{Blob, BlobOperations};
+#[cfg(not(feature = "loadable-extension"))]
+pub use crate::config::ThreadingMode;
+pub use crate::connection::{Connection, Database, OpenFlags};
+pub use crate::csv::{CsvOperations, CsvOptions};
 pub use crate::ehandle::MinSqliteWrapperError;
-pub use crate::operations::{Operations, SqliteNull, SQLITE_NULL};
-pub use crate::statement::PreparedStatementStatus;
+pub use crate::extension::{Extensions, LoadExtensionGuard};
+pub use crate::fault_injection::{FaultFile, FaultOperation, FaultScript, FaultVfs};
+pub use crate::fts5::{
+    Fts5, Fts5Detail, Fts5Match, Fts5Query, Fts5Table, TokenizeReason, Tokenizer, Tokens,
+};
+pub use crate::hooks::{Hooks, Preupdate, PreupdateOperation};
+pub use crate::interrupt::{Interrupt, InterruptHandle};
+pub use crate::json::{Json, JsonEntry, JsonOperations, JsonPath, JsonType};
+pub use crate::migrations::{Migration, MigrationReport, MigrationStep, Migrations};
+pub use crate::operations::{Operations, Returning, SqliteNull, ZeroBlob, SQLITE_NULL};
+pub use crate::pragma::{Pragma, Synchronous};
+pub use crate::rtree::{RTree, RTreeQueryInfo, RTreeTable, RTreeWithin};
+pub use crate::schema::{
+    Column, ColumnMetadata, ForeignKey, Index, Schema, SchemaIntrospection, Table, TableKind,
+    Trigger,
+};
+pub use crate::serialize::Serialization;
+pub use crate::session::{
+    Change, ChangeOperation, Changeset, ChangesetIter, Conflict, ConflictAction, ConflictType,
+    Session, SessionOperations,
+};
+pub use crate::statement::{PreparedStatementStatus, Row};
+pub use crate::table_functions::{Array, TableFunctions};
+pub use crate::trace::{TraceEvent, TraceEvents, TracedStatement, Tracing};
+pub use crate::value::{Value, ValueRef};
+pub use crate::vfs::{
+    AccessCheck, LockLevel, MemoryFile, MemoryVfs, PassthroughFile, PassthroughVfs, SyncFlags, Vfs,
+    VfsFile, VfsFileName, VfsResult,
+};
+pub use crate::vtab::{
+    ConstraintOp, IndexConstraint, IndexInfo, Module, ModuleKind, OrderBy, UpdateOperation, VTab,
+    VTabCursor, Values, VirtualTables,
+};
+pub use crate::wal::{CheckpointMode, JournalMode, WalOperations};
@@ -0,0 +1,401 @@
+//! This module contains data-types and functions for the spatial indexes
+//! of the R*Tree extension, and for registering R*Tree query callbacks.
+
+#![forbid(missing_docs)]
+
+use std::{
+    ffi::CString,
+    os,
+    panic::{self, AssertUnwindSafe},
+    slice,
+};
+
+use crate::{
+    attach::quote_identifier,
+    bindings::{sqlite3_rtree_query_callback, sqlite3_rtree_query_info},
+    ehandle::MinSqliteWrapperError,
+    prelude::*,
+};
+
+/// Binder of SQLITE_ERROR from C source
+const SQLITE_ERROR: os::raw::c_int = 1;
+
+/// Maximum number of dimensions of an R*Tree table.
+const MAX_DIMENSIONS: usize = 5;
+
+/// Definition of an R*Tree table that is created with `create`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RTreeTable {
+    name: String,
+    id_column: String,
+    dimensions: Vec<(String, String)>,
+    auxiliary_columns: Vec<String>,
+    integer_coordinates: bool,
+}
+
+impl RTreeTable {
+    /// Creates the definition of the R*Tree table `name`, with the `id`
+    /// column as its rowid.
+    #[inline]
+    pub fn new<N: Into<String>>(name: N) -> Self {
+        RTreeTable {
+            name: name.into(),
+            id_column: String::from("id"),
+            dimensions: Vec::new(),
+            auxiliary_columns: Vec::new(),
+            integer_coordinates: false,
+        }
+    }
+
+    /// Sets the name of the rowid column.
+    #[inline]
+    pub fn id_column(mut self, name: &str) -> Self {
+        self.id_column = name.to_owned();
+        self
+    }
+
+    /// Adds a dimension with its lower and upper bound columns. A table
+    /// with one dimension indexes intervals, e.g. version ranges.
+    #[inline]
+    pub fn dimension(mut self, min_column: &str, max_column: &str) -> Self {
+        self.dimensions
+            .push((min_column.to_owned(), max_column.to_owned()));
+        self
+    }
+
+    /// Adds a column that is stored along with the entry but not indexed.
+    #[inline]
+    pub fn auxiliary_column(mut self, name: &str) -> Self {
+        self.auxiliary_columns.push(name.to_owned());
+        self
+    }
+
+    /// Stores the coordinates as 32-bit integers (`rtree_i32`) instead of
+    /// 32-bit floats, so that integer bounds aren't rounded.
+    #[inline]
+    pub fn integer_coordinates(mut self) -> Self {
+        self.integer_coordinates = true;
+        self
+    }
+
+    /// Returns the `CREATE VIRTUAL TABLE` statement of the table.
+    pub fn sql(&self) -> String {
+        let columns: Vec<String> = std::iter::once(quote_identifier(&self.id_column))
+            .chain(
+                self.dimensions
+                    .iter()
+                    .flat_map(|(min, max)| [quote_identifier(min), quote_identifier(max)]),
+            )
+            .chain(
+                self.auxiliary_columns
+                    .iter()
+                    .map(|column| format!("+{}", quote_identifier(column))),
+            )
+            .collect();
+
+        let module = if self.integer_coordinates {
+            "rtree_i32"
+        } else {
+            "rtree"
+        };
+
+        format!(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS {} USING {}({});",
+            quote_identifier(&self.name),
+            module,
+            columns.join(", ")
+        )
+    }
+
+    /// Creates the table if it doesn't exist.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// RTreeTable::new("version_ranges")
+    ///     .dimension("min_version", "max_version")
+    ///     .auxiliary_column("package")
+    ///     .integer_coordinates()
+    ///     .create(&db)
+    ///     .unwrap();
+    ///
+    /// db.close();
+    /// ```
+    pub fn create<'a>(&self, db: &Database) -> Result<(), MinSqliteWrapperError<'a>> {
+        if self.dimensions.is_empty() || self.dimensions.len() > MAX_DIMENSIONS {
+            return Err(MinSqliteWrapperError {
+                kind: "sqlite3:RTree",
+                reason: format!(
+                    "R*Tree tables must have 1 to {} dimensions, got {}",
+                    MAX_DIMENSIONS,
+                    self.dimensions.len()
+                ),
+            });
+        }
+
+        db.execute_checked(&self.sql())
+    }
+}
+
+/// Relation between the region of an R*Tree query and a node or entry.
+#[repr(i32)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum RTreeWithin {
+    /// Outside of the region, the node or entry is skipped.
+    NotWithin = 0,
+    /// Partially overlaps the region.
+    PartlyWithin = 1,
+    /// Fully contained within the region.
+    FullyWithin = 2,
+}
+
+impl From<os::raw::c_int> for RTreeWithin {
+    #[inline]
+    fn from(value: os::raw::c_int) -> Self {
+        match value {
+            0 => RTreeWithin::NotWithin,
+            1 => RTreeWithin::PartlyWithin,
+            _ => RTreeWithin::FullyWithin,
+        }
+    }
+}
+
+/// Node or entry of the R*Tree that is checked by a query callback.
+///
+/// The visibility and the score start with the values of the parent
+/// node, and the callback may change them with `set_within` and
+/// `set_score`. Entries and nodes with lower scores are visited first.
+pub struct RTreeQueryInfo<'a>(&'a mut sqlite3_rtree_query_info);
+
+impl<'a> RTreeQueryInfo<'a> {
+    /// Returns the arguments of the SQL function, e.g. `[1.0, 4.0]` for
+    /// `WHERE id MATCH overlaps(1, 4)`.
+    #[inline]
+    pub fn params(&self) -> &[f64] {
+        if self.0.a_param.is_null() || self.0.n_param <= 0 {
+            return &[];
+        }
+
+        unsafe { slice::from_raw_parts(self.0.a_param, self.0.n_param as usize) }
+    }
+
+    /// Returns the bounds of the node or entry, as pairs of the lower and
+    /// upper bound of each dimension.
+    #[inline]
+    pub fn coords(&self) -> &[f64] {
+        if self.0.a_coord.is_null() || self.0.n_coord <= 0 {
+            return &[];
+        }
+
+        unsafe { slice::from_raw_parts(self.0.a_coord, self.0.n_coord as usize) }
+    }
+
+    /// Returns the level of the node, `0` for the entries of the table.
+    #[inline]
+    pub fn level(&self) -> i32 {
+        self.0.i_level
+    }
+
+    /// Returns the level of the root node.
+    #[inline]
+    pub fn max_level(&self) -> i32 {
+        self.0.mx_level
+    }
+
+    /// Returns the rowid of the entry, `None` for the inner nodes.
+    #[inline]
+    pub fn rowid(&self) -> Option<i64> {
+        if self.0.i_level == 0 {
+            Some(self.0.i_rowid)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the score of the parent node.
+    #[inline]
+    pub fn parent_score(&self) -> f64 {
+        self.0.r_parent_score
+    }
+
+    /// Returns the visibility of the parent node.
+    #[inline]
+    pub fn parent_within(&self) -> RTreeWithin {
+        RTreeWithin::from(self.0.e_parent_within)
+    }
+
+    /// Sets the visibility of the node or entry.
+    #[inline]
+    pub fn set_within(&mut self, within: RTreeWithin) {
+        self.0.e_within = within as os::raw::c_int;
+    }
+
+    /// Sets the score of the node or entry.
+    #[inline]
+    pub fn set_score(&mut self, score: f64) {
+        self.0.r_score = score;
+    }
+}
+
+unsafe extern "C" fn free_query_callback<F>(context: *mut os::raw::c_void)
+where
+    F: FnMut(&mut RTreeQueryInfo) + Send + 'static,
+{
+    drop(Box::from_raw(context as *mut F));
+}
+
+unsafe extern "C" fn query_trampoline<F>(info: *mut sqlite3_rtree_query_info) -> os::raw::c_int
+where
+    F: FnMut(&mut RTreeQueryInfo) + Send + 'static,
+{
+    let callback = &mut *((*info).context as *mut F);
+    let mut info = RTreeQueryInfo(&mut *info);
+
+    // unwinding into C is undefined behavior, fail the query instead
+    match panic::catch_unwind(AssertUnwindSafe(|| callback(&mut info))) {
+        Ok(()) => 0,
+        Err(_) => SQLITE_ERROR,
+    }
+}
+
+/// Specifies the R*Tree functions of the SQLite connection.
+pub trait RTree {
+    /// Registers `callback` as the SQL function `name`, that can be used
+    /// on the left side of `MATCH` on the id column of R*Tree tables.
+    /// Registering another callback with the same name replaces it.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// db.register_rtree_query("overlaps", |info| {
+    ///     let (low, high) = (info.params()[0], info.params()[1]);
+    ///     let (min, max) = (info.coords()[0], info.coords()[1]);
+    ///
+    ///     if max < low || min > high {
+    ///         info.set_within(RTreeWithin::NotWithin);
+    ///     } else if low <= min && max <= high {
+    ///         info.set_within(RTreeWithin::FullyWithin);
+    ///     } else {
+    ///         info.set_within(RTreeWithin::PartlyWithin);
+    ///     }
+    /// })
+    /// .unwrap();
+    ///
+    /// let status = db.execute(
+    ///     String::from("SELECT package FROM version_ranges WHERE id MATCH overlaps(3, 7);"),
+    ///     None::<Box<dyn FnOnce(SqlitePrimaryResult, String)>>,
+    /// ).unwrap();
+    ///
+    /// db.close();
+    /// ```
+    fn register_rtree_query<'a, F>(
+        &self,
+        name: &str,
+        callback: F,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>
+    where
+        F: FnMut(&mut RTreeQueryInfo) + Send + 'static;
+}
+
+impl RTree for Database {
+    fn register_rtree_query<'a, F>(
+        &self,
+        name: &str,
+        callback: F,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>
+    where
+        F: FnMut(&mut RTreeQueryInfo) + Send + 'static,
+    {
+        let name = CString::new(name)?;
+        let context = Box::into_raw(Box::new(callback));
+
+        // SQLite calls `free_query_callback` when the function is replaced
+        // or the connection is closed, and also when the registration fails.
+        unsafe {
+            Ok(SqlitePrimaryResult::from(sqlite3_rtree_query_callback(
+                self.rp,
+                name.as_ptr(),
+                Some(query_trampoline::<F>),
+                context as *mut os::raw::c_void,
+                Some(free_query_callback::<F>),
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open() -> Database {
+        let db = Database::open(":memory:").unwrap();
+        RTreeTable::new("ranges")
+            .dimension("min_version", "max_version")
+            .auxiliary_column("package")
+            .integer_coordinates()
+            .create(&db)
+            .unwrap();
+        db.execute_checked(
+            "INSERT INTO ranges VALUES (1, 1, 4, 'core'), (2, 5, 9, 'extra'), (3, 2, 6, 'tools');",
+        )
+        .unwrap();
+        db
+    }
+
+    fn packages(db: &Database, statement: &str) -> Vec<Value> {
+        db.query_values(statement)
+            .unwrap()
+            .into_iter()
+            .map(|mut row| row.remove(0))
+            .collect()
+    }
+
+    #[test]
+    fn query_callback_filters_the_entries() {
+        let db = open();
+        assert_eq!(
+            db.register_rtree_query("overlaps", |info| {
+                let (low, high) = (info.params()[0], info.params()[1]);
+                let (min, max) = (info.coords()[0], info.coords()[1]);
+
+                if max < low || min > high {
+                    info.set_within(RTreeWithin::NotWithin);
+                } else if low <= min && max <= high {
+                    info.set_within(RTreeWithin::FullyWithin);
+                } else {
+                    info.set_within(RTreeWithin::PartlyWithin);
+                }
+            })
+            .unwrap(),
+            SqlitePrimaryResult::Ok
+        );
+
+        assert_eq!(
+            packages(
+                &db,
+                "SELECT package FROM ranges WHERE id MATCH overlaps(5, 5) ORDER BY id;"
+            ),
+            [
+                Value::Text(String::from("extra")),
+                Value::Text(String::from("tools"))
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_tables_and_panicking_callbacks_fail() {
+        let db = Database::open(":memory:").unwrap();
+
+        let error = RTreeTable::new("flat").create(&db).unwrap_err();
+        assert_eq!(error.kind, "sqlite3:RTree");
+
+        let db = open();
+        db.register_rtree_query("broken", |_| panic!("broken callback"))
+            .unwrap();
+        assert!(db
+            .query_values("SELECT package FROM ranges WHERE id MATCH broken(1);")
+            .is_err());
+    }
+}
@@ -0,0 +1,494 @@
+//! This module contains data-types and functions to inspect the
+//! schema of the database.
+
+#![forbid(missing_docs)]
+
+use std::{
+    ffi::{CStr, CString},
+    os, ptr,
+};
+
+use crate::{bindings::sqlite3_table_column_metadata, ehandle::MinSqliteWrapperError, prelude::*};
+
+/// Type of the schema object returned by `Schema::tables`.
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum TableKind {
+    /// Ordinary table
+    Table,
+    /// View
+    View,
+    /// Virtual table
+    Virtual,
+    /// Table that stores the content of a virtual table
+    Shadow,
+}
+
+/// Column of a table or view.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    /// Index of the column in the table.
+    pub cid: i64,
+    /// Name of the column.
+    pub name: String,
+    /// Declared type of the column, empty if not specified.
+    pub declared_type: String,
+    /// Whether the column has NOT NULL constraint.
+    pub not_null: bool,
+    /// Default value of the column as SQL text.
+    pub default_value: Option<String>,
+    /// 1-based position of the column in the primary key, 0 if it's not part of it.
+    pub primary_key: i64,
+    /// Whether the column is a hidden column of a virtual table
+    /// or a generated column.
+    pub hidden: bool,
+}
+
+/// Index of a table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Index {
+    /// Name of the index.
+    pub name: String,
+    /// Whether the index is UNIQUE.
+    pub unique: bool,
+    /// How the index is created. "c" for CREATE INDEX, "u" for UNIQUE
+    /// constraint and "pk" for PRIMARY KEY constraint.
+    pub origin: String,
+    /// Whether the index has WHERE clause.
+    pub partial: bool,
+    /// Indexed columns in order. `None` stands for expressions.
+    pub columns: Vec<Option<String>>,
+}
+
+/// Foreign key of a table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKey {
+    /// Id of the foreign key within the table.
+    pub id: i64,
+    /// Referenced table.
+    pub table: String,
+    /// Columns of the table that refer to the `to` columns.
+    pub from: Vec<String>,
+    /// Referenced columns. `None` stands for the primary key of the referenced table.
+    pub to: Vec<Option<String>>,
+    /// ON UPDATE action, e.g. "CASCADE" or "NO ACTION".
+    pub on_update: String,
+    /// ON DELETE action, e.g. "CASCADE" or "NO ACTION".
+    pub on_delete: String,
+}
+
+/// Trigger of a table or view.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trigger {
+    /// Name of the trigger.
+    pub name: String,
+    /// CREATE TRIGGER statement of the trigger.
+    pub sql: Option<String>,
+}
+
+/// Table or view with all of its schema objects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    /// Name of the table.
+    pub name: String,
+    /// Type of the table.
+    pub kind: TableKind,
+    /// CREATE statement of the table.
+    pub sql: Option<String>,
+    /// Whether the table is declared as STRICT.
+    pub strict: bool,
+    /// Whether the table is declared as WITHOUT ROWID.
+    pub without_rowid: bool,
+    /// Columns of the table.
+    pub columns: Vec<Column>,
+    /// Indexes of the table.
+    pub indexes: Vec<Index>,
+    /// Foreign keys of the table.
+    pub foreign_keys: Vec<ForeignKey>,
+    /// Triggers of the table.
+    pub triggers: Vec<Trigger>,
+}
+
+/// Schema of a database of the connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schema {
+    /// Tables and views ordered by name, without the internal
+    /// tables of SQLite.
+    pub tables: Vec<Table>,
+}
+
+impl Schema {
+    /// Returns the table or view called `name`.
+    #[inline]
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.tables.iter().find(|table| table.name == name)
+    }
+}
+
+/// Column information returned by `table_column_metadata`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnMetadata {
+    /// Declared type of the column.
+    pub declared_type: Option<String>,
+    /// Name of the default collation sequence of the column.
+    pub collation: Option<String>,
+    /// Whether the column has NOT NULL constraint.
+    pub not_null: bool,
+    /// Whether the column is part of the primary key.
+    pub primary_key: bool,
+    /// Whether the column is AUTOINCREMENT.
+    pub auto_increment: bool,
+}
+
+#[inline]
+fn integer(value: &Value) -> i64 {
+    match value {
+        Value::Integer(t) => *t,
+        _ => 0,
+    }
+}
+
+#[inline]
+fn text(value: Value) -> Option<String> {
+    match value {
+        Value::Text(t) => Some(t),
+        _ => None,
+    }
+}
+
+#[inline]
+unsafe fn optional_str(pointer: *const os::raw::c_char) -> Option<String> {
+    if pointer.is_null() {
+        return None;
+    }
+
+    Some(CStr::from_ptr(pointer).to_string_lossy().into_owned())
+}
+
+/// Specifies the schema introspection functions of the SQLite connection.
+pub trait SchemaIntrospection {
+    /// Reads the tables, views, columns, indexes, foreign keys and
+    /// triggers of the `database`.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let schema = db.schema(DatabaseName::Main).unwrap();
+    /// for table in schema.tables {
+    ///     println!("{} ({} columns)", table.name, table.columns.len());
+    /// }
+    ///
+    /// db.close();
+    /// ```
+    fn schema<'a>(&self, database: DatabaseName) -> Result<Schema, MinSqliteWrapperError<'a>>;
+
+    /// Returns the information of `column` in `table` of the `database`.
+    /// If `database` is `None`, all of the databases are searched.
+    ///
+    /// # Warning
+    /// SQLite must be compiled with `SQLITE_ENABLE_COLUMN_METADATA`.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let metadata = db.table_column_metadata(None, "items", "id").unwrap();
+    /// assert!(metadata.primary_key);
+    ///
+    /// db.close();
+    /// ```
+    fn table_column_metadata<'a>(
+        &self,
+        database: Option<DatabaseName>,
+        table: &str,
+        column: &str,
+    ) -> Result<ColumnMetadata, MinSqliteWrapperError<'a>>;
+}
+
+impl Database {
+    fn table_columns<'a>(
+        &self,
+        database: DatabaseName,
+        table: &str,
+    ) -> Result<Vec<Column>, MinSqliteWrapperError<'a>> {
+        let rows = self.query_values(&format!(
+            "SELECT cid, name, type, \"notnull\", dflt_value, pk, hidden
+                FROM pragma_table_xinfo({}, {}) ORDER BY cid;",
+            Value::from(table).to_sql_literal(),
+            Value::from(database.as_str()).to_sql_literal()
+        ))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let mut row = row.into_iter();
+                Column {
+                    cid: integer(&row.next().unwrap_or(Value::Null)),
+                    name: text(row.next().unwrap_or(Value::Null)).unwrap_or_default(),
+                    declared_type: text(row.next().unwrap_or(Value::Null)).unwrap_or_default(),
+                    not_null: integer(&row.next().unwrap_or(Value::Null)) != 0,
+                    default_value: text(row.next().unwrap_or(Value::Null)),
+                    primary_key: integer(&row.next().unwrap_or(Value::Null)),
+                    hidden: integer(&row.next().unwrap_or(Value::Null)) != 0,
+                }
+            })
+            .collect())
+    }
+
+    fn table_indexes<'a>(
+        &self,
+        database: DatabaseName,
+        table: &str,
+    ) -> Result<Vec<Index>, MinSqliteWrapperError<'a>> {
+        let rows = self.query_values(&format!(
+            "SELECT name, \"unique\", origin, partial
+                FROM pragma_index_list({}, {}) ORDER BY name;",
+            Value::from(table).to_sql_literal(),
+            Value::from(database.as_str()).to_sql_literal()
+        ))?;
+
+        let mut indexes = Vec::with_capacity(rows.len());
+        for row in rows {
+            let mut row = row.into_iter();
+            let name = text(row.next().unwrap_or(Value::Null)).unwrap_or_default();
+
+            let columns = self
+                .query_values(&format!(
+                    "SELECT name FROM pragma_index_info({}, {}) ORDER BY seqno;",
+                    Value::from(name.as_str()).to_sql_literal(),
+                    Value::from(database.as_str()).to_sql_literal()
+                ))?
+                .into_iter()
+                .map(|column| text(column.into_iter().next().unwrap_or(Value::Null)))
+                .collect();
+
+            indexes.push(Index {
+                name,
+                unique: integer(&row.next().unwrap_or(Value::Null)) != 0,
+                origin: text(row.next().unwrap_or(Value::Null)).unwrap_or_default(),
+                partial: integer(&row.next().unwrap_or(Value::Null)) != 0,
+                columns,
+            });
+        }
+
+        Ok(indexes)
+    }
+
+    fn table_foreign_keys<'a>(
+        &self,
+        database: DatabaseName,
+        table: &str,
+    ) -> Result<Vec<ForeignKey>, MinSqliteWrapperError<'a>> {
+        let rows = self.query_values(&format!(
+            "SELECT id, \"table\", \"from\", \"to\", on_update, on_delete
+                FROM pragma_foreign_key_list({}, {}) ORDER BY id, seq;",
+            Value::from(table).to_sql_literal(),
+            Value::from(database.as_str()).to_sql_literal()
+        ))?;
+
+        let mut foreign_keys: Vec<ForeignKey> = Vec::new();
+        for row in rows {
+            let mut row = row.into_iter();
+            let id = integer(&row.next().unwrap_or(Value::Null));
+            let referenced = text(row.next().unwrap_or(Value::Null)).unwrap_or_default();
+            let from = text(row.next().unwrap_or(Value::Null)).unwrap_or_default();
+            let to = text(row.next().unwrap_or(Value::Null));
+
+            // multi-column foreign keys have one row per column
+            match foreign_keys.last_mut() {
+                Some(foreign_key) if foreign_key.id == id => {
+                    foreign_key.from.push(from);
+                    foreign_key.to.push(to);
+                }
+                _ => foreign_keys.push(ForeignKey {
+                    id,
+                    table: referenced,
+                    from: vec![from],
+                    to: vec![to],
+                    on_update: text(row.next().unwrap_or(Value::Null)).unwrap_or_default(),
+                    on_delete: text(row.next().unwrap_or(Value::Null)).unwrap_or_default(),
+                }),
+            }
+        }
+
+        Ok(foreign_keys)
+    }
+
+    fn table_triggers<'a>(
+        &self,
+        database: DatabaseName,
+        table: &str,
+    ) -> Result<Vec<Trigger>, MinSqliteWrapperError<'a>> {
+        let rows = self.query_values(&format!(
+            "SELECT name, sql FROM {}.sqlite_schema
+                WHERE type = 'trigger' AND tbl_name = {} ORDER BY name;",
+            database.to_sql_identifier(),
+            Value::from(table).to_sql_literal()
+        ))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let mut row = row.into_iter();
+                Trigger {
+                    name: text(row.next().unwrap_or(Value::Null)).unwrap_or_default(),
+                    sql: text(row.next().unwrap_or(Value::Null)),
+                }
+            })
+            .collect())
+    }
+}
+
+impl SchemaIntrospection for Database {
+    fn schema<'a>(&self, database: DatabaseName) -> Result<Schema, MinSqliteWrapperError<'a>> {
+        let rows = self.query_values(&format!(
+            "SELECT s.name, l.type, s.sql, l.wr, l.strict
+                FROM {}.sqlite_schema AS s
+                JOIN pragma_table_list AS l ON l.schema = {} AND l.name = s.name
+                WHERE s.type IN ('table', 'view') AND s.name NOT LIKE 'sqlite\\_%' ESCAPE '\\'
+                ORDER BY s.name;",
+            database.to_sql_identifier(),
+            Value::from(database.as_str()).to_sql_literal()
+        ))?;
+
+        let mut tables = Vec::with_capacity(rows.len());
+        for row in rows {
+            let mut row = row.into_iter();
+            let name = text(row.next().unwrap_or(Value::Null)).unwrap_or_default();
+            let kind = match text(row.next().unwrap_or(Value::Null)).as_deref() {
+                Some("view") => TableKind::View,
+                Some("virtual") => TableKind::Virtual,
+                Some("shadow") => TableKind::Shadow,
+                _ => TableKind::Table,
+            };
+
+            tables.push(Table {
+                kind,
+                sql: text(row.next().unwrap_or(Value::Null)),
+                without_rowid: integer(&row.next().unwrap_or(Value::Null)) != 0,
+                strict: integer(&row.next().unwrap_or(Value::Null)) != 0,
+                columns: self.table_columns(database, &name)?,
+                indexes: self.table_indexes(database, &name)?,
+                foreign_keys: self.table_foreign_keys(database, &name)?,
+                triggers: self.table_triggers(database, &name)?,
+                name,
+            });
+        }
+
+        Ok(Schema { tables })
+    }
+
+    fn table_column_metadata<'a>(
+        &self,
+        database: Option<DatabaseName>,
+        table: &str,
+        column: &str,
+    ) -> Result<ColumnMetadata, MinSqliteWrapperError<'a>> {
+        let database = database.map(DatabaseName::to_cstring).transpose()?;
+        let table = CString::new(table)?;
+        let column = CString::new(column)?;
+
+        let mut declared_type = ptr::null();
+        let mut collation = ptr::null();
+        let mut not_null = 0;
+        let mut primary_key = 0;
+        let mut auto_increment = 0;
+
+        unsafe {
+            let status = SqlitePrimaryResult::from(sqlite3_table_column_metadata(
+                self.rp,
+                database.as_ref().map_or(ptr::null(), |name| name.as_ptr()),
+                table.as_ptr(),
+                column.as_ptr(),
+                &mut declared_type,
+                &mut collation,
+                &mut not_null,
+                &mut primary_key,
+                &mut auto_increment,
+            ));
+
+            if status != SqlitePrimaryResult::Ok {
+                return Err(MinSqliteWrapperError::from_connection(self.rp));
+            }
+
+            Ok(ColumnMetadata {
+                declared_type: optional_str(declared_type),
+                collation: optional_str(collation),
+                not_null: not_null != 0,
+                primary_key: primary_key != 0,
+                auto_increment: auto_increment != 0,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{prelude::*, test_support::open_memory};
+
+    fn open() -> Database {
+        open_memory(
+            "CREATE TABLE owners(id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL);
+            CREATE TABLE items(
+                id INTEGER PRIMARY KEY,
+                owner INTEGER REFERENCES owners(id) ON DELETE CASCADE,
+                name TEXT COLLATE NOCASE DEFAULT 'unnamed'
+            );
+            CREATE UNIQUE INDEX items_name ON items(name);
+            CREATE VIEW item_names AS SELECT name FROM items;
+            CREATE TRIGGER items_touch AFTER UPDATE ON items BEGIN SELECT 1; END;",
+        )
+    }
+
+    #[test]
+    fn schema_describes_tables_and_views() {
+        let db = open();
+        let schema = db.schema(DatabaseName::Main).unwrap();
+
+        let names: Vec<&str> = schema.tables.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["item_names", "items", "owners"]);
+        assert_eq!(schema.table("item_names").unwrap().kind, TableKind::View);
+
+        let items = schema.table("items").unwrap();
+        assert_eq!(items.kind, TableKind::Table);
+        let columns: Vec<&str> = items.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(columns, ["id", "owner", "name"]);
+        assert_eq!(items.columns[2].default_value.as_deref(), Some("'unnamed'"));
+
+        assert_eq!(items.indexes.len(), 1);
+        assert!(items.indexes[0].unique);
+        assert_eq!(items.indexes[0].columns, [Some(String::from("name"))]);
+
+        assert_eq!(items.foreign_keys.len(), 1);
+        assert_eq!(items.foreign_keys[0].table, "owners");
+        assert_eq!(items.foreign_keys[0].on_delete, "CASCADE");
+
+        assert_eq!(items.triggers.len(), 1);
+        assert_eq!(items.triggers[0].name, "items_touch");
+    }
+
+    #[test]
+    fn column_metadata_is_read() {
+        let db = open();
+
+        let id = db.table_column_metadata(None, "owners", "id").unwrap();
+        assert!(id.primary_key);
+        assert!(id.auto_increment);
+
+        let name = db
+            .table_column_metadata(Some(DatabaseName::Main), "items", "name")
+            .unwrap();
+        assert_eq!(name.collation.as_deref(), Some("NOCASE"));
+        assert!(!name.not_null);
+
+        assert!(db.table_column_metadata(None, "items", "missing").is_err());
+        assert!(db.table_column_metadata(None, "missing", "id").is_err());
+    }
+
+    #[test]
+    fn schema_of_missing_database_fails() {
+        let db = open();
+        assert!(db.schema(DatabaseName::Attached("missing")).is_err());
+    }
+}
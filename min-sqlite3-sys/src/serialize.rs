@@ -0,0 +1,215 @@
+//! This module contains functions to serialize the databases into
+//! bytes and to load them back from bytes, without touching the disk.
+
+#![forbid(missing_docs)]
+
+use std::{os, ptr, slice};
+
+use crate::{
+    bindings::{sqlite3_deserialize, sqlite3_free, sqlite3_malloc64, sqlite3_serialize},
+    ehandle::MinSqliteWrapperError,
+    prelude::*,
+};
+
+/// Binder of SQLITE_DESERIALIZE_FREEONCLOSE from C source
+const DESERIALIZE_FREEONCLOSE: os::raw::c_uint = 1;
+
+/// Binder of SQLITE_DESERIALIZE_RESIZEABLE from C source
+const DESERIALIZE_RESIZEABLE: os::raw::c_uint = 2;
+
+/// Binder of SQLITE_DESERIALIZE_READONLY from C source
+const DESERIALIZE_READONLY: os::raw::c_uint = 4;
+
+/// Specifies the serialization functions of the SQLite connection.
+pub trait Serialization {
+    /// Returns the content of the `schema` database as bytes. The bytes are
+    /// the same as the database file would have on disk.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let bytes = db.serialize(DatabaseName::Main).unwrap();
+    ///
+    /// db.close();
+    /// ```
+    fn serialize<'a>(&self, schema: DatabaseName) -> Result<Vec<u8>, MinSqliteWrapperError<'a>>;
+
+    /// Replaces the content of the `schema` database with the given bytes.
+    /// The database is then served from memory, and changes on it are never
+    /// written to disk.
+    ///
+    /// If `read_only` is `false`, the database can be modified and grow.
+    ///
+    /// # Usage
+    /// let db = Database::open(":memory:").unwrap();
+    ///
+    /// let status = db.deserialize(DatabaseName::Main, &seed_index_bytes, true).unwrap();
+    ///
+    /// if status != SqlitePrimaryResult::Ok {
+    ///     ...
+    /// }
+    ///
+    /// db.close();
+    /// ```
+    fn deserialize<'a>(
+        &self,
+        schema: DatabaseName,
+        bytes: &[u8],
+        read_only: bool,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>;
+}
+
+impl Serialization for Database {
+    fn serialize<'a>(&self, schema: DatabaseName) -> Result<Vec<u8>, MinSqliteWrapperError<'a>> {
+        let name = schema;
+        let schema = schema.to_cstring()?;
+        let mut size = 0;
+
+        unsafe {
+            let data = sqlite3_serialize(self.rp, schema.as_ptr(), &mut size, 0);
+            if data.is_null() {
+                // SQLite reports the size as -1 if the schema doesn't exist or
+                // its page count can't be queried, as 0 if the database is empty.
+                return match size {
+                    0 => Ok(vec![]),
+                    size if size < 0 => match self.schema_exists(name)? {
+                        true => Err(MinSqliteWrapperError::from_connection(self.rp)),
+                        // the temporary database is opened when it's first used
+                        false if name == DatabaseName::Temp => Ok(vec![]),
+                        false => Err(MinSqliteWrapperError {
+                            kind: "sqlite3:SqlitePrimaryResult",
+                            reason: format!("Error: unknown database {}", schema.to_string_lossy()),
+                        }),
+                    },
+                    _ => Err(SqlitePrimaryResult::NoMem.into()),
+                };
+            }
+
+            let bytes = slice::from_raw_parts(data, size as usize).to_vec();
+            sqlite3_free(data as *mut os::raw::c_void);
+            Ok(bytes)
+        }
+    }
+
+    fn deserialize<'a>(
+        &self,
+        schema: DatabaseName,
+        bytes: &[u8],
+        read_only: bool,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>> {
+        let schema = schema.to_cstring()?;
+
+        unsafe {
+            // SQLite takes the ownership of the buffer and frees it with `sqlite3_free`,
+            // so it must be allocated by SQLite.
+            let data = sqlite3_malloc64(bytes.len().max(1) as os::raw::c_ulonglong)
+                as *mut os::raw::c_uchar;
+            if data.is_null() {
+                return Ok(SqlitePrimaryResult::NoMem);
+            }
+            ptr::copy_nonoverlapping(bytes.as_ptr(), data, bytes.len());
+
+            let flags = DESERIALIZE_FREEONCLOSE
+                | if read_only {
+                    DESERIALIZE_READONLY
+                } else {
+                    DESERIALIZE_RESIZEABLE
+                };
+
+            // The buffer is freed by SQLite even if the call fails.
+            Ok(SqlitePrimaryResult::from(sqlite3_deserialize(
+                self.rp,
+                schema.as_ptr(),
+                data,
+                bytes.len() as os::raw::c_longlong,
+                bytes.len() as os::raw::c_longlong,
+                flags,
+            )))
+        }
+    }
+}
+
+impl Database {
+    /// Returns `true` if `schema` is an opened database of the connection.
+    fn schema_exists<'a>(&self, schema: DatabaseName) -> Result<bool, MinSqliteWrapperError<'a>> {
+        Ok(self
+            .database_list()?
+            .iter()
+            .any(|entry| entry.name.eq_ignore_ascii_case(schema.as_str())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{prelude::*, test_support::open_memory};
+
+    fn count(db: &Database) -> i64 {
+        let mut sql = db.prepare_checked("SELECT count(*) FROM t;").unwrap();
+        sql.execute_prepared();
+        sql.get_owned_data::<i64>(0).unwrap()
+    }
+
+    fn serialized() -> Vec<u8> {
+        let db = open_memory("CREATE TABLE t(x); INSERT INTO t VALUES (1), (2);");
+        db.serialize(DatabaseName::Main).unwrap()
+    }
+
+    #[test]
+    fn serialized_database_is_deserialized() {
+        let bytes = serialized();
+        assert!(bytes.starts_with(b"SQLite format 3\0"));
+
+        let db = Database::open(":memory:").unwrap();
+        assert_eq!(
+            db.deserialize(DatabaseName::Main, &bytes, false).unwrap(),
+            SqlitePrimaryResult::Ok
+        );
+        assert_eq!(count(&db), 2);
+
+        // resizeable databases can grow
+        db.execute_changes(String::from("INSERT INTO t VALUES (3);"))
+            .unwrap();
+        assert_eq!(count(&db), 3);
+    }
+
+    #[test]
+    fn read_only_database_rejects_writes() {
+        let db = Database::open(":memory:").unwrap();
+        db.deserialize(DatabaseName::Main, &serialized(), true)
+            .unwrap();
+
+        assert_eq!(count(&db), 2);
+        let error = db
+            .execute_changes(String::from("INSERT INTO t VALUES (3);"))
+            .unwrap_err();
+        assert!(error.reason.starts_with("Readonly"), "{}", error.reason);
+    }
+
+    #[test]
+    fn serialize_reports_unknown_and_empty_databases() {
+        let db = Database::open(":memory:").unwrap();
+
+        let error = db.serialize(DatabaseName::Attached("missing")).unwrap_err();
+        assert_eq!(error.reason, "Error: unknown database missing");
+
+        // the temporary database isn't opened until it's used
+        assert_eq!(db.serialize(DatabaseName::Temp).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn serialize_reports_the_error_of_existing_databases() {
+        let mut db = open_memory("CREATE TABLE t(x);");
+        db.set_authorizer(Some(|action: AuthAction| match action {
+            AuthAction::Pragma { .. } => Authorization::Deny,
+            _ => Authorization::Allow,
+        }));
+
+        let error = db.serialize(DatabaseName::Main).unwrap_err();
+        assert!(
+            !error.reason.contains("unknown database"),
+            "{}",
+            error.reason
+        );
+    }
+}
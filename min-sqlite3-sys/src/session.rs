@@ -0,0 +1,761 @@
+//! This module contains data-types and functions to record the changes
+//! of the database as changesets, and to apply them to other databases.
+
+#![forbid(missing_docs)]
+
+use std::{
+    ffi::{CStr, CString},
+    marker::PhantomData,
+    os,
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use crate::{
+    bindings::{
+        sqlite3_changeset_iter, sqlite3_free, sqlite3_session, sqlite3_value,
+        sqlite3changeset_apply, sqlite3changeset_concat, sqlite3changeset_conflict,
+        sqlite3changeset_finalize, sqlite3changeset_invert, sqlite3changeset_new,
+        sqlite3changeset_next, sqlite3changeset_old, sqlite3changeset_op, sqlite3changeset_pk,
+        sqlite3changeset_start, sqlite3session_attach, sqlite3session_changeset,
+        sqlite3session_create, sqlite3session_delete, sqlite3session_enable,
+        sqlite3session_indirect, sqlite3session_isempty, sqlite3session_patchset, SQLITE_DELETE,
+        SQLITE_INSERT, SQLITE_UPDATE,
+    },
+    ehandle::MinSqliteWrapperError,
+    prelude::*,
+    value::ValueRef,
+};
+
+/// Binder of SQLITE_ROW from C source
+const SQLITE_ROW: os::raw::c_int = 100;
+
+/// Binder of SQLITE_DONE from C source
+const SQLITE_DONE: os::raw::c_int = 101;
+
+/// Binded instance of the sqlite3_session. Records the changes that are
+/// made to the attached tables of a database.
+pub struct Session<'a> {
+    sp: *mut sqlite3_session,
+    _marker: PhantomData<&'a Database>,
+}
+
+impl<'a> Drop for Session<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            sqlite3session_delete(self.sp);
+        }
+    }
+}
+
+/// Copies the buffer that is allocated by SQLite into `Changeset`, then
+/// frees it.
+unsafe fn take_buffer<'e>(
+    status: os::raw::c_int,
+    size: os::raw::c_int,
+    buffer: *mut os::raw::c_void,
+) -> Result<Changeset, MinSqliteWrapperError<'e>> {
+    let data = if buffer.is_null() || size <= 0 {
+        Vec::new()
+    } else {
+        slice::from_raw_parts(buffer as *const u8, size as usize).to_vec()
+    };
+    sqlite3_free(buffer);
+
+    match SqlitePrimaryResult::from(status) {
+        SqlitePrimaryResult::Ok => Ok(Changeset { data }),
+        status => Err(status.into()),
+    }
+}
+
+impl<'a> Session<'a> {
+    /// Creates a session that records the changes made to the `database`
+    /// of `db`. No table is recorded until it's attached with `attach`.
+    ///
+    /// # Usage
+    /// let db = Database::open(Path::new("./example.db")).unwrap();
+    ///
+    /// let mut session = Session::new(&db, DatabaseName::Main).unwrap();
+    /// session.attach(None).unwrap();
+    ///
+    /// let statement = String::from("UPDATE packages SET version = 2 WHERE name = 'lpm';");
+    /// db.execute(statement, None::<Box<dyn FnOnce(SqlitePrimaryResult, String)>>).unwrap();
+    ///
+    /// let changeset = session.changeset().unwrap();
+    /// ```
+    pub fn new<'e>(
+        db: &'a Database,
+        database: DatabaseName,
+    ) -> Result<Self, MinSqliteWrapperError<'e>> {
+        let database = database.to_cstring()?;
+        let mut sp = ptr::null_mut();
+
+        unsafe {
+            let status =
+                SqlitePrimaryResult::from(sqlite3session_create(db.rp, database.as_ptr(), &mut sp));
+
+            if status != SqlitePrimaryResult::Ok {
+                return Err(status.into());
+            }
+        }
+
+        Ok(Session {
+            sp,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Starts recording the changes of `table`, or of all the tables if
+    /// `None`. Only the tables with a PRIMARY KEY are recorded.
+    pub fn attach<'e>(
+        &mut self,
+        table: Option<&str>,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'e>> {
+        let table = table.map(CString::new).transpose()?;
+
+        unsafe {
+            Ok(SqlitePrimaryResult::from(sqlite3session_attach(
+                self.sp,
+                table.as_ref().map_or(ptr::null(), |table| table.as_ptr()),
+            )))
+        }
+    }
+
+    /// Enables or disables recording the changes. Sessions are enabled on
+    /// creation. Returns the new state.
+    #[inline]
+    pub fn set_enabled(&mut self, enabled: bool) -> bool {
+        unsafe { sqlite3session_enable(self.sp, enabled as os::raw::c_int) == 1 }
+    }
+
+    /// Returns `true` if the session records the changes.
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        unsafe { sqlite3session_enable(self.sp, -1) == 1 }
+    }
+
+    /// Marks the changes that are recorded from now on as indirect, e.g.
+    /// changes made by triggers or foreign key actions. Returns the new state.
+    #[inline]
+    pub fn set_indirect(&mut self, indirect: bool) -> bool {
+        unsafe { sqlite3session_indirect(self.sp, indirect as os::raw::c_int) == 1 }
+    }
+
+    /// Returns `true` if no change has been recorded.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        unsafe { sqlite3session_isempty(self.sp) != 0 }
+    }
+
+    /// Returns the changeset of the recorded changes, which holds both
+    /// the old and the new values of the changed rows.
+    pub fn changeset<'e>(&self) -> Result<Changeset, MinSqliteWrapperError<'e>> {
+        let mut size = 0;
+        let mut buffer = ptr::null_mut();
+
+        unsafe {
+            let status = sqlite3session_changeset(self.sp, &mut size, &mut buffer);
+            take_buffer(status, size, buffer)
+        }
+    }
+
+    /// Returns the patchset of the recorded changes. Patchsets are smaller
+    /// than changesets since they only hold the primary keys of the deleted
+    /// rows and the new values of the updated columns, but they can't be
+    /// inverted and conflicts are detected with less accuracy.
+    pub fn patchset<'e>(&self) -> Result<Changeset, MinSqliteWrapperError<'e>> {
+        let mut size = 0;
+        let mut buffer = ptr::null_mut();
+
+        unsafe {
+            let status = sqlite3session_patchset(self.sp, &mut size, &mut buffer);
+            take_buffer(status, size, buffer)
+        }
+    }
+}
+
+/// Serialized changes that are recorded by `Session`. Can be stored or sent
+/// as bytes and applied to another database with
+/// `SessionOperations::apply_changeset`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Changeset {
+    data: Vec<u8>,
+}
+
+impl From<Vec<u8>> for Changeset {
+    #[inline]
+    fn from(data: Vec<u8>) -> Self {
+        Changeset { data }
+    }
+}
+
+impl Changeset {
+    /// Returns the serialized changeset.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the serialized changeset, consuming it.
+    #[inline]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Returns `true` if the changeset doesn't have any change.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the changeset that undoes the changes of this one. Inserts
+    /// become deletes, deletes become inserts, and the old and new values of
+    /// the updates are swapped.
+    ///
+    /// # Warning
+    /// Patchsets can't be inverted.
+    pub fn invert<'e>(&self) -> Result<Changeset, MinSqliteWrapperError<'e>> {
+        let mut size = 0;
+        let mut buffer = ptr::null_mut();
+
+        unsafe {
+            let status = sqlite3changeset_invert(
+                self.data.len() as os::raw::c_int,
+                self.data.as_ptr() as *const os::raw::c_void,
+                &mut size,
+                &mut buffer,
+            );
+            take_buffer(status, size, buffer)
+        }
+    }
+
+    /// Returns the changeset that has the changes of this one followed by
+    /// the changes of `other`.
+    pub fn concat<'e>(&self, other: &Changeset) -> Result<Changeset, MinSqliteWrapperError<'e>> {
+        let mut size = 0;
+        let mut buffer = ptr::null_mut();
+
+        unsafe {
+            let status = sqlite3changeset_concat(
+                self.data.len() as os::raw::c_int,
+                self.data.as_ptr() as *mut os::raw::c_void,
+                other.data.len() as os::raw::c_int,
+                other.data.as_ptr() as *mut os::raw::c_void,
+                &mut size,
+                &mut buffer,
+            );
+            take_buffer(status, size, buffer)
+        }
+    }
+
+    /// Returns an iterator over the changes of the changeset.
+    ///
+    /// # Usage
+    /// for change in changeset.iter().unwrap() {
+    ///     let change = change.unwrap();
+    ///     println!("{:?} on {}", change.operation, change.table);
+    /// }
+    /// ```
+    pub fn iter<'e>(&self) -> Result<ChangesetIter<'_>, MinSqliteWrapperError<'e>> {
+        let mut ip = ptr::null_mut();
+
+        unsafe {
+            // SQLite doesn't modify the changeset while iterating it.
+            let status = SqlitePrimaryResult::from(sqlite3changeset_start(
+                &mut ip,
+                self.data.len() as os::raw::c_int,
+                self.data.as_ptr() as *mut os::raw::c_void,
+            ));
+
+            if status != SqlitePrimaryResult::Ok {
+                return Err(status.into());
+            }
+        }
+
+        Ok(ChangesetIter {
+            ip,
+            done: false,
+            _marker: PhantomData,
+        })
+    }
+}
+
+/// Type of the row change in a changeset.
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ChangeOperation {
+    /// A row is inserted.
+    Insert,
+    /// A row is updated.
+    Update,
+    /// A row is deleted.
+    Delete,
+}
+
+/// Row change in a changeset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    /// Name of the changed table.
+    pub table: String,
+    /// Type of the change.
+    pub operation: ChangeOperation,
+    /// Whether the change is indirect, see `Session::set_indirect`.
+    pub indirect: bool,
+    /// Whether each column is a part of the PRIMARY KEY of the table.
+    pub primary_key: Vec<bool>,
+    /// Values of the row before the change, empty for inserts. For updates,
+    /// the columns that aren't changed are `None`.
+    pub old: Vec<Option<Value>>,
+    /// Values of the row after the change, empty for deletes. For updates,
+    /// the columns that aren't changed are `None`.
+    pub new: Vec<Option<Value>>,
+}
+
+/// Reads column values of the current change with `read`, e.g.
+/// `sqlite3changeset_old`.
+unsafe fn read_values(
+    ip: *mut sqlite3_changeset_iter,
+    columns: usize,
+    read: unsafe fn(
+        *mut sqlite3_changeset_iter,
+        os::raw::c_int,
+        *mut *mut sqlite3_value,
+    ) -> os::raw::c_int,
+) -> Result<Vec<Option<Value>>, MinSqliteWrapperError<'static>> {
+    let mut values = Vec::with_capacity(columns);
+    for i in 0..columns {
+        let mut value = ptr::null_mut();
+        let status = SqlitePrimaryResult::from(read(ip, i as os::raw::c_int, &mut value));
+        if status != SqlitePrimaryResult::Ok {
+            return Err(status.into());
+        }
+
+        if value.is_null() {
+            values.push(None);
+        } else {
+            values.push(Some(ValueRef::from_raw(value)?.to_value()));
+        }
+    }
+    Ok(values)
+}
+
+/// Reads the current change of the iterator.
+unsafe fn read_change(
+    ip: *mut sqlite3_changeset_iter,
+) -> Result<Change, MinSqliteWrapperError<'static>> {
+    let mut table = ptr::null();
+    let mut columns = 0;
+    let mut op = 0;
+    let mut indirect = 0;
+
+    let status = SqlitePrimaryResult::from(sqlite3changeset_op(
+        ip,
+        &mut table,
+        &mut columns,
+        &mut op,
+        &mut indirect,
+    ));
+    if status != SqlitePrimaryResult::Ok {
+        return Err(status.into());
+    }
+
+    let mut pk = ptr::null_mut();
+    let mut pk_columns = 0;
+    let status = SqlitePrimaryResult::from(sqlite3changeset_pk(ip, &mut pk, &mut pk_columns));
+    if status != SqlitePrimaryResult::Ok {
+        return Err(status.into());
+    }
+
+    let primary_key = if pk.is_null() || pk_columns <= 0 {
+        Vec::new()
+    } else {
+        slice::from_raw_parts(pk, pk_columns as usize)
+            .iter()
+            .map(|&flag| flag != 0)
+            .collect()
+    };
+
+    let columns = columns.max(0) as usize;
+    let operation = match op {
+        SQLITE_INSERT => ChangeOperation::Insert,
+        SQLITE_DELETE => ChangeOperation::Delete,
+        SQLITE_UPDATE => ChangeOperation::Update,
+        _ => return Err(SqlitePrimaryResult::Corrupt.into()),
+    };
+
+    let old = if operation == ChangeOperation::Insert {
+        Vec::new()
+    } else {
+        read_values(ip, columns, |ip, i, value| {
+            sqlite3changeset_old(ip, i, value)
+        })?
+    };
+    let new = if operation == ChangeOperation::Delete {
+        Vec::new()
+    } else {
+        read_values(ip, columns, |ip, i, value| {
+            sqlite3changeset_new(ip, i, value)
+        })?
+    };
+
+    Ok(Change {
+        table: CStr::from_ptr(table).to_str()?.to_owned(),
+        operation,
+        indirect: indirect != 0,
+        primary_key,
+        old,
+        new,
+    })
+}
+
+/// Iterator over the changes of a `Changeset`.
+pub struct ChangesetIter<'a> {
+    ip: *mut sqlite3_changeset_iter,
+    done: bool,
+    _marker: PhantomData<&'a Changeset>,
+}
+
+impl<'a> Drop for ChangesetIter<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            sqlite3changeset_finalize(self.ip);
+        }
+    }
+}
+
+impl<'a> Iterator for ChangesetIter<'a> {
+    type Item = Result<Change, MinSqliteWrapperError<'static>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match unsafe { sqlite3changeset_next(self.ip) } {
+            SQLITE_ROW => Some(unsafe { read_change(self.ip) }),
+            SQLITE_DONE => {
+                self.done = true;
+                None
+            }
+            other_id => {
+                self.done = true;
+                Some(Err(SqlitePrimaryResult::from(other_id).into()))
+            }
+        }
+    }
+}
+
+/// Reason of the conflict that occurs while applying a changeset.
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ConflictType {
+    /// Row that is updated or deleted exists, but its values don't match
+    /// the old values of the change.
+    Data,
+    /// Row that is updated or deleted doesn't exist.
+    NotFound,
+    /// Row that is inserted already exists.
+    Conflict,
+    /// Change violates a constraint, e.g. UNIQUE or NOT NULL.
+    Constraint,
+    /// Foreign key constraints are violated once all of the changes are
+    /// applied.
+    ForeignKey,
+}
+
+/// How a conflict is resolved.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ConflictAction {
+    /// Skips the change.
+    Omit,
+    /// Applies the change over the conflicting row. Only allowed for
+    /// `ConflictType::Data` and `ConflictType::Conflict`.
+    Replace,
+    /// Rolls back all of the changes and stops applying the changeset.
+    Abort,
+}
+
+/// Conflict that is passed to the conflict handler of
+/// `SessionOperations::apply_changeset`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    /// Reason of the conflict.
+    pub kind: ConflictType,
+    /// Change that conflicts, `None` for `ConflictType::ForeignKey`.
+    pub change: Option<Change>,
+    /// Values of the row in the database that conflicts with the change,
+    /// only for `ConflictType::Data` and `ConflictType::Conflict`.
+    pub conflicting_row: Option<Vec<Value>>,
+}
+
+/// Handlers that are passed to the trampolines of `sqlite3changeset_apply`.
+struct ApplyContext<F, C> {
+    filter: Option<F>,
+    conflict: C,
+}
+
+unsafe extern "C" fn filter_trampoline<F, C>(
+    ctx: *mut os::raw::c_void,
+    table: *const os::raw::c_char,
+) -> os::raw::c_int
+where
+    F: FnMut(&str) -> bool,
+{
+    let ctx = &mut *(ctx as *mut ApplyContext<F, C>);
+    let table = CStr::from_ptr(table).to_string_lossy();
+
+    match ctx.filter.as_mut() {
+        // unwinding into C is undefined behavior
+        Some(filter) => panic::catch_unwind(AssertUnwindSafe(|| filter(&table)))
+            .map_or(0, |apply| apply as os::raw::c_int),
+        None => 1,
+    }
+}
+
+unsafe extern "C" fn conflict_trampoline<F, C>(
+    ctx: *mut os::raw::c_void,
+    conflict: os::raw::c_int,
+    ip: *mut sqlite3_changeset_iter,
+) -> os::raw::c_int
+where
+    C: FnMut(&Conflict) -> ConflictAction,
+{
+    let ctx = &mut *(ctx as *mut ApplyContext<F, C>);
+
+    let kind = match conflict {
+        1 => ConflictType::Data,
+        2 => ConflictType::NotFound,
+        3 => ConflictType::Conflict,
+        4 => ConflictType::Constraint,
+        5 => ConflictType::ForeignKey,
+        _ => return 2,
+    };
+
+    // The iterator can only be read for the row conflicts.
+    let change = match kind {
+        ConflictType::ForeignKey => None,
+        _ => read_change(ip).ok(),
+    };
+
+    let conflicting_row = match (kind, &change) {
+        (ConflictType::Data | ConflictType::Conflict, Some(change)) => {
+            let columns = change.primary_key.len();
+            read_values(ip, columns, |ip, i, value| {
+                sqlite3changeset_conflict(ip, i, value)
+            })
+            .ok()
+            .map(|values| {
+                values
+                    .into_iter()
+                    .map(|value| value.unwrap_or(Value::Null))
+                    .collect()
+            })
+        }
+        _ => None,
+    };
+
+    let case = Conflict {
+        kind,
+        change,
+        conflicting_row,
+    };
+
+    // unwinding into C is undefined behavior
+    let action = panic::catch_unwind(AssertUnwindSafe(|| (ctx.conflict)(&case)))
+        .unwrap_or(ConflictAction::Abort);
+
+    match action {
+        ConflictAction::Omit => 0,
+        ConflictAction::Replace => 1,
+        ConflictAction::Abort => 2,
+    }
+}
+
+/// Specifies the changeset functions of the SQLite connection.
+pub trait SessionOperations {
+    /// Applies the changes of `changeset` to the database in a single
+    /// transaction. If `filter` is given, only the changes of the tables
+    /// that it returns `true` for are applied. `conflict` is invoked for
+    /// each conflicting change, and decides how it's resolved.
+    ///
+    /// Returns `SqlitePrimaryResult::Abort` if the conflict handler aborts,
+    /// in which case none of the changes are applied.
+    ///
+    /// # Usage
+    /// let db = Database::open(Path::new("./replica.db")).unwrap();
+    ///
+    /// let status = db.apply_changeset(
+    ///     &changeset,
+    ///     None::<fn(&str) -> bool>,
+    ///     |conflict: &Conflict| match conflict.kind {
+    ///         ConflictType::Data | ConflictType::Conflict => ConflictAction::Replace,
+    ///         _ => ConflictAction::Omit,
+    ///     },
+    /// ).unwrap();
+    ///
+    /// if status != SqlitePrimaryResult::Ok {
+    ///     ...
+    /// }
+    /// ```
+    fn apply_changeset<'a, F, C>(
+        &self,
+        changeset: &Changeset,
+        filter: Option<F>,
+        conflict: C,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>
+    where
+        F: FnMut(&str) -> bool,
+        C: FnMut(&Conflict) -> ConflictAction;
+}
+
+impl SessionOperations for Database {
+    fn apply_changeset<'a, F, C>(
+        &self,
+        changeset: &Changeset,
+        filter: Option<F>,
+        conflict: C,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>
+    where
+        F: FnMut(&str) -> bool,
+        C: FnMut(&Conflict) -> ConflictAction,
+    {
+        let mut ctx = ApplyContext { filter, conflict };
+
+        unsafe {
+            Ok(SqlitePrimaryResult::from(sqlite3changeset_apply(
+                self.rp,
+                changeset.data.len() as os::raw::c_int,
+                changeset.data.as_ptr() as *mut os::raw::c_void,
+                Some(filter_trampoline::<F, C>),
+                Some(conflict_trampoline::<F, C>),
+                &mut ctx as *mut ApplyContext<F, C> as *mut os::raw::c_void,
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::open_memory;
+
+    const SCHEMA: &str =
+        "CREATE TABLE t(id INTEGER PRIMARY KEY, v TEXT); INSERT INTO t VALUES (1, 'a');";
+
+    fn open() -> Database {
+        open_memory(SCHEMA)
+    }
+
+    fn rows(db: &Database) -> Vec<Vec<Value>> {
+        db.query_values("SELECT id, v FROM t ORDER BY id;").unwrap()
+    }
+
+    fn record(db: &Database) -> Changeset {
+        let mut session = Session::new(db, DatabaseName::Main).unwrap();
+        assert_eq!(session.attach(None).unwrap(), SqlitePrimaryResult::Ok);
+        assert!(session.is_empty());
+
+        db.execute_checked("UPDATE t SET v = 'b' WHERE id = 1; INSERT INTO t VALUES (2, 'c');")
+            .unwrap();
+        assert!(!session.is_empty());
+        session.changeset().unwrap()
+    }
+
+    #[test]
+    fn changeset_is_recorded_and_applied() {
+        let source = open();
+        let changeset = record(&source);
+
+        let operations: Vec<ChangeOperation> = changeset
+            .iter()
+            .unwrap()
+            .map(|change| change.unwrap().operation)
+            .collect();
+        assert_eq!(
+            operations,
+            [ChangeOperation::Update, ChangeOperation::Insert]
+        );
+
+        let replica = open();
+        assert_eq!(
+            replica
+                .apply_changeset(&changeset, None::<fn(&str) -> bool>, |_: &Conflict| {
+                    ConflictAction::Abort
+                })
+                .unwrap(),
+            SqlitePrimaryResult::Ok
+        );
+        assert_eq!(rows(&replica), rows(&source));
+
+        replica
+            .apply_changeset(
+                &changeset.invert().unwrap(),
+                None::<fn(&str) -> bool>,
+                |_: &Conflict| ConflictAction::Abort,
+            )
+            .unwrap();
+        assert_eq!(
+            rows(&replica),
+            [[Value::Integer(1), Value::Text(String::from("a"))]]
+        );
+    }
+
+    #[test]
+    fn conflicts_are_resolved_by_the_handler() {
+        let changeset = record(&open());
+        let replica = open();
+        replica
+            .execute_checked("INSERT INTO t VALUES (2, 'x');")
+            .unwrap();
+
+        let mut conflicts = vec![];
+        assert_eq!(
+            replica
+                .apply_changeset(
+                    &changeset,
+                    None::<fn(&str) -> bool>,
+                    |conflict: &Conflict| {
+                        conflicts.push(conflict.kind);
+                        ConflictAction::Abort
+                    }
+                )
+                .unwrap(),
+            SqlitePrimaryResult::Abort
+        );
+        assert_eq!(conflicts, [ConflictType::Conflict]);
+        assert_eq!(rows(&replica)[0][1], Value::Text(String::from("a")));
+
+        replica
+            .apply_changeset(&changeset, None::<fn(&str) -> bool>, |_: &Conflict| {
+                ConflictAction::Replace
+            })
+            .unwrap();
+        assert_eq!(
+            rows(&replica),
+            [
+                [Value::Integer(1), Value::Text(String::from("b"))],
+                [Value::Integer(2), Value::Text(String::from("c"))]
+            ]
+        );
+    }
+
+    #[test]
+    fn corrupt_changesets_are_rejected() {
+        let bytes = record(&open()).into_bytes();
+        let changeset = Changeset::from(bytes[..bytes.len() - 3].to_vec());
+        let db = open();
+
+        assert!(changeset.iter().unwrap().any(|change| change.is_err()));
+        assert_eq!(
+            db.apply_changeset(&changeset, None::<fn(&str) -> bool>, |_: &Conflict| {
+                ConflictAction::Omit
+            })
+            .unwrap(),
+            SqlitePrimaryResult::Corrupt
+        );
+        assert_eq!(
+            rows(&db),
+            [[Value::Integer(1), Value::Text(String::from("a"))]]
+        );
+    }
+}
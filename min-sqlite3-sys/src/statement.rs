@@ -3,8 +3,14 @@
 
 #![forbid(missing_docs)]
 
+use std::{ffi::CStr, os};
+
 use crate::{
-    bindings::{sqlite3_finalize, sqlite3_step, sqlite3_stmt},
+    bindings::{
+        sqlite3_changes64, sqlite3_column_count, sqlite3_column_name, sqlite3_db_handle,
+        sqlite3_expanded_sql, sqlite3_finalize, sqlite3_free, sqlite3_reset, sqlite3_sql,
+        sqlite3_step, sqlite3_stmt, sqlite3_total_changes64,
+    },
     ehandle::MinSqliteWrapperError,
     operations::ColumnCapabilities,
     prelude::*,
@@ -22,6 +28,9 @@ pub enum PreparedStatementStatus {
     FoundRow,
     /// Indicates that an operation has completed.
     Done,
+    /// Indicates that the operation is interrupted by `InterruptHandle`
+    /// or the progress handler.
+    Interrupted,
 }
 
 /// Binded instance of the sqlite3_stmt.
@@ -30,6 +39,24 @@ pub struct SqlStatement(*mut sqlite3_stmt);
 unsafe impl Send for SqlStatement {}
 unsafe impl Sync for SqlStatement {}
 
+/// Row of the result set that is passed to the row mapping functions.
+/// Only valid inside of the mapping function.
+pub struct Row<'a>(pub(crate) &'a SqlStatement);
+
+impl<'a> Row<'a> {
+    /// Reads the column data at index `i`, see `SqlStatement::get_data`.
+    #[inline]
+    pub fn get<T: ColumnCapabilities<'a>>(&self, i: usize) -> Result<T, MinSqliteWrapperError<'a>> {
+        self.0.get_data(i)
+    }
+
+    /// Returns the number of columns in the row.
+    #[inline]
+    pub fn column_count(&self) -> usize {
+        self.0.column_count()
+    }
+}
+
 impl Drop for SqlStatement {
     fn drop(&mut self) {
         self.kill();
@@ -74,10 +101,99 @@ impl<'a> SqlStatement {
         match unsafe { sqlite3_step(self.0) } {
             100 => PreparedStatementStatus::FoundRow,
             101 => PreparedStatementStatus::Done,
+            9 => PreparedStatementStatus::Interrupted,
             other_id => PreparedStatementStatus::Other(other_id),
         }
     }
 
+    /// Runs the prepared statement to completion and returns the number of
+    /// rows that are inserted, updated or deleted by it. Rows returned by
+    /// the statement are skipped.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let statement = String::from(
+    ///     "UPDATE example_table SET tag = ? WHERE name = 'Onur';"
+    /// );
+    ///
+    /// let mut sql = db.prepare(statement, None::<Box<dyn FnOnce(SqlitePrimaryResult, String)>>).unwrap();
+    /// sql.bind_val(1, "stable");
+    ///
+    /// let changes = sql.execute_changes().unwrap();
+    /// println!("{} rows are updated", changes);
+    ///
+    /// sql.kill();
+    /// db.close();
+    /// ```
+    pub fn execute_changes(&mut self) -> Result<u64, MinSqliteWrapperError<'a>> {
+        let total_changes = unsafe { sqlite3_total_changes64(sqlite3_db_handle(self.0)) };
+        loop {
+            match self.execute_prepared() {
+                PreparedStatementStatus::FoundRow => continue,
+                PreparedStatementStatus::Done => return Ok(self.changes_since(total_changes)),
+                _ => {
+                    return Err(MinSqliteWrapperError::from_connection(unsafe {
+                        sqlite3_db_handle(self.0)
+                    }))
+                }
+            }
+        }
+    }
+
+    /// Returns the number of rows changed by the statement, given the
+    /// `sqlite3_total_changes64` of the connection before it ran.
+    /// `sqlite3_changes64` keeps the count of the last INSERT, UPDATE or
+    /// DELETE, so it's only read if the total has moved since.
+    fn changes_since(&self, total_changes: i64) -> u64 {
+        unsafe {
+            let db = sqlite3_db_handle(self.0);
+            if sqlite3_total_changes64(db) == total_changes {
+                return 0;
+            }
+            sqlite3_changes64(db) as u64
+        }
+    }
+
+    /// Binds `params` to the parameters of the statement in order, then
+    /// runs the statement to completion, mapping each returned row with
+    /// `map_row`. Returns the mapped rows and the number of changed rows.
+    pub(crate) fn collect_rows<R, F>(
+        &mut self,
+        params: &[Value],
+        mut map_row: F,
+    ) -> Result<Returning<R>, MinSqliteWrapperError<'a>>
+    where
+        F: FnMut(&Row) -> R,
+    {
+        for (i, param) in params.iter().enumerate() {
+            let status = self.bind_val(i + 1, param.clone());
+            if status != SqlitePrimaryResult::Ok {
+                return Err(status.into());
+            }
+        }
+
+        let total_changes = unsafe { sqlite3_total_changes64(sqlite3_db_handle(self.0)) };
+        let mut rows = Vec::new();
+        loop {
+            match self.execute_prepared() {
+                PreparedStatementStatus::FoundRow => rows.push(map_row(&Row(self))),
+                PreparedStatementStatus::Done => {
+                    return Ok(Returning {
+                        rows,
+                        changes: self.changes_since(total_changes),
+                    });
+                }
+                _ => {
+                    return Err(MinSqliteWrapperError::from_connection(unsafe {
+                        sqlite3_db_handle(self.0)
+                    }))
+                }
+            }
+        }
+    }
+
     /// Reads the column data of the rows that returns from the SQL query.
     ///
     /// # Panics
@@ -129,10 +245,50 @@ impl<'a> SqlStatement {
     pub fn get_data<T: ColumnCapabilities<'a>>(
         &'a self,
         i: usize,
-    ) -> Result<T, MinSqliteWrapperError> {
+    ) -> Result<T, MinSqliteWrapperError<'a>> {
         ColumnCapabilities::get_data(self.0, i)
     }
 
+    /// Returns the number of columns in the result set of the prepared statement.
+    /// Returns zero for the statements that don't return data (e.g. UPDATE).
+    #[inline]
+    pub fn column_count(&self) -> usize {
+        unsafe { sqlite3_column_count(self.0) as usize }
+    }
+
+    /// Reads the column data into an owned type, so that the error doesn't
+    /// borrow the statement.
+    ///
+    /// # Warning
+    /// Must not be used with the borrowed types like `&str`.
+    #[inline]
+    pub(crate) fn get_owned_data<T: ColumnCapabilities<'static> + 'static>(
+        &self,
+        i: usize,
+    ) -> Result<T, MinSqliteWrapperError<'static>> {
+        ColumnCapabilities::get_data(self.0, i)
+    }
+
+    /// Returns the name of the column at index `i` in the result set.
+    #[inline]
+    pub(crate) fn column_name(&self, i: usize) -> Option<String> {
+        unsafe {
+            let name = sqlite3_column_name(self.0, i as os::raw::c_int);
+            if name.is_null() {
+                return None;
+            }
+
+            Some(CStr::from_ptr(name).to_string_lossy().into_owned())
+        }
+    }
+
+    /// Resets the statement so that it can be executed again. Bindings
+    /// are kept.
+    #[inline]
+    pub(crate) fn reset(&mut self) -> SqlitePrimaryResult {
+        unsafe { SqlitePrimaryResult::from(sqlite3_reset(self.0)) }
+    }
+
     /// Binds the value of a parameter to a prepared statement indicator.
     ///
     /// Supported indicator patterns:
@@ -181,6 +337,51 @@ impl<'a> SqlStatement {
         ColumnCapabilities::bind_val(val, self.0, i)
     }
 
+    /// Returns the SQL text that is used to create the prepared statement.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let statement = String::from(
+    ///     "SELECT * FROM example_table WHERE ID = ?;"
+    /// );
+    ///
+    /// let sql = db.prepare(statement, None::<Box<dyn FnOnce(SqlitePrimaryResult, String)>>).unwrap();
+    /// assert_eq!(sql.sql(), Some("SELECT * FROM example_table WHERE ID = ?;"));
+    ///
+    /// sql.kill();
+    /// db.close();
+    /// ```
+    #[inline]
+    pub fn sql(&self) -> Option<&str> {
+        statement_sql(self.0)
+    }
+
+    /// Returns the SQL text of the prepared statement with the bound
+    /// parameters expanded. Returns `None` if there isn't enough memory
+    /// or the expanded text exceeds `SQLITE_LIMIT_LENGTH`.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let statement = String::from(
+    ///     "SELECT * FROM example_table WHERE ID = ?;"
+    /// );
+    ///
+    /// let sql = db.prepare(statement, None::<Box<dyn FnOnce(SqlitePrimaryResult, String)>>).unwrap();
+    /// sql.bind_val(1, 15);
+    /// assert_eq!(sql.expanded_sql(), Some(String::from("SELECT * FROM example_table WHERE ID = 15;")));
+    ///
+    /// sql.kill();
+    /// db.close();
+    /// ```
+    #[inline]
+    pub fn expanded_sql(&self) -> Option<String> {
+        statement_expanded_sql(self.0)
+    }
+
     /// Called to destroy prepared statement. This function must be called for
     /// each prepared statement. Otherwise some resource leaks might happen.
     ///
@@ -202,3 +403,28 @@ impl<'a> SqlStatement {
         unsafe { SqlitePrimaryResult::from(sqlite3_finalize(self.0)) }
     }
 }
+
+#[inline]
+pub(crate) fn statement_sql<'a>(stmt: *mut sqlite3_stmt) -> Option<&'a str> {
+    unsafe {
+        let sql = sqlite3_sql(stmt);
+        if sql.is_null() {
+            return None;
+        }
+
+        CStr::from_ptr(sql).to_str().ok()
+    }
+}
+
+pub(crate) fn statement_expanded_sql(stmt: *mut sqlite3_stmt) -> Option<String> {
+    unsafe {
+        let sql = sqlite3_expanded_sql(stmt);
+        if sql.is_null() {
+            return None;
+        }
+
+        let expanded = CStr::from_ptr(sql).to_str().ok().map(str::to_owned);
+        sqlite3_free(sql as *mut os::raw::c_void);
+        expanded
+    }
+}
@@ -0,0 +1,521 @@
+//! This module contains ready-made eponymous virtual tables that are
+//! used as table-valued functions, `rarray` and `generate_series`.
+
+#![forbid(missing_docs)]
+#![allow(clippy::not_unsafe_ptr_arg_deref)] // same as operations module, `bind_val` is driven by `SqlStatement`.
+
+use std::{os, sync::Arc};
+
+use crate::{
+    bindings::{sqlite3_bind_pointer, sqlite3_stmt, sqlite3_value_pointer},
+    ehandle::MinSqliteWrapperError,
+    operations::ColumnCapabilities,
+    prelude::*,
+    value::ValueRef,
+};
+
+/// Type tag of the pointers that are bound for `rarray`. SQLite compares
+/// tags with strcmp, so it must be kept in sync with the C extension.
+const ARRAY_TYPE: &[u8] = b"rarray\0";
+
+/// List of values that is bound as parameter of `rarray(?)`.
+pub type Array = Arc<Vec<Value>>;
+
+unsafe extern "C" fn free_array(pointer: *mut os::raw::c_void) {
+    drop(Arc::from_raw(pointer as *const Vec<Value>));
+}
+
+impl<'a> ColumnCapabilities<'a> for Array {
+    fn get_data(_stmt: *mut sqlite3_stmt, _i: usize) -> Result<Self, MinSqliteWrapperError<'a>>
+    where
+        Self: Sized,
+    {
+        unimplemented!()
+    }
+
+    fn bind_val(self, stmt: *mut sqlite3_stmt, i: usize) -> SqlitePrimaryResult
+    where
+        Self: Sized,
+    {
+        unsafe {
+            // SQLite calls `free_array` when the binding is replaced, the
+            // statement is finalized, or binding fails.
+            SqlitePrimaryResult::from(sqlite3_bind_pointer(
+                stmt,
+                i as os::raw::c_int,
+                Arc::into_raw(self) as *mut os::raw::c_void,
+                ARRAY_TYPE.as_ptr() as *const os::raw::c_char,
+                Some(free_array),
+            ))
+        }
+    }
+}
+
+/// `rarray(?)` table which returns the values of the bound `Array`.
+struct ArrayTable;
+
+/// Cursor of `rarray`.
+struct ArrayCursor {
+    array: Option<Array>,
+    position: usize,
+}
+
+/// Column of `rarray` that holds the bound pointer.
+const ARRAY_COLUMN_POINTER: i32 = 1;
+
+impl VTab for ArrayTable {
+    type Aux = ();
+    type Cursor = ArrayCursor;
+
+    fn connect(
+        _aux: &Self::Aux,
+        _args: &[&str],
+    ) -> Result<(String, Self), MinSqliteWrapperError<'static>> {
+        Ok((
+            String::from("CREATE TABLE x(value, pointer HIDDEN)"),
+            ArrayTable,
+        ))
+    }
+
+    fn best_index(&self, info: &mut IndexInfo) -> Result<(), MinSqliteWrapperError<'static>> {
+        let pointer = info.constraints().position(|constraint| {
+            constraint.usable
+                && constraint.op == ConstraintOp::Eq
+                && constraint.column == ARRAY_COLUMN_POINTER
+        });
+
+        match pointer {
+            Some(constraint) => {
+                info.use_constraint(constraint, 1, true);
+                info.set_idx_num(1);
+                info.set_estimated_cost(1.0);
+                info.set_estimated_rows(100);
+            }
+            None => {
+                // Without the pointer the table is empty, so make sure the
+                // planner prefers any plan that passes it.
+                info.set_idx_num(0);
+                info.set_estimated_cost(2_147_483_647.0);
+                info.set_estimated_rows(2_147_483_647);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn open(&self) -> Result<Self::Cursor, MinSqliteWrapperError<'static>> {
+        Ok(ArrayCursor {
+            array: None,
+            position: 0,
+        })
+    }
+}
+
+impl VTabCursor for ArrayCursor {
+    fn filter(
+        &mut self,
+        idx_num: i32,
+        _idx_str: Option<&str>,
+        args: &Values,
+    ) -> Result<(), MinSqliteWrapperError<'static>> {
+        self.position = 0;
+        self.array = None;
+
+        if idx_num == 1 && !args.is_empty() {
+            unsafe {
+                let pointer = sqlite3_value_pointer(
+                    args.raw(0),
+                    ARRAY_TYPE.as_ptr() as *const os::raw::c_char,
+                ) as *const Vec<Value>;
+
+                // The statement keeps its reference until it's finalized,
+                // the cursor takes its own so that it can't outlive the data.
+                if !pointer.is_null() {
+                    Arc::increment_strong_count(pointer);
+                    self.array = Some(Arc::from_raw(pointer));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<(), MinSqliteWrapperError<'static>> {
+        self.position += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        match &self.array {
+            Some(array) => self.position >= array.len(),
+            None => true,
+        }
+    }
+
+    fn column(&self, i: usize) -> Result<Value, MinSqliteWrapperError<'static>> {
+        match (&self.array, i) {
+            (Some(array), 0) => Ok(array[self.position].clone()),
+            _ => Ok(Value::Null),
+        }
+    }
+
+    fn rowid(&self) -> Result<i64, MinSqliteWrapperError<'static>> {
+        Ok(self.position as i64 + 1)
+    }
+}
+
+/// `generate_series(start, stop, step)` table which returns the integers
+/// from `start` to `stop` with `step` increments.
+struct SeriesTable;
+
+/// Cursor of `generate_series`.
+struct SeriesCursor {
+    start: i64,
+    stop: i64,
+    step: i64,
+    count: u64,
+    position: u64,
+    descending: bool,
+}
+
+/// First and last columns of `generate_series` that hold the arguments.
+const SERIES_COLUMN_START: i32 = 1;
+const SERIES_COLUMN_STEP: i32 = 3;
+
+/// Bits of `idx_num` for `generate_series`.
+const SERIES_START: i32 = 1;
+const SERIES_STOP: i32 = 2;
+const SERIES_STEP: i32 = 4;
+const SERIES_DESC: i32 = 8;
+const SERIES_ASC: i32 = 16;
+
+/// Default `stop` of `generate_series`, same as the C extension.
+const SERIES_DEFAULT_STOP: i64 = 0xffff_ffff;
+
+impl VTab for SeriesTable {
+    type Aux = ();
+    type Cursor = SeriesCursor;
+
+    fn connect(
+        _aux: &Self::Aux,
+        _args: &[&str],
+    ) -> Result<(String, Self), MinSqliteWrapperError<'static>> {
+        Ok((
+            String::from("CREATE TABLE x(value, start HIDDEN, stop HIDDEN, step HIDDEN)"),
+            SeriesTable,
+        ))
+    }
+
+    fn best_index(&self, info: &mut IndexInfo) -> Result<(), MinSqliteWrapperError<'static>> {
+        let mut arguments = [None; 3];
+
+        for (i, constraint) in info.constraints().enumerate() {
+            if constraint.usable
+                && constraint.op == ConstraintOp::Eq
+                && (SERIES_COLUMN_START..=SERIES_COLUMN_STEP).contains(&constraint.column)
+            {
+                arguments[(constraint.column - SERIES_COLUMN_START) as usize] = Some(i);
+            }
+        }
+
+        let mut idx_num = 0;
+        let mut argv_index = 0;
+        for (bit, constraint) in [SERIES_START, SERIES_STOP, SERIES_STEP]
+            .into_iter()
+            .zip(arguments)
+        {
+            if let Some(constraint) = constraint {
+                argv_index += 1;
+                idx_num |= bit;
+                info.use_constraint(constraint, argv_index, true);
+            }
+        }
+
+        let order_by: Vec<OrderBy> = info.order_by().collect();
+        if let [term] = order_by.as_slice() {
+            if term.column == 0 {
+                idx_num |= if term.desc { SERIES_DESC } else { SERIES_ASC };
+                info.set_order_by_consumed(true);
+            }
+        }
+
+        // Arguments may only be known after another table is scanned, so
+        // make sure the planner prefers the plans that pass them.
+        if idx_num & (SERIES_START | SERIES_STOP) == SERIES_START | SERIES_STOP {
+            info.set_estimated_cost(if idx_num & SERIES_STEP != 0 { 1.0 } else { 2.0 });
+            info.set_estimated_rows(1000);
+        } else {
+            info.set_estimated_cost(2_147_483_647.0);
+            info.set_estimated_rows(2_147_483_647);
+        }
+
+        info.set_idx_num(idx_num);
+        Ok(())
+    }
+
+    fn open(&self) -> Result<Self::Cursor, MinSqliteWrapperError<'static>> {
+        Ok(SeriesCursor {
+            start: 0,
+            stop: 0,
+            step: 1,
+            count: 0,
+            position: 0,
+            descending: false,
+        })
+    }
+}
+
+/// Reads an argument of `generate_series`, `None` for NULL.
+fn series_argument(value: ValueRef) -> Option<i64> {
+    match value {
+        ValueRef::Null => None,
+        ValueRef::Integer(t) => Some(t),
+        ValueRef::Real(t) => Some(t as i64),
+        ValueRef::Text(t) => Some(t.trim().parse::<f64>().map(|t| t as i64).unwrap_or(0)),
+        ValueRef::Blob(_) => Some(0),
+    }
+}
+
+impl SeriesCursor {
+    #[inline]
+    fn current(&self) -> i64 {
+        let position = if self.descending {
+            self.count - 1 - self.position
+        } else {
+            self.position
+        };
+
+        (self.start as i128 + position as i128 * self.step as i128) as i64
+    }
+}
+
+impl VTabCursor for SeriesCursor {
+    fn filter(
+        &mut self,
+        idx_num: i32,
+        _idx_str: Option<&str>,
+        args: &Values,
+    ) -> Result<(), MinSqliteWrapperError<'static>> {
+        let mut start = Some(0);
+        let mut stop = Some(SERIES_DEFAULT_STOP);
+        let mut step = Some(1);
+
+        let mut i = 0;
+        for (bit, argument) in [
+            (SERIES_START, &mut start),
+            (SERIES_STOP, &mut stop),
+            (SERIES_STEP, &mut step),
+        ] {
+            if idx_num & bit != 0 && i < args.len() {
+                // Text that isn't valid UTF-8 can't be a number either.
+                *argument = args.get(i).map_or(Some(0), series_argument);
+                i += 1;
+            }
+        }
+
+        self.position = 0;
+        self.count = 0;
+
+        // Any NULL argument yields an empty series.
+        let (Some(start), Some(stop), Some(step)) = (start, stop, step) else {
+            return Ok(());
+        };
+
+        // The order is up to the ORDER BY if it's consumed, otherwise a
+        // negative step returns the same values in descending order.
+        self.descending = if idx_num & (SERIES_ASC | SERIES_DESC) != 0 {
+            idx_num & SERIES_DESC != 0
+        } else {
+            step < 0
+        };
+        let step = match step {
+            0 => 1,
+            step => step.unsigned_abs().min(i64::MAX as u64) as i64,
+        };
+
+        if start <= stop {
+            self.count = ((stop as i128 - start as i128) / step as i128 + 1) as u64;
+        }
+        self.start = start;
+        self.stop = stop;
+        self.step = step;
+
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<(), MinSqliteWrapperError<'static>> {
+        self.position += 1;
+        Ok(())
+    }
+
+    fn eof(&self) -> bool {
+        self.position >= self.count
+    }
+
+    fn column(&self, i: usize) -> Result<Value, MinSqliteWrapperError<'static>> {
+        match i {
+            0 => Ok(Value::Integer(self.current())),
+            1 => Ok(Value::Integer(self.start)),
+            2 => Ok(Value::Integer(self.stop)),
+            _ => Ok(Value::Integer(self.step)),
+        }
+    }
+
+    fn rowid(&self) -> Result<i64, MinSqliteWrapperError<'static>> {
+        Ok(self.position as i64 + 1)
+    }
+}
+
+/// Specifies the built-in table-valued functions of the SQLite connection.
+pub trait TableFunctions {
+    /// Registers the `rarray` table-valued function, which returns the
+    /// values of an `Array` that is bound as its argument. Binding the list
+    /// keeps large `IN` lists out of the SQL text.
+    ///
+    /// # Warning
+    /// The array can only be bound with `SqlStatement::bind_val`, it can't
+    /// be passed in the SQL text of `Operations::execute`.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// db.register_rarray().unwrap();
+    ///
+    /// let ids: Array = Arc::new(vec![Value::Integer(1), Value::Integer(5)]);
+    ///
+    /// let statement = String::from("SELECT name FROM items WHERE id IN rarray(?);");
+    /// let mut sql = db.prepare(statement, None::<Box<dyn FnOnce(SqlitePrimaryResult, String)>>).unwrap();
+    ///
+    /// let status = sql.bind_val(1, ids);
+    /// ...
+    ///
+    /// sql.kill();
+    /// db.close();
+    /// ```
+    fn register_rarray<'a>(&self) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>;
+
+    /// Registers the `generate_series(start, stop, step)` table-valued
+    /// function, which returns the integers from `start` to `stop`. `stop`
+    /// defaults to 4294967295 and `step` defaults to 1. A negative `step`
+    /// returns the same values in descending order, unless the query orders
+    /// them with ORDER BY.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// db.register_generate_series().unwrap();
+    ///
+    /// let statement = String::from("SELECT value FROM generate_series(1, 100, 5);");
+    /// ...
+    ///
+    /// db.close();
+    /// ```
+    fn register_generate_series<'a>(
+        &self,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>;
+}
+
+impl TableFunctions for Database {
+    #[inline]
+    fn register_rarray<'a>(&self) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>> {
+        self.create_module("rarray", Module::<ArrayTable>::eponymous_only(()))
+    }
+
+    #[inline]
+    fn register_generate_series<'a>(
+        &self,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>> {
+        self.create_module("generate_series", Module::<SeriesTable>::eponymous_only(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::prelude::*;
+
+    fn series(db: &Database, statement: &str) -> Vec<i64> {
+        db.query_values(statement)
+            .unwrap()
+            .into_iter()
+            .map(|row| match row[0] {
+                Value::Integer(value) => value,
+                ref other => panic!("{:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn generate_series_follows_the_step_and_order() {
+        let db = Database::open(":memory:").unwrap();
+        db.register_generate_series().unwrap();
+
+        assert_eq!(
+            series(&db, "SELECT value FROM generate_series(1, 9, 2);"),
+            [1, 3, 5, 7, 9]
+        );
+        assert_eq!(
+            series(&db, "SELECT value FROM generate_series(1, 9, -2);"),
+            [9, 7, 5, 3, 1]
+        );
+        assert_eq!(
+            series(
+                &db,
+                "SELECT value FROM generate_series(1, 9, -2) ORDER BY value ASC;"
+            ),
+            [1, 3, 5, 7, 9]
+        );
+        assert_eq!(
+            series(
+                &db,
+                "SELECT value FROM generate_series(1, 9, 2) ORDER BY value DESC;"
+            ),
+            [9, 7, 5, 3, 1]
+        );
+
+        // NULL arguments and empty ranges yield no rows
+        assert!(series(&db, "SELECT value FROM generate_series(1, NULL);").is_empty());
+        assert!(series(&db, "SELECT value FROM generate_series(5, 1);").is_empty());
+    }
+
+    #[test]
+    fn table_functions_must_be_registered() {
+        let db = Database::open(":memory:").unwrap();
+
+        let error = db
+            .query_values("SELECT value FROM generate_series(1, 3);")
+            .unwrap_err();
+        assert!(error.reason.contains("no such table"), "{}", error.reason);
+        assert!(db.query_values("SELECT value FROM rarray(?);").is_err());
+    }
+
+    #[test]
+    fn rarray_returns_the_bound_values() {
+        let db = Database::open(":memory:").unwrap();
+        db.register_rarray().unwrap();
+
+        let ids: Array = Arc::new(vec![Value::Integer(1), Value::Integer(5)]);
+        let mut sql = db.prepare_checked("SELECT value FROM rarray(?);").unwrap();
+        assert_eq!(sql.bind_val(1, Arc::clone(&ids)), SqlitePrimaryResult::Ok);
+        assert_eq!(Arc::strong_count(&ids), 2);
+
+        let mut values = vec![];
+        while let PreparedStatementStatus::FoundRow = sql.execute_prepared() {
+            values.push(sql.get_owned_data::<i64>(0).unwrap());
+        }
+        assert_eq!(values, [1, 5]);
+
+        // the binding is released with the statement
+        drop(sql);
+        assert_eq!(Arc::strong_count(&ids), 1);
+
+        // without a bound array the table is empty
+        assert!(db
+            .query_values("SELECT value FROM rarray(NULL);")
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,32 @@
+//! This module contains the helpers that are shared by the unit tests.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::prelude::*;
+
+/// Opens an in-memory database and runs `statements` on it.
+pub(crate) fn open_memory(statements: &str) -> Database {
+    let db = Database::open(":memory:").unwrap();
+    db.execute_checked(statements).unwrap();
+    db
+}
+
+/// Returns the path of the temporary file `name`, which is unique to the
+/// test process, after removing what an earlier run left there.
+pub(crate) fn temp_path(name: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("min_sqlite3_sys_{}_{}", std::process::id(), name));
+    remove_file(&path);
+    path
+}
+
+/// Removes the file at `path` together with its journal, WAL and shared
+/// memory files.
+pub(crate) fn remove_file(path: &Path) {
+    for suffix in ["", "-journal", "-wal", "-shm"] {
+        let _ = fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+}
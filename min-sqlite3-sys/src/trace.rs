@@ -0,0 +1,311 @@
+//! This module contains data-types and functions to trace the
+//! SQL statements that run on SQLite connection.
+
+#![forbid(missing_docs)]
+
+use std::{
+    ffi::CStr,
+    marker::PhantomData,
+    ops::{BitOr, BitOrAssign},
+    os,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use crate::{
+    bindings::{sqlite3_stmt, sqlite3_trace_v2},
+    prelude::*,
+    statement::{statement_expanded_sql, statement_sql},
+};
+
+/// Boxed trace callback stored by the `Database`.
+pub(crate) type TraceFn = Box<dyn FnMut(TraceEvent) + Send>;
+
+/// Set of the events that trace callback subscribes to.
+/// Can be combined with `|` operator.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct TraceEvents(u32);
+
+impl TraceEvents {
+    /// No event, disables tracing.
+    pub const NONE: TraceEvents = TraceEvents(0);
+    /// Invoked when a prepared statement first begins running.
+    pub const STMT: TraceEvents = TraceEvents(0x01);
+    /// Invoked when a prepared statement finishes, with its run time.
+    pub const PROFILE: TraceEvents = TraceEvents(0x02);
+    /// Invoked whenever a prepared statement generates a single row of result.
+    pub const ROW: TraceEvents = TraceEvents(0x04);
+    /// Invoked when the database connection closes with `close` or on drop.
+    /// It isn't delivered if unfinalized statements defer the close.
+    pub const CLOSE: TraceEvents = TraceEvents(0x08);
+    /// All of the events.
+    pub const ALL: TraceEvents = TraceEvents(0x0f);
+
+    /// Returns `true` if all of the events in `other` are contained in `self`.
+    #[inline]
+    pub fn contains(&self, other: TraceEvents) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for TraceEvents {
+    type Output = TraceEvents;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self::Output {
+        TraceEvents(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for TraceEvents {
+    #[inline]
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Statement that is being traced. Only valid inside of the trace callback.
+pub struct TracedStatement<'a> {
+    stmt: *mut sqlite3_stmt,
+    _marker: PhantomData<&'a ()>,
+}
+
+impl<'a> TracedStatement<'a> {
+    /// Returns the original SQL text of the statement.
+    #[inline]
+    pub fn sql(&self) -> Option<&'a str> {
+        statement_sql(self.stmt)
+    }
+
+    /// Returns the SQL text of the statement with bound parameters expanded.
+    #[inline]
+    pub fn expanded_sql(&self) -> Option<String> {
+        statement_expanded_sql(self.stmt)
+    }
+}
+
+/// Event that is passed to the trace callback.
+#[non_exhaustive]
+pub enum TraceEvent<'a> {
+    /// Statement started running. `sql` is the unexpanded SQL text, or a
+    /// comment that identifies the trigger if the statement is run by trigger.
+    Statement {
+        /// Statement that started running.
+        statement: TracedStatement<'a>,
+        /// Unexpanded SQL text of the statement.
+        sql: &'a str,
+    },
+    /// Statement finished running.
+    Profile {
+        /// Statement that finished running.
+        statement: TracedStatement<'a>,
+        /// Approximate run time of the statement in nanoseconds.
+        nanoseconds: u64,
+    },
+    /// Statement generated a row of result.
+    Row {
+        /// Statement that generated the row.
+        statement: TracedStatement<'a>,
+    },
+    /// Database connection is closing.
+    Close,
+}
+
+/// Specifies the tracing functions of the SQLite connection.
+pub trait Tracing {
+    /// Registers a trace callback that is invoked for the subscribed `events`.
+    /// Passing `None` or `TraceEvents::NONE` removes the registered callback.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let mut db = Database::open(db_path).unwrap();
+    ///
+    /// db.trace(
+    ///     TraceEvents::STMT | TraceEvents::PROFILE,
+    ///     Some(|event: TraceEvent| match event {
+    ///         TraceEvent::Statement { statement, .. } => {
+    ///             println!("running {:?}", statement.expanded_sql());
+    ///         }
+    ///         TraceEvent::Profile { statement, nanoseconds } => {
+    ///             println!("{:?} took {}ns", statement.sql(), nanoseconds);
+    ///         }
+    ///         _ => {}
+    ///     }),
+    /// );
+    ///
+    /// db.close();
+    /// ```
+    fn trace<F>(&mut self, events: TraceEvents, callback: Option<F>) -> SqlitePrimaryResult
+    where
+        F: FnMut(TraceEvent) + Send + 'static;
+}
+
+impl Tracing for Database {
+    fn trace<F>(&mut self, events: TraceEvents, callback: Option<F>) -> SqlitePrimaryResult
+    where
+        F: FnMut(TraceEvent) + Send + 'static,
+    {
+        match callback {
+            Some(callback) if events != TraceEvents::NONE => {
+                let mut boxed: Box<TraceFn> = Box::new(Box::new(callback));
+                let status = unsafe {
+                    SqlitePrimaryResult::from(sqlite3_trace_v2(
+                        self.rp,
+                        events.0 as os::raw::c_uint,
+                        Some(trace_trampoline),
+                        &mut *boxed as *mut TraceFn as *mut os::raw::c_void,
+                    ))
+                };
+
+                if status == SqlitePrimaryResult::Ok {
+                    self.trace = Some(boxed);
+                }
+
+                status
+            }
+            _ => {
+                let status = unsafe {
+                    SqlitePrimaryResult::from(sqlite3_trace_v2(self.rp, 0, None, ptr::null_mut()))
+                };
+                self.trace = None;
+
+                status
+            }
+        }
+    }
+}
+
+unsafe extern "C" fn trace_trampoline(
+    event_code: os::raw::c_uint,
+    ctx: *mut os::raw::c_void,
+    p: *mut os::raw::c_void,
+    x: *mut os::raw::c_void,
+) -> os::raw::c_int {
+    let statement = TracedStatement {
+        stmt: p as *mut sqlite3_stmt,
+        _marker: PhantomData,
+    };
+
+    let event = match event_code {
+        0x01 => TraceEvent::Statement {
+            statement,
+            sql: CStr::from_ptr(x as *const os::raw::c_char)
+                .to_str()
+                .unwrap_or_default(),
+        },
+        0x02 => TraceEvent::Profile {
+            statement,
+            nanoseconds: *(x as *const i64) as u64,
+        },
+        0x04 => TraceEvent::Row { statement },
+        0x08 => TraceEvent::Close,
+        _ => return 0,
+    };
+
+    let callback = &mut *(ctx as *mut TraceFn);
+    // unwinding into C is undefined behavior
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| callback(event)));
+
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::prelude::*;
+
+    #[test]
+    fn trace_reports_expanded_statements_and_rows() {
+        let mut db = Database::open(":memory:").unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+
+        let seen = events.clone();
+        let status = db.trace(
+            TraceEvents::STMT | TraceEvents::ROW,
+            Some(move |event: TraceEvent| {
+                let event = match event {
+                    TraceEvent::Statement { statement, .. } => statement.expanded_sql(),
+                    TraceEvent::Row { .. } => Some(String::from("row")),
+                    _ => None,
+                };
+                seen.lock().unwrap().extend(event);
+            }),
+        );
+        assert_eq!(status, SqlitePrimaryResult::Ok);
+
+        let mut sql = db
+            .prepare(
+                String::from("SELECT ? UNION ALL SELECT 2;"),
+                None::<Box<dyn FnOnce(SqlitePrimaryResult, String)>>,
+            )
+            .unwrap();
+        sql.bind_val(1, 1);
+        while sql.execute_prepared() == PreparedStatementStatus::FoundRow {}
+
+        assert_eq!(sql.sql(), Some("SELECT ? UNION ALL SELECT 2;"));
+        assert_eq!(
+            sql.expanded_sql().as_deref(),
+            Some("SELECT 1 UNION ALL SELECT 2;")
+        );
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                String::from("SELECT 1 UNION ALL SELECT 2;"),
+                String::from("row"),
+                String::from("row"),
+            ]
+        );
+    }
+
+    #[test]
+    fn trace_is_removed() {
+        let mut db = Database::open(":memory:").unwrap();
+        let calls = Arc::new(Mutex::new(0));
+
+        let seen = calls.clone();
+        db.trace(
+            TraceEvents::ALL,
+            Some(move |_: TraceEvent| *seen.lock().unwrap() += 1),
+        );
+        db.trace(TraceEvents::NONE, None::<fn(TraceEvent)>);
+        db.execute_changes(String::from("SELECT 1;")).unwrap();
+
+        assert_eq!(*calls.lock().unwrap(), 0);
+    }
+
+    #[test]
+    fn close_is_traced() {
+        let closes = Arc::new(Mutex::new(0));
+        let trace_closes = |db: &mut Database| {
+            let seen = closes.clone();
+            db.trace(
+                TraceEvents::CLOSE,
+                Some(move |event: TraceEvent| {
+                    if let TraceEvent::Close = event {
+                        *seen.lock().unwrap() += 1;
+                    }
+                }),
+            );
+        };
+
+        let mut db = Database::open(":memory:").unwrap();
+        trace_closes(&mut db);
+        assert_eq!(db.close(), SqlitePrimaryResult::Ok);
+        assert_eq!(*closes.lock().unwrap(), 1);
+
+        let mut db = Database::open(":memory:").unwrap();
+        trace_closes(&mut db);
+        drop(db);
+        assert_eq!(*closes.lock().unwrap(), 2);
+
+        // the close is deferred until the statement is finalized, after the
+        // callback is dropped
+        let mut db = Database::open(":memory:").unwrap();
+        trace_closes(&mut db);
+        let sql = db.prepare_checked("SELECT 1;").unwrap();
+        db.close();
+        drop(sql);
+        assert_eq!(*closes.lock().unwrap(), 2);
+    }
+}
@@ -0,0 +1,190 @@
+//! This module contains dynamically typed value representations
+//! of SQLite data.
+
+#![forbid(missing_docs)]
+
+use std::{slice, str};
+
+use crate::{
+    bindings::{
+        sqlite3_value, sqlite3_value_blob, sqlite3_value_bytes, sqlite3_value_double,
+        sqlite3_value_int64, sqlite3_value_text, sqlite3_value_type, COLUMN_BLOB, COLUMN_FLOAT,
+        COLUMN_INTEGER, COLUMN_NULL,
+    },
+    ehandle::MinSqliteWrapperError,
+};
+
+/// Owned SQLite value which can hold any of the fundamental
+/// SQLite data-types.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// SQL NULL
+    Null,
+    /// 64-bit signed integer
+    Integer(i64),
+    /// 64-bit IEEE floating point number
+    Real(f64),
+    /// UTF-8 string
+    Text(String),
+    /// Binary data
+    Blob(Vec<u8>),
+}
+
+/// Borrowed SQLite value which can hold any of the fundamental
+/// SQLite data-types. Text and blob variants point into memory
+/// owned by SQLite, so the value is only valid as long as its source.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueRef<'a> {
+    /// SQL NULL
+    Null,
+    /// 64-bit signed integer
+    Integer(i64),
+    /// 64-bit IEEE floating point number
+    Real(f64),
+    /// UTF-8 string
+    Text(&'a str),
+    /// Binary data
+    Blob(&'a [u8]),
+}
+
+impl<'a> ValueRef<'a> {
+    /// Reads the `sqlite3_value` pointer into `ValueRef`.
+    ///
+    /// # Safety
+    /// `value` must be a valid pointer that stays alive during `'a`.
+    pub(crate) unsafe fn from_raw(
+        value: *mut sqlite3_value,
+    ) -> Result<ValueRef<'a>, MinSqliteWrapperError<'a>> {
+        match sqlite3_value_type(value) as u32 {
+            COLUMN_NULL => Ok(ValueRef::Null),
+            COLUMN_INTEGER => Ok(ValueRef::Integer(sqlite3_value_int64(value))),
+            COLUMN_FLOAT => Ok(ValueRef::Real(sqlite3_value_double(value))),
+            COLUMN_BLOB => {
+                let pointer = sqlite3_value_blob(value);
+                if pointer.is_null() {
+                    return Ok(ValueRef::Blob(&[]));
+                }
+
+                let count = sqlite3_value_bytes(value) as usize;
+                Ok(ValueRef::Blob(slice::from_raw_parts(
+                    pointer as *const u8,
+                    count,
+                )))
+            }
+            _ => {
+                let pointer = sqlite3_value_text(value);
+                if pointer.is_null() {
+                    return Ok(ValueRef::Text(""));
+                }
+
+                // sqlite3_value_bytes must be called after sqlite3_value_text
+                // so that it reports the size of the UTF-8 representation.
+                let count = sqlite3_value_bytes(value) as usize;
+                Ok(ValueRef::Text(str::from_utf8(slice::from_raw_parts(
+                    pointer, count,
+                ))?))
+            }
+        }
+    }
+
+    /// Copies the borrowed data into an owned `Value`.
+    #[inline]
+    pub fn to_value(&self) -> Value {
+        Value::from(*self)
+    }
+}
+
+impl<'a> From<ValueRef<'a>> for Value {
+    fn from(value: ValueRef<'a>) -> Self {
+        match value {
+            ValueRef::Null => Value::Null,
+            ValueRef::Integer(t) => Value::Integer(t),
+            ValueRef::Real(t) => Value::Real(t),
+            ValueRef::Text(t) => Value::Text(t.to_owned()),
+            ValueRef::Blob(t) => Value::Blob(t.to_vec()),
+        }
+    }
+}
+
+impl<'a> From<&'a Value> for ValueRef<'a> {
+    fn from(value: &'a Value) -> Self {
+        match value {
+            Value::Null => ValueRef::Null,
+            Value::Integer(t) => ValueRef::Integer(*t),
+            Value::Real(t) => ValueRef::Real(*t),
+            Value::Text(t) => ValueRef::Text(t),
+            Value::Blob(t) => ValueRef::Blob(t),
+        }
+    }
+}
+
+impl Value {
+    /// Returns the value as SQL literal, e.g. `'it''s'` for `Value::Text("it's")`.
+    pub(crate) fn to_sql_literal(&self) -> String {
+        match self {
+            Value::Null => String::from("NULL"),
+            Value::Integer(t) => t.to_string(),
+            Value::Real(t) if t.is_nan() => String::from("NULL"),
+            Value::Real(t) if t.is_infinite() => String::from(if t.is_sign_positive() {
+                "9e999"
+            } else {
+                "-9e999"
+            }),
+            Value::Real(t) => format!("{:?}", t),
+            Value::Text(t) => format!("'{}'", t.replace('\'', "''")),
+            Value::Blob(t) => {
+                let hex: String = t.iter().map(|b| format!("{:02X}", b)).collect();
+                format!("X'{}'", hex)
+            }
+        }
+    }
+}
+
+impl From<i64> for Value {
+    #[inline]
+    fn from(value: i64) -> Self {
+        Value::Integer(value)
+    }
+}
+
+impl From<i32> for Value {
+    #[inline]
+    fn from(value: i32) -> Self {
+        Value::Integer(value.into())
+    }
+}
+
+impl From<bool> for Value {
+    #[inline]
+    fn from(value: bool) -> Self {
+        Value::Integer(value.into())
+    }
+}
+
+impl From<f64> for Value {
+    #[inline]
+    fn from(value: f64) -> Self {
+        Value::Real(value)
+    }
+}
+
+impl From<&str> for Value {
+    #[inline]
+    fn from(value: &str) -> Self {
+        Value::Text(value.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    #[inline]
+    fn from(value: String) -> Self {
+        Value::Text(value)
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    #[inline]
+    fn from(value: Vec<u8>) -> Self {
+        Value::Blob(value)
+    }
+}
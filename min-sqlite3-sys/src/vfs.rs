@@ -0,0 +1,1523 @@
+//! This module contains traits and functions to implement SQLite VFSes
+//! (the OS interface that SQLite does its file I/O through) in Rust, and
+//! the in-memory and pass-through VFSes as reference implementations.
+
+#![forbid(missing_docs)]
+#![allow(clippy::not_unsafe_ptr_arg_deref)] // file controls and shared memory are passed as SQLite gives them.
+
+use std::{
+    collections::HashMap,
+    ffi::{CStr, CString},
+    fs,
+    io::Read,
+    mem::{self, MaybeUninit},
+    os,
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    bindings::{
+        sqlite3_file, sqlite3_io_methods, sqlite3_vfs, sqlite3_vfs_find, sqlite3_vfs_register,
+    },
+    ehandle::MinSqliteWrapperError,
+    prelude::*,
+};
+
+/// Binder of SQLITE_IOERR_SHORT_READ from C source
+const SQLITE_IOERR_SHORT_READ: os::raw::c_int = 522;
+
+/// Binder of SQLITE_SYNC_FULL from C source
+const SYNC_FULL: os::raw::c_int = 0x00003;
+
+/// Binder of SQLITE_SYNC_DATAONLY from C source
+const SYNC_DATAONLY: os::raw::c_int = 0x00010;
+
+/// Julian day of the unix epoch in milliseconds.
+const UNIX_EPOCH_JULIAN_MS: i64 = 210_866_760_000_000;
+
+/// Result of the VFS operations. Errors are returned to SQLite as they
+/// are, e.g. `SqlitePrimaryResult::Other(266)` for SQLITE_IOERR_READ.
+pub type VfsResult<T> = Result<T, SqlitePrimaryResult>;
+
+/// Lock level of a database file.
+#[repr(i32)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+pub enum LockLevel {
+    /// No lock is held.
+    None = 0,
+    /// File can be read, any number of connections can hold it.
+    Shared = 1,
+    /// Connection is going to write, only one connection can hold it.
+    /// Other connections can still acquire shared locks.
+    Reserved = 2,
+    /// Connection is waiting for the shared locks to be released, no new
+    /// shared locks can be acquired. Never requested by SQLite directly.
+    Pending = 3,
+    /// File can be written, no other lock is held.
+    Exclusive = 4,
+}
+
+impl From<os::raw::c_int> for LockLevel {
+    #[inline]
+    fn from(value: os::raw::c_int) -> Self {
+        match value {
+            0 => LockLevel::None,
+            1 => LockLevel::Shared,
+            2 => LockLevel::Reserved,
+            3 => LockLevel::Pending,
+            _ => LockLevel::Exclusive,
+        }
+    }
+}
+
+/// Check of `Vfs::access`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum AccessCheck {
+    /// File exists.
+    Exists,
+    /// File is readable and writable.
+    ReadWrite,
+    /// File is readable.
+    Read,
+}
+
+impl From<os::raw::c_int> for AccessCheck {
+    #[inline]
+    fn from(value: os::raw::c_int) -> Self {
+        match value {
+            1 => AccessCheck::ReadWrite,
+            2 => AccessCheck::Read,
+            _ => AccessCheck::Exists,
+        }
+    }
+}
+
+impl AccessCheck {
+    #[inline]
+    fn bits(&self) -> os::raw::c_int {
+        match self {
+            AccessCheck::Exists => 0,
+            AccessCheck::ReadWrite => 1,
+            AccessCheck::Read => 2,
+        }
+    }
+}
+
+/// Flags of `VfsFile::sync`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct SyncFlags {
+    /// Sync is done with `PRAGMA synchronous = FULL` semantics, e.g.
+    /// `F_FULLFSYNC` on macOS.
+    pub full: bool,
+    /// Only the content of the file needs to be synced, not its metadata.
+    pub data_only: bool,
+}
+
+impl SyncFlags {
+    #[inline]
+    fn from_bits(bits: os::raw::c_int) -> Self {
+        SyncFlags {
+            full: bits & 0x0f == SYNC_FULL,
+            data_only: bits & SYNC_DATAONLY != 0,
+        }
+    }
+
+    #[inline]
+    fn bits(&self) -> os::raw::c_int {
+        let sync = if self.full { SYNC_FULL } else { 0x00002 };
+        if self.data_only {
+            sync | SYNC_DATAONLY
+        } else {
+            sync
+        }
+    }
+}
+
+/// Name of the file that is opened with `Vfs::open`.
+#[derive(Debug, Copy, Clone)]
+pub struct VfsFileName<'a> {
+    /// Name as SQLite passed it, URI parameters of the main database file
+    /// follow its terminating nul.
+    raw: *const os::raw::c_char,
+    name: &'a str,
+}
+
+impl<'a> VfsFileName<'a> {
+    /// Wraps the name `raw`, which must be followed by two more nuls like
+    /// the names that SQLite passes without URI parameters.
+    #[inline]
+    pub(crate) fn new(raw: *const os::raw::c_char, name: &'a str) -> Self {
+        VfsFileName { raw, name }
+    }
+
+    /// Returns the name of the file.
+    #[inline]
+    pub fn as_str(&self) -> &'a str {
+        self.name
+    }
+}
+
+/// File that is opened by a `Vfs`, it's closed when it's dropped. Mirrors
+/// `sqlite3_io_methods`.
+pub trait VfsFile: Send + 'static {
+    /// Reads `buf.len()` bytes at `offset` into `buf`, and returns the
+    /// number of bytes read. Reading less than `buf.len()` bytes means that
+    /// the end of the file is reached.
+    fn read(&mut self, buf: &mut [u8], offset: u64) -> VfsResult<usize>;
+
+    /// Writes `buf` at `offset`, extending the file if needed.
+    fn write(&mut self, buf: &[u8], offset: u64) -> VfsResult<()>;
+
+    /// Truncates the file to `size` bytes.
+    fn truncate(&mut self, size: u64) -> VfsResult<()>;
+
+    /// Flushes the content of the file to the persistent storage.
+    fn sync(&mut self, flags: SyncFlags) -> VfsResult<()>;
+
+    /// Returns the size of the file in bytes.
+    fn file_size(&mut self) -> VfsResult<u64>;
+
+    /// Upgrades the lock of the file to `level`. Returns
+    /// `SqlitePrimaryResult::Busy` if it's held by another connection.
+    fn lock(&mut self, level: LockLevel) -> VfsResult<()>;
+
+    /// Downgrades the lock of the file to `level`, which is either
+    /// `LockLevel::Shared` or `LockLevel::None`.
+    fn unlock(&mut self, level: LockLevel) -> VfsResult<()>;
+
+    /// Returns `true` if any connection holds a reserved or higher lock on
+    /// the file.
+    fn check_reserved_lock(&mut self) -> VfsResult<bool>;
+
+    /// Handles the file control `op` (one of the SQLITE_FCNTL_* codes) with
+    /// its argument `arg`. Unknown controls must return
+    /// `SqlitePrimaryResult::NotFound`.
+    #[inline]
+    fn file_control(&mut self, op: i32, arg: *mut os::raw::c_void) -> VfsResult<()> {
+        let _ = (op, arg);
+        Err(SqlitePrimaryResult::NotFound)
+    }
+
+    /// Returns the sector size of the underlying storage in bytes.
+    #[inline]
+    fn sector_size(&mut self) -> i32 {
+        4096
+    }
+
+    /// Returns the SQLITE_IOCAP_* flags of the underlying storage.
+    #[inline]
+    fn device_characteristics(&mut self) -> i32 {
+        0
+    }
+
+    /// Returns `true` if the file implements the shared memory methods,
+    /// which are required by the WAL journal mode.
+    #[inline]
+    fn has_shared_memory(&self) -> bool {
+        false
+    }
+
+    /// Returns the shared memory `region` of `size` bytes, creating it if
+    /// `extend` is `true`. Returns a null pointer if it doesn't exist.
+    #[inline]
+    fn shm_map(&mut self, region: usize, size: usize, extend: bool) -> VfsResult<*mut u8> {
+        let _ = (region, size, extend);
+        Err(SqlitePrimaryResult::IoErr)
+    }
+
+    /// Acquires or releases the `n` shared memory locks starting at
+    /// `offset`, with the SQLITE_SHM_* `flags`.
+    #[inline]
+    fn shm_lock(&mut self, offset: usize, n: usize, flags: i32) -> VfsResult<()> {
+        let _ = (offset, n, flags);
+        Err(SqlitePrimaryResult::IoErr)
+    }
+
+    /// Orders the memory accesses to the shared memory.
+    #[inline]
+    fn shm_barrier(&mut self) {}
+
+    /// Unmaps the shared memory of the file, deleting it if `delete` is
+    /// `true`.
+    #[inline]
+    fn shm_unmap(&mut self, delete: bool) -> VfsResult<()> {
+        let _ = delete;
+        Ok(())
+    }
+}
+
+/// OS interface of SQLite, registered with `register_vfs`. Mirrors
+/// `sqlite3_vfs`.
+pub trait Vfs: Send + Sync + 'static {
+    /// Files that are opened by the VFS.
+    type File: VfsFile;
+
+    /// Opens the file `name` with `flags`, and returns it with the flags
+    /// it's actually opened with (e.g. `OpenFlags::READ_ONLY` if it can't
+    /// be written). `name` is `None` for the temporary files, which must
+    /// be deleted when they're closed.
+    fn open(
+        &self,
+        name: Option<VfsFileName>,
+        flags: OpenFlags,
+    ) -> VfsResult<(Self::File, OpenFlags)>;
+
+    /// Deletes the file `name`, and syncs its directory if `sync_dir` is
+    /// `true`.
+    fn delete(&self, name: &str, sync_dir: bool) -> VfsResult<()>;
+
+    /// Returns the result of `check` on the file `name`.
+    fn access(&self, name: &str, check: AccessCheck) -> VfsResult<bool>;
+
+    /// Returns the canonical path of the file `name`.
+    fn full_pathname(&self, name: &str) -> VfsResult<String>;
+
+    /// Returns the maximum length of the paths in bytes.
+    #[inline]
+    fn max_pathname(&self) -> usize {
+        1024
+    }
+
+    /// Fills `buf` with random bytes, and returns the number of bytes
+    /// written.
+    fn randomness(&self, buf: &mut [u8]) -> usize {
+        if let Ok(mut urandom) = fs::File::open("/dev/urandom") {
+            if urandom.read_exact(buf).is_ok() {
+                return buf.len();
+            }
+        }
+
+        // xorshift seeded with the time, SQLite only uses it for seeding
+        let mut state = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64
+            | 1;
+        for byte in buf.iter_mut() {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            *byte = state as u8;
+        }
+
+        buf.len()
+    }
+
+    /// Sleeps for `duration`, and returns how long it actually slept.
+    #[inline]
+    fn sleep(&self, duration: Duration) -> Duration {
+        thread::sleep(duration);
+        duration
+    }
+
+    /// Returns the current time as milliseconds since the julian epoch.
+    #[inline]
+    fn current_time(&self) -> i64 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        UNIX_EPOCH_JULIAN_MS + now.as_millis() as i64
+    }
+}
+
+/// `sqlite3_file` subclass of the files of `F`.
+#[repr(C)]
+struct FileHandle<F: VfsFile> {
+    base: sqlite3_file,
+    file: MaybeUninit<F>,
+}
+
+/// `sqlite3_vfs` subclass of the registered VFSes.
+#[repr(C)]
+struct Registration<V: Vfs> {
+    base: sqlite3_vfs,
+    vfs: V,
+    name: CString,
+    /// Default VFS at the time of the registration, the dynamic library
+    /// loading is forwarded to it.
+    fallback: *mut sqlite3_vfs,
+    methods: sqlite3_io_methods,
+    shm_methods: sqlite3_io_methods,
+}
+
+/// Runs `func`, turning panics into `SqlitePrimaryResult::IoErr` since
+/// unwinding into C is undefined behavior.
+#[inline]
+fn catch<T>(func: impl FnOnce() -> VfsResult<T>) -> VfsResult<T> {
+    panic::catch_unwind(AssertUnwindSafe(func)).unwrap_or(Err(SqlitePrimaryResult::IoErr))
+}
+
+#[inline]
+fn result_code(result: VfsResult<()>) -> os::raw::c_int {
+    match result {
+        Ok(()) => 0,
+        Err(status) => status.into(),
+    }
+}
+
+#[inline]
+unsafe fn registration<'a, V: Vfs>(vfs: *mut sqlite3_vfs) -> &'a Registration<V> {
+    &*(vfs as *const Registration<V>)
+}
+
+#[inline]
+unsafe fn vfs_file<'a, F: VfsFile>(file: *mut sqlite3_file) -> &'a mut F {
+    (*(file as *mut FileHandle<F>)).file.assume_init_mut()
+}
+
+#[inline]
+unsafe fn path<'a>(name: *const os::raw::c_char) -> VfsResult<&'a str> {
+    if name.is_null() {
+        return Err(SqlitePrimaryResult::CantOpen);
+    }
+
+    CStr::from_ptr(name)
+        .to_str()
+        .map_err(|_| SqlitePrimaryResult::CantOpen)
+}
+
+unsafe extern "C" fn x_close<F: VfsFile>(file: *mut sqlite3_file) -> os::raw::c_int {
+    let handle = file as *mut FileHandle<F>;
+    let result = catch(|| {
+        (*handle).file.assume_init_drop();
+        Ok(())
+    });
+    (*handle).base.p_methods = ptr::null();
+
+    result_code(result)
+}
+
+unsafe extern "C" fn x_read<F: VfsFile>(
+    file: *mut sqlite3_file,
+    buf: *mut os::raw::c_void,
+    amount: os::raw::c_int,
+    offset: os::raw::c_longlong,
+) -> os::raw::c_int {
+    let buf = slice::from_raw_parts_mut(buf as *mut u8, amount.max(0) as usize);
+
+    match catch(|| vfs_file::<F>(file).read(buf, offset as u64)) {
+        Ok(read) if read >= buf.len() => 0,
+        Ok(read) => {
+            // SQLite expects the rest of the buffer to be zeroed
+            buf[read..].fill(0);
+            SQLITE_IOERR_SHORT_READ
+        }
+        Err(status) => status.into(),
+    }
+}
+
+unsafe extern "C" fn x_write<F: VfsFile>(
+    file: *mut sqlite3_file,
+    buf: *const os::raw::c_void,
+    amount: os::raw::c_int,
+    offset: os::raw::c_longlong,
+) -> os::raw::c_int {
+    let buf = slice::from_raw_parts(buf as *const u8, amount.max(0) as usize);
+    result_code(catch(|| vfs_file::<F>(file).write(buf, offset as u64)))
+}
+
+unsafe extern "C" fn x_truncate<F: VfsFile>(
+    file: *mut sqlite3_file,
+    size: os::raw::c_longlong,
+) -> os::raw::c_int {
+    result_code(catch(|| vfs_file::<F>(file).truncate(size as u64)))
+}
+
+unsafe extern "C" fn x_sync<F: VfsFile>(
+    file: *mut sqlite3_file,
+    flags: os::raw::c_int,
+) -> os::raw::c_int {
+    result_code(catch(|| {
+        vfs_file::<F>(file).sync(SyncFlags::from_bits(flags))
+    }))
+}
+
+unsafe extern "C" fn x_file_size<F: VfsFile>(
+    file: *mut sqlite3_file,
+    size: *mut os::raw::c_longlong,
+) -> os::raw::c_int {
+    result_code(catch(|| {
+        *size = vfs_file::<F>(file).file_size()? as os::raw::c_longlong;
+        Ok(())
+    }))
+}
+
+unsafe extern "C" fn x_lock<F: VfsFile>(
+    file: *mut sqlite3_file,
+    level: os::raw::c_int,
+) -> os::raw::c_int {
+    result_code(catch(|| vfs_file::<F>(file).lock(LockLevel::from(level))))
+}
+
+unsafe extern "C" fn x_unlock<F: VfsFile>(
+    file: *mut sqlite3_file,
+    level: os::raw::c_int,
+) -> os::raw::c_int {
+    result_code(catch(|| vfs_file::<F>(file).unlock(LockLevel::from(level))))
+}
+
+unsafe extern "C" fn x_check_reserved_lock<F: VfsFile>(
+    file: *mut sqlite3_file,
+    reserved: *mut os::raw::c_int,
+) -> os::raw::c_int {
+    result_code(catch(|| {
+        *reserved = vfs_file::<F>(file).check_reserved_lock()? as os::raw::c_int;
+        Ok(())
+    }))
+}
+
+unsafe extern "C" fn x_file_control<F: VfsFile>(
+    file: *mut sqlite3_file,
+    op: os::raw::c_int,
+    arg: *mut os::raw::c_void,
+) -> os::raw::c_int {
+    result_code(catch(|| vfs_file::<F>(file).file_control(op, arg)))
+}
+
+unsafe extern "C" fn x_sector_size<F: VfsFile>(file: *mut sqlite3_file) -> os::raw::c_int {
+    catch(|| Ok(vfs_file::<F>(file).sector_size())).unwrap_or(4096)
+}
+
+unsafe extern "C" fn x_device_characteristics<F: VfsFile>(
+    file: *mut sqlite3_file,
+) -> os::raw::c_int {
+    catch(|| Ok(vfs_file::<F>(file).device_characteristics())).unwrap_or(0)
+}
+
+unsafe extern "C" fn x_shm_map<F: VfsFile>(
+    file: *mut sqlite3_file,
+    region: os::raw::c_int,
+    size: os::raw::c_int,
+    extend: os::raw::c_int,
+    pp: *mut *mut os::raw::c_void,
+) -> os::raw::c_int {
+    result_code(catch(|| {
+        *pp = vfs_file::<F>(file).shm_map(region as usize, size as usize, extend != 0)?
+            as *mut os::raw::c_void;
+        Ok(())
+    }))
+}
+
+unsafe extern "C" fn x_shm_lock<F: VfsFile>(
+    file: *mut sqlite3_file,
+    offset: os::raw::c_int,
+    n: os::raw::c_int,
+    flags: os::raw::c_int,
+) -> os::raw::c_int {
+    result_code(catch(|| {
+        vfs_file::<F>(file).shm_lock(offset as usize, n as usize, flags)
+    }))
+}
+
+unsafe extern "C" fn x_shm_barrier<F: VfsFile>(file: *mut sqlite3_file) {
+    let _ = catch(|| {
+        vfs_file::<F>(file).shm_barrier();
+        Ok(())
+    });
+}
+
+unsafe extern "C" fn x_shm_unmap<F: VfsFile>(
+    file: *mut sqlite3_file,
+    delete: os::raw::c_int,
+) -> os::raw::c_int {
+    result_code(catch(|| vfs_file::<F>(file).shm_unmap(delete != 0)))
+}
+
+fn io_methods<F: VfsFile>(shared_memory: bool) -> sqlite3_io_methods {
+    sqlite3_io_methods {
+        i_version: if shared_memory { 2 } else { 1 },
+        x_close: Some(x_close::<F>),
+        x_read: Some(x_read::<F>),
+        x_write: Some(x_write::<F>),
+        x_truncate: Some(x_truncate::<F>),
+        x_sync: Some(x_sync::<F>),
+        x_file_size: Some(x_file_size::<F>),
+        x_lock: Some(x_lock::<F>),
+        x_unlock: Some(x_unlock::<F>),
+        x_check_reserved_lock: Some(x_check_reserved_lock::<F>),
+        x_file_control: Some(x_file_control::<F>),
+        x_sector_size: Some(x_sector_size::<F>),
+        x_device_characteristics: Some(x_device_characteristics::<F>),
+        x_shm_map: if shared_memory {
+            Some(x_shm_map::<F>)
+        } else {
+            None
+        },
+        x_shm_lock: if shared_memory {
+            Some(x_shm_lock::<F>)
+        } else {
+            None
+        },
+        x_shm_barrier: if shared_memory {
+            Some(x_shm_barrier::<F>)
+        } else {
+            None
+        },
+        x_shm_unmap: if shared_memory {
+            Some(x_shm_unmap::<F>)
+        } else {
+            None
+        },
+        x_fetch: None,
+        x_unfetch: None,
+    }
+}
+
+unsafe extern "C" fn x_open<V: Vfs>(
+    vfs: *mut sqlite3_vfs,
+    name: *const os::raw::c_char,
+    file: *mut sqlite3_file,
+    flags: os::raw::c_int,
+    out_flags: *mut os::raw::c_int,
+) -> os::raw::c_int {
+    let registration = registration::<V>(vfs);
+    let handle = file as *mut FileHandle<V::File>;
+    // SQLite doesn't close the file if the methods are null
+    (*handle).base.p_methods = ptr::null();
+
+    let name = if name.is_null() {
+        None
+    } else {
+        match path(name) {
+            Ok(path) => Some(VfsFileName {
+                raw: name,
+                name: path,
+            }),
+            Err(status) => return status.into(),
+        }
+    };
+
+    match catch(|| registration.vfs.open(name, OpenFlags::from_bits(flags))) {
+        Ok((opened, opened_flags)) => {
+            let methods = if opened.has_shared_memory() {
+                &registration.shm_methods
+            } else {
+                &registration.methods
+            };
+
+            (*handle).file = MaybeUninit::new(opened);
+            (*handle).base.p_methods = methods;
+            if !out_flags.is_null() {
+                *out_flags = opened_flags.bits();
+            }
+
+            0
+        }
+        Err(status) => status.into(),
+    }
+}
+
+unsafe extern "C" fn x_delete<V: Vfs>(
+    vfs: *mut sqlite3_vfs,
+    name: *const os::raw::c_char,
+    sync_dir: os::raw::c_int,
+) -> os::raw::c_int {
+    let registration = registration::<V>(vfs);
+    result_code(catch(|| {
+        registration.vfs.delete(path(name)?, sync_dir != 0)
+    }))
+}
+
+unsafe extern "C" fn x_access<V: Vfs>(
+    vfs: *mut sqlite3_vfs,
+    name: *const os::raw::c_char,
+    flags: os::raw::c_int,
+    result: *mut os::raw::c_int,
+) -> os::raw::c_int {
+    let registration = registration::<V>(vfs);
+    result_code(catch(|| {
+        *result = registration
+            .vfs
+            .access(path(name)?, AccessCheck::from(flags))? as os::raw::c_int;
+        Ok(())
+    }))
+}
+
+unsafe extern "C" fn x_full_pathname<V: Vfs>(
+    vfs: *mut sqlite3_vfs,
+    name: *const os::raw::c_char,
+    n_out: os::raw::c_int,
+    out: *mut os::raw::c_char,
+) -> os::raw::c_int {
+    let registration = registration::<V>(vfs);
+    result_code(catch(|| {
+        let full = registration.vfs.full_pathname(path(name)?)?;
+        if full.len() >= n_out.max(0) as usize {
+            return Err(SqlitePrimaryResult::CantOpen);
+        }
+
+        ptr::copy_nonoverlapping(full.as_ptr(), out as *mut u8, full.len());
+        *out.add(full.len()) = 0;
+        Ok(())
+    }))
+}
+
+unsafe extern "C" fn x_dl_open<V: Vfs>(
+    vfs: *mut sqlite3_vfs,
+    filename: *const os::raw::c_char,
+) -> *mut os::raw::c_void {
+    let fallback = registration::<V>(vfs).fallback;
+    match fallback.as_ref().and_then(|fallback| fallback.x_dl_open) {
+        Some(dl_open) => dl_open(fallback, filename),
+        None => ptr::null_mut(),
+    }
+}
+
+unsafe extern "C" fn x_dl_error<V: Vfs>(
+    vfs: *mut sqlite3_vfs,
+    n_byte: os::raw::c_int,
+    err_msg: *mut os::raw::c_char,
+) {
+    let fallback = registration::<V>(vfs).fallback;
+    match fallback.as_ref().and_then(|fallback| fallback.x_dl_error) {
+        Some(dl_error) => dl_error(fallback, n_byte, err_msg),
+        None if n_byte > 0 => {
+            let message = b"dynamic libraries are not supported by this VFS";
+            let len = message.len().min(n_byte as usize - 1);
+            ptr::copy_nonoverlapping(message.as_ptr(), err_msg as *mut u8, len);
+            *err_msg.add(len) = 0;
+        }
+        None => {}
+    }
+}
+
+unsafe extern "C" fn x_dl_sym<V: Vfs>(
+    vfs: *mut sqlite3_vfs,
+    handle: *mut os::raw::c_void,
+    symbol: *const os::raw::c_char,
+) -> Option<unsafe extern "C" fn()> {
+    let fallback = registration::<V>(vfs).fallback;
+    match fallback.as_ref().and_then(|fallback| fallback.x_dl_sym) {
+        Some(dl_sym) => dl_sym(fallback, handle, symbol),
+        None => None,
+    }
+}
+
+unsafe extern "C" fn x_dl_close<V: Vfs>(vfs: *mut sqlite3_vfs, handle: *mut os::raw::c_void) {
+    let fallback = registration::<V>(vfs).fallback;
+    if let Some(dl_close) = fallback.as_ref().and_then(|fallback| fallback.x_dl_close) {
+        dl_close(fallback, handle);
+    }
+}
+
+unsafe extern "C" fn x_randomness<V: Vfs>(
+    vfs: *mut sqlite3_vfs,
+    n_byte: os::raw::c_int,
+    out: *mut os::raw::c_char,
+) -> os::raw::c_int {
+    let registration = registration::<V>(vfs);
+    let buf = slice::from_raw_parts_mut(out as *mut u8, n_byte.max(0) as usize);
+    catch(|| Ok(registration.vfs.randomness(buf))).unwrap_or(0) as os::raw::c_int
+}
+
+unsafe extern "C" fn x_sleep<V: Vfs>(
+    vfs: *mut sqlite3_vfs,
+    microseconds: os::raw::c_int,
+) -> os::raw::c_int {
+    let registration = registration::<V>(vfs);
+    let duration = Duration::from_micros(microseconds.max(0) as u64);
+    catch(|| Ok(registration.vfs.sleep(duration)))
+        .unwrap_or_default()
+        .as_micros() as os::raw::c_int
+}
+
+unsafe extern "C" fn x_current_time<V: Vfs>(
+    vfs: *mut sqlite3_vfs,
+    time: *mut f64,
+) -> os::raw::c_int {
+    let registration = registration::<V>(vfs);
+    result_code(catch(|| {
+        *time = registration.vfs.current_time() as f64 / 86_400_000.0;
+        Ok(())
+    }))
+}
+
+unsafe extern "C" fn x_current_time_int64<V: Vfs>(
+    vfs: *mut sqlite3_vfs,
+    time: *mut os::raw::c_longlong,
+) -> os::raw::c_int {
+    let registration = registration::<V>(vfs);
+    result_code(catch(|| {
+        *time = registration.vfs.current_time();
+        Ok(())
+    }))
+}
+
+unsafe extern "C" fn x_get_last_error(
+    _vfs: *mut sqlite3_vfs,
+    _n_byte: os::raw::c_int,
+    _err_msg: *mut os::raw::c_char,
+) -> os::raw::c_int {
+    0
+}
+
+/// Registers `vfs` as `name`, so that it can be used by
+/// `Database::open_with_flags` or the `vfs` URI parameter. If
+/// `make_default` is `true`, it's used by all the connections that don't
+/// specify a VFS.
+///
+/// Registered VFSes live until the end of the process, and the names can't
+/// be registered twice.
+///
+/// # Usage
+/// min_sqlite3_sys::vfs::register_vfs("memory", MemoryVfs::default(), false).unwrap();
+///
+/// let db = Database::open_with_flags("packages.db", OpenFlags::default(), Some("memory")).unwrap();
+///
+/// db.close();
+/// ```
+pub fn register_vfs<'a, V: Vfs>(
+    name: &str,
+    vfs: V,
+    make_default: bool,
+) -> Result<(), MinSqliteWrapperError<'a>> {
+    let name = CString::new(name)?;
+
+    if mem::align_of::<FileHandle<V::File>>() > mem::align_of::<u64>() {
+        return Err(MinSqliteWrapperError {
+            kind: "sqlite3:Vfs",
+            reason: String::from("VFS files can't be aligned to more than 8 bytes"),
+        });
+    }
+
+    unsafe {
+        if !sqlite3_vfs_find(name.as_ptr()).is_null() {
+            return Err(MinSqliteWrapperError {
+                kind: "sqlite3:Vfs",
+                reason: format!("VFS {:?} is already registered", name),
+            });
+        }
+
+        let registration = Box::into_raw(Box::new(Registration {
+            base: sqlite3_vfs {
+                i_version: 2,
+                sz_os_file: mem::size_of::<FileHandle<V::File>>() as os::raw::c_int,
+                mx_pathname: vfs.max_pathname() as os::raw::c_int,
+                p_next: ptr::null_mut(),
+                // CString keeps its buffer when it's moved into the registration
+                z_name: name.as_ptr(),
+                p_app_data: ptr::null_mut(),
+                x_open: Some(x_open::<V>),
+                x_delete: Some(x_delete::<V>),
+                x_access: Some(x_access::<V>),
+                x_full_pathname: Some(x_full_pathname::<V>),
+                x_dl_open: Some(x_dl_open::<V>),
+                x_dl_error: Some(x_dl_error::<V>),
+                x_dl_sym: Some(x_dl_sym::<V>),
+                x_dl_close: Some(x_dl_close::<V>),
+                x_randomness: Some(x_randomness::<V>),
+                x_sleep: Some(x_sleep::<V>),
+                x_current_time: Some(x_current_time::<V>),
+                x_get_last_error: Some(x_get_last_error),
+                x_current_time_int64: Some(x_current_time_int64::<V>),
+                x_set_system_call: None,
+                x_get_system_call: None,
+                x_next_system_call: None,
+            },
+            vfs,
+            name,
+            fallback: sqlite3_vfs_find(ptr::null()),
+            methods: io_methods::<V::File>(false),
+            shm_methods: io_methods::<V::File>(true),
+        }));
+
+        let status = SqlitePrimaryResult::from(sqlite3_vfs_register(
+            registration as *mut sqlite3_vfs,
+            make_default as os::raw::c_int,
+        ));
+
+        if status != SqlitePrimaryResult::Ok {
+            drop(Box::from_raw(registration));
+            return Err(status.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Content and locks of a file of `MemoryVfs`.
+#[derive(Default)]
+struct MemoryFileData {
+    bytes: Vec<u8>,
+    shared: usize,
+    reserved: bool,
+    pending: bool,
+    exclusive: bool,
+}
+
+type MemoryFiles = Arc<Mutex<HashMap<String, Arc<Mutex<MemoryFileData>>>>>;
+
+#[inline]
+fn lock_data<T>(data: &Mutex<T>) -> MutexGuard<'_, T> {
+    data.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// VFS that keeps the files in memory, so they're shared by the
+/// connections of the process and lost when it exits. Clones share the
+/// same files.
+#[derive(Default, Clone)]
+pub struct MemoryVfs {
+    files: MemoryFiles,
+}
+
+impl MemoryVfs {
+    /// Creates a VFS with no files.
+    #[inline]
+    pub fn new() -> Self {
+        MemoryVfs::default()
+    }
+
+    /// Returns the names of the files in the VFS.
+    pub fn file_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = lock_data(&self.files).keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+/// File of `MemoryVfs`.
+pub struct MemoryFile {
+    data: Arc<Mutex<MemoryFileData>>,
+    level: LockLevel,
+    /// Name and the files of the VFS, if it's deleted when it's closed.
+    delete_on_close: Option<(String, MemoryFiles)>,
+}
+
+impl Drop for MemoryFile {
+    fn drop(&mut self) {
+        let _ = self.unlock(LockLevel::None);
+
+        if let Some((name, files)) = self.delete_on_close.take() {
+            lock_data(&files).remove(&name);
+        }
+    }
+}
+
+impl VfsFile for MemoryFile {
+    fn read(&mut self, buf: &mut [u8], offset: u64) -> VfsResult<usize> {
+        let data = lock_data(&self.data);
+        let start = (offset as usize).min(data.bytes.len());
+        let end = (start + buf.len()).min(data.bytes.len());
+
+        buf[..end - start].copy_from_slice(&data.bytes[start..end]);
+        Ok(end - start)
+    }
+
+    fn write(&mut self, buf: &[u8], offset: u64) -> VfsResult<()> {
+        let mut data = lock_data(&self.data);
+        let end = offset as usize + buf.len();
+        if data.bytes.len() < end {
+            data.bytes.resize(end, 0);
+        }
+
+        data.bytes[offset as usize..end].copy_from_slice(buf);
+        Ok(())
+    }
+
+    fn truncate(&mut self, size: u64) -> VfsResult<()> {
+        lock_data(&self.data).bytes.truncate(size as usize);
+        Ok(())
+    }
+
+    #[inline]
+    fn sync(&mut self, _flags: SyncFlags) -> VfsResult<()> {
+        Ok(())
+    }
+
+    #[inline]
+    fn file_size(&mut self) -> VfsResult<u64> {
+        Ok(lock_data(&self.data).bytes.len() as u64)
+    }
+
+    fn lock(&mut self, level: LockLevel) -> VfsResult<()> {
+        if self.level >= level {
+            return Ok(());
+        }
+
+        let mut data = lock_data(&self.data);
+        match level {
+            LockLevel::None => {}
+            LockLevel::Shared => {
+                if data.pending || data.exclusive {
+                    return Err(SqlitePrimaryResult::Busy);
+                }
+                data.shared += 1;
+            }
+            LockLevel::Reserved => {
+                if data.reserved {
+                    return Err(SqlitePrimaryResult::Busy);
+                }
+                data.reserved = true;
+            }
+            LockLevel::Pending | LockLevel::Exclusive => {
+                if self.level < LockLevel::Reserved {
+                    if data.reserved {
+                        return Err(SqlitePrimaryResult::Busy);
+                    }
+                    data.reserved = true;
+                }
+
+                // keeps the new readers out until the current ones are done
+                data.pending = true;
+                self.level = LockLevel::Pending;
+                if level == LockLevel::Pending || data.shared > 1 {
+                    return if level == LockLevel::Pending {
+                        Ok(())
+                    } else {
+                        Err(SqlitePrimaryResult::Busy)
+                    };
+                }
+                data.exclusive = true;
+            }
+        }
+
+        self.level = level;
+        Ok(())
+    }
+
+    fn unlock(&mut self, level: LockLevel) -> VfsResult<()> {
+        if self.level <= level {
+            return Ok(());
+        }
+
+        let mut data = lock_data(&self.data);
+        if self.level >= LockLevel::Reserved {
+            data.reserved = false;
+            data.pending = false;
+            data.exclusive = false;
+        }
+        if level == LockLevel::None {
+            data.shared -= 1;
+        }
+
+        self.level = level;
+        Ok(())
+    }
+
+    #[inline]
+    fn check_reserved_lock(&mut self) -> VfsResult<bool> {
+        Ok(lock_data(&self.data).reserved)
+    }
+}
+
+impl Vfs for MemoryVfs {
+    type File = MemoryFile;
+
+    fn open(
+        &self,
+        name: Option<VfsFileName>,
+        flags: OpenFlags,
+    ) -> VfsResult<(MemoryFile, OpenFlags)> {
+        let name = match name {
+            Some(name) => name.as_str().to_owned(),
+            // temporary files aren't visible to the other connections
+            None => {
+                let file = MemoryFile {
+                    data: Arc::default(),
+                    level: LockLevel::None,
+                    delete_on_close: None,
+                };
+                return Ok((file, flags));
+            }
+        };
+
+        let mut files = lock_data(&self.files);
+        let data = match files.get(&name) {
+            Some(_) if flags.contains(OpenFlags::EXCLUSIVE | OpenFlags::CREATE) => {
+                return Err(SqlitePrimaryResult::CantOpen)
+            }
+            Some(data) => data.clone(),
+            None if flags.contains(OpenFlags::CREATE) => {
+                let data = Arc::new(Mutex::new(MemoryFileData::default()));
+                files.insert(name.clone(), data.clone());
+                data
+            }
+            None => return Err(SqlitePrimaryResult::CantOpen),
+        };
+
+        let delete_on_close = if flags.contains(OpenFlags::DELETE_ON_CLOSE) {
+            Some((name, self.files.clone()))
+        } else {
+            None
+        };
+
+        let file = MemoryFile {
+            data,
+            level: LockLevel::None,
+            delete_on_close,
+        };
+
+        Ok((file, flags))
+    }
+
+    #[inline]
+    fn delete(&self, name: &str, _sync_dir: bool) -> VfsResult<()> {
+        lock_data(&self.files).remove(name);
+        Ok(())
+    }
+
+    #[inline]
+    fn access(&self, name: &str, _check: AccessCheck) -> VfsResult<bool> {
+        Ok(lock_data(&self.files).contains_key(name))
+    }
+
+    #[inline]
+    fn full_pathname(&self, name: &str) -> VfsResult<String> {
+        Ok(name.to_owned())
+    }
+}
+
+/// VFS that forwards everything to another registered VFS, e.g. to the
+/// default `unix` VFS. It's meant to be wrapped by VFSes that only change
+/// a few of the operations.
+pub struct PassthroughVfs {
+    inner: *mut sqlite3_vfs,
+}
+
+unsafe impl Send for PassthroughVfs {}
+unsafe impl Sync for PassthroughVfs {}
+
+#[inline]
+fn check(code: os::raw::c_int) -> VfsResult<()> {
+    match SqlitePrimaryResult::from(code) {
+        SqlitePrimaryResult::Ok => Ok(()),
+        status => Err(status),
+    }
+}
+
+impl PassthroughVfs {
+    /// Wraps the VFS registered as `name`, or the default VFS if `None`.
+    ///
+    /// # Usage
+    /// let vfs = PassthroughVfs::new(Some("unix")).unwrap();
+    /// min_sqlite3_sys::vfs::register_vfs("passthrough", vfs, false).unwrap();
+    /// ```
+    pub fn new<'a>(name: Option<&str>) -> Result<Self, MinSqliteWrapperError<'a>> {
+        let c_name = name.map(CString::new).transpose()?;
+        let inner =
+            unsafe { sqlite3_vfs_find(c_name.as_ref().map_or(ptr::null(), |n| n.as_ptr())) };
+
+        if inner.is_null() {
+            return Err(MinSqliteWrapperError {
+                kind: "sqlite3:Vfs",
+                reason: format!("VFS {:?} is not registered", name.unwrap_or("default")),
+            });
+        }
+
+        Ok(PassthroughVfs { inner })
+    }
+
+    /// Returns the name of the wrapped VFS.
+    pub fn inner_name(&self) -> String {
+        unsafe { CStr::from_ptr((*self.inner).z_name) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[inline]
+    fn inner(&self) -> &sqlite3_vfs {
+        unsafe { &*self.inner }
+    }
+}
+
+/// File of `PassthroughVfs`.
+pub struct PassthroughFile {
+    /// `sqlite3_file` of the wrapped VFS, allocated as `u64`s for alignment.
+    storage: Box<[u64]>,
+}
+
+unsafe impl Send for PassthroughFile {}
+
+impl PassthroughFile {
+    #[inline]
+    fn raw(&mut self) -> *mut sqlite3_file {
+        self.storage.as_mut_ptr() as *mut sqlite3_file
+    }
+
+    #[inline]
+    fn methods(&mut self) -> &sqlite3_io_methods {
+        unsafe { &*(*self.raw()).p_methods }
+    }
+}
+
+impl Drop for PassthroughFile {
+    fn drop(&mut self) {
+        let raw = self.raw();
+        unsafe {
+            if !(*raw).p_methods.is_null() {
+                if let Some(close) = (*(*raw).p_methods).x_close {
+                    close(raw);
+                }
+            }
+        }
+    }
+}
+
+impl VfsFile for PassthroughFile {
+    fn read(&mut self, buf: &mut [u8], offset: u64) -> VfsResult<usize> {
+        let raw = self.raw();
+        let read = self.methods().x_read.ok_or(SqlitePrimaryResult::IoErr)?;
+        let code = unsafe {
+            read(
+                raw,
+                buf.as_mut_ptr() as *mut os::raw::c_void,
+                buf.len() as os::raw::c_int,
+                offset as os::raw::c_longlong,
+            )
+        };
+
+        match code {
+            SQLITE_IOERR_SHORT_READ => {
+                let size = self.file_size()?;
+                Ok((size.saturating_sub(offset) as usize).min(buf.len()))
+            }
+            code => check(code).map(|_| buf.len()),
+        }
+    }
+
+    fn write(&mut self, buf: &[u8], offset: u64) -> VfsResult<()> {
+        let raw = self.raw();
+        let write = self.methods().x_write.ok_or(SqlitePrimaryResult::IoErr)?;
+        check(unsafe {
+            write(
+                raw,
+                buf.as_ptr() as *const os::raw::c_void,
+                buf.len() as os::raw::c_int,
+                offset as os::raw::c_longlong,
+            )
+        })
+    }
+
+    fn truncate(&mut self, size: u64) -> VfsResult<()> {
+        let raw = self.raw();
+        let truncate = self
+            .methods()
+            .x_truncate
+            .ok_or(SqlitePrimaryResult::IoErr)?;
+        check(unsafe { truncate(raw, size as os::raw::c_longlong) })
+    }
+
+    fn sync(&mut self, flags: SyncFlags) -> VfsResult<()> {
+        let raw = self.raw();
+        let sync = self.methods().x_sync.ok_or(SqlitePrimaryResult::IoErr)?;
+        check(unsafe { sync(raw, flags.bits()) })
+    }
+
+    fn file_size(&mut self) -> VfsResult<u64> {
+        let raw = self.raw();
+        let file_size = self
+            .methods()
+            .x_file_size
+            .ok_or(SqlitePrimaryResult::IoErr)?;
+        let mut size = 0;
+        check(unsafe { file_size(raw, &mut size) })?;
+        Ok(size as u64)
+    }
+
+    fn lock(&mut self, level: LockLevel) -> VfsResult<()> {
+        let raw = self.raw();
+        let lock = self.methods().x_lock.ok_or(SqlitePrimaryResult::IoErr)?;
+        check(unsafe { lock(raw, level as os::raw::c_int) })
+    }
+
+    fn unlock(&mut self, level: LockLevel) -> VfsResult<()> {
+        let raw = self.raw();
+        let unlock = self.methods().x_unlock.ok_or(SqlitePrimaryResult::IoErr)?;
+        check(unsafe { unlock(raw, level as os::raw::c_int) })
+    }
+
+    fn check_reserved_lock(&mut self) -> VfsResult<bool> {
+        let raw = self.raw();
+        let check_reserved_lock = self
+            .methods()
+            .x_check_reserved_lock
+            .ok_or(SqlitePrimaryResult::IoErr)?;
+        let mut reserved = 0;
+        check(unsafe { check_reserved_lock(raw, &mut reserved) })?;
+        Ok(reserved != 0)
+    }
+
+    fn file_control(&mut self, op: i32, arg: *mut os::raw::c_void) -> VfsResult<()> {
+        let raw = self.raw();
+        let file_control = self
+            .methods()
+            .x_file_control
+            .ok_or(SqlitePrimaryResult::NotFound)?;
+        check(unsafe { file_control(raw, op, arg) })
+    }
+
+    fn sector_size(&mut self) -> i32 {
+        let raw = self.raw();
+        match self.methods().x_sector_size {
+            Some(sector_size) => unsafe { sector_size(raw) },
+            None => 4096,
+        }
+    }
+
+    fn device_characteristics(&mut self) -> i32 {
+        let raw = self.raw();
+        match self.methods().x_device_characteristics {
+            Some(device_characteristics) => unsafe { device_characteristics(raw) },
+            None => 0,
+        }
+    }
+
+    fn has_shared_memory(&self) -> bool {
+        let methods = unsafe { &*(*(self.storage.as_ptr() as *const sqlite3_file)).p_methods };
+        methods.i_version >= 2 && methods.x_shm_map.is_some()
+    }
+
+    fn shm_map(&mut self, region: usize, size: usize, extend: bool) -> VfsResult<*mut u8> {
+        let raw = self.raw();
+        let shm_map = self.methods().x_shm_map.ok_or(SqlitePrimaryResult::IoErr)?;
+        let mut memory = ptr::null_mut();
+        check(unsafe {
+            shm_map(
+                raw,
+                region as os::raw::c_int,
+                size as os::raw::c_int,
+                extend as os::raw::c_int,
+                &mut memory,
+            )
+        })?;
+        Ok(memory as *mut u8)
+    }
+
+    fn shm_lock(&mut self, offset: usize, n: usize, flags: i32) -> VfsResult<()> {
+        let raw = self.raw();
+        let shm_lock = self
+            .methods()
+            .x_shm_lock
+            .ok_or(SqlitePrimaryResult::IoErr)?;
+        check(unsafe { shm_lock(raw, offset as os::raw::c_int, n as os::raw::c_int, flags) })
+    }
+
+    fn shm_barrier(&mut self) {
+        let raw = self.raw();
+        if let Some(shm_barrier) = self.methods().x_shm_barrier {
+            unsafe { shm_barrier(raw) };
+        }
+    }
+
+    fn shm_unmap(&mut self, delete: bool) -> VfsResult<()> {
+        let raw = self.raw();
+        let shm_unmap = self
+            .methods()
+            .x_shm_unmap
+            .ok_or(SqlitePrimaryResult::IoErr)?;
+        check(unsafe { shm_unmap(raw, delete as os::raw::c_int) })
+    }
+}
+
+impl Vfs for PassthroughVfs {
+    type File = PassthroughFile;
+
+    fn open(
+        &self,
+        name: Option<VfsFileName>,
+        flags: OpenFlags,
+    ) -> VfsResult<(PassthroughFile, OpenFlags)> {
+        let open = self.inner().x_open.ok_or(SqlitePrimaryResult::CantOpen)?;
+        let words = (self.inner().sz_os_file.max(0) as usize).div_ceil(8).max(1);
+        let mut file = PassthroughFile {
+            storage: vec![0_u64; words].into_boxed_slice(),
+        };
+
+        let mut out_flags = 0;
+        // the raw name is passed, since the wrapped VFS may read the URI
+        // parameters that follow it.
+        let code = unsafe {
+            open(
+                self.inner,
+                name.map_or(ptr::null(), |name| name.raw),
+                file.raw(),
+                flags.bits(),
+                &mut out_flags,
+            )
+        };
+
+        if let Err(status) = check(code) {
+            // the wrapped VFS expects to be closed if it set the methods
+            // even though opening failed, dropping the file does that.
+            drop(file);
+            return Err(status);
+        }
+
+        Ok((file, OpenFlags::from_bits(out_flags)))
+    }
+
+    fn delete(&self, name: &str, sync_dir: bool) -> VfsResult<()> {
+        let name = CString::new(name).map_err(|_| SqlitePrimaryResult::CantOpen)?;
+        let delete = self.inner().x_delete.ok_or(SqlitePrimaryResult::IoErr)?;
+        check(unsafe { delete(self.inner, name.as_ptr(), sync_dir as os::raw::c_int) })
+    }
+
+    fn access(&self, name: &str, check_kind: AccessCheck) -> VfsResult<bool> {
+        let name = CString::new(name).map_err(|_| SqlitePrimaryResult::CantOpen)?;
+        let access = self.inner().x_access.ok_or(SqlitePrimaryResult::IoErr)?;
+        let mut result = 0;
+        check(unsafe { access(self.inner, name.as_ptr(), check_kind.bits(), &mut result) })?;
+        Ok(result != 0)
+    }
+
+    fn full_pathname(&self, name: &str) -> VfsResult<String> {
+        let name = CString::new(name).map_err(|_| SqlitePrimaryResult::CantOpen)?;
+        let full_pathname = self
+            .inner()
+            .x_full_pathname
+            .ok_or(SqlitePrimaryResult::CantOpen)?;
+
+        let mut out = vec![0_u8; self.max_pathname() + 1];
+        check(unsafe {
+            full_pathname(
+                self.inner,
+                name.as_ptr(),
+                out.len() as os::raw::c_int,
+                out.as_mut_ptr() as *mut os::raw::c_char,
+            )
+        })?;
+
+        let full = CStr::from_bytes_until_nul(&out).map_err(|_| SqlitePrimaryResult::CantOpen)?;
+        full.to_str()
+            .map(str::to_owned)
+            .map_err(|_| SqlitePrimaryResult::CantOpen)
+    }
+
+    #[inline]
+    fn max_pathname(&self) -> usize {
+        self.inner().mx_pathname.max(0) as usize
+    }
+
+    fn randomness(&self, buf: &mut [u8]) -> usize {
+        match self.inner().x_randomness {
+            Some(randomness) => unsafe {
+                randomness(
+                    self.inner,
+                    buf.len() as os::raw::c_int,
+                    buf.as_mut_ptr() as *mut os::raw::c_char,
+                ) as usize
+            },
+            None => 0,
+        }
+    }
+
+    fn sleep(&self, duration: Duration) -> Duration {
+        match self.inner().x_sleep {
+            Some(sleep) => unsafe {
+                let slept = sleep(self.inner, duration.as_micros() as os::raw::c_int);
+                Duration::from_micros(slept.max(0) as u64)
+            },
+            None => Duration::ZERO,
+        }
+    }
+
+    fn current_time(&self) -> i64 {
+        let inner = self.inner();
+        if inner.i_version >= 2 {
+            if let Some(current_time) = inner.x_current_time_int64 {
+                let mut time = 0;
+                unsafe { current_time(self.inner, &mut time) };
+                return time;
+            }
+        }
+
+        match inner.x_current_time {
+            Some(current_time) => {
+                let mut time = 0.0;
+                unsafe { current_time(self.inner, &mut time) };
+                (time * 86_400_000.0) as i64
+            }
+            None => UNIX_EPOCH_JULIAN_MS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{remove_file, temp_path};
+
+    #[test]
+    fn memory_vfs_shares_the_files() {
+        let vfs = MemoryVfs::new();
+        register_vfs("test_memory", vfs.clone(), false).unwrap();
+
+        let writer =
+            Database::open_with_flags("shared.db", OpenFlags::default(), Some("test_memory"))
+                .unwrap();
+        writer
+            .execute_checked("CREATE TABLE t(x); INSERT INTO t VALUES (1);")
+            .unwrap();
+
+        let reader =
+            Database::open_with_flags("shared.db", OpenFlags::READ_ONLY, Some("test_memory"))
+                .unwrap();
+        assert_eq!(
+            reader.query_values("SELECT x FROM t;").unwrap(),
+            [[Value::Integer(1)]]
+        );
+        assert!(vfs.file_names().contains(&String::from("shared.db")));
+
+        assert!(Database::open_with_flags(
+            "missing.db",
+            OpenFlags::READ_WRITE,
+            Some("test_memory")
+        )
+        .is_err());
+        assert!(register_vfs("test_memory", MemoryVfs::new(), false).is_err());
+
+        reader.close();
+        writer.close();
+    }
+
+    #[test]
+    fn passthrough_vfs_forwards_to_the_wrapped_vfs() {
+        let vfs = PassthroughVfs::new(Some("unix")).unwrap();
+        assert_eq!(vfs.inner_name(), "unix");
+        register_vfs("test_passthrough", vfs, false).unwrap();
+
+        let path = temp_path("passthrough.db");
+
+        let db = Database::open_with_flags(&path, OpenFlags::default(), Some("test_passthrough"))
+            .unwrap();
+        db.execute_checked("CREATE TABLE t(x); INSERT INTO t VALUES (1);")
+            .unwrap();
+        db.close();
+
+        let db = Database::open(&path).unwrap();
+        assert_eq!(
+            db.query_values("SELECT x FROM t;").unwrap(),
+            [[Value::Integer(1)]]
+        );
+        db.close();
+        remove_file(&path);
+    }
+
+    #[test]
+    fn passthrough_vfs_reports_failed_opens() {
+        assert!(PassthroughVfs::new(Some("missing")).is_err());
+        register_vfs(
+            "test_passthrough_failing",
+            PassthroughVfs::new(None).unwrap(),
+            false,
+        )
+        .unwrap();
+
+        // the wrapped file is closed each time, so opening can be retried
+        for _ in 0..3 {
+            let error = Database::open_with_flags(
+                "/nonexistent/directory/db.sqlite",
+                OpenFlags::default(),
+                Some("test_passthrough_failing"),
+            )
+            .err()
+            .unwrap();
+            assert!(error.reason.starts_with("CantOpen"), "{}", error.reason);
+        }
+    }
+}
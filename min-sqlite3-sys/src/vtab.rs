@@ -0,0 +1,1026 @@
+//! This module contains traits and functions to expose Rust data
+//! as SQL tables through the virtual table mechanism of SQLite.
+
+#![forbid(missing_docs)]
+
+use std::{
+    ffi::{CStr, CString},
+    marker::PhantomData,
+    os,
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use crate::{
+    bindings::{
+        sqlite3, sqlite3_context, sqlite3_create_module_v2, sqlite3_declare_vtab, sqlite3_errmsg,
+        sqlite3_free, sqlite3_index_info, sqlite3_malloc64, sqlite3_module, sqlite3_result_blob64,
+        sqlite3_result_double, sqlite3_result_error, sqlite3_result_int64, sqlite3_result_null,
+        sqlite3_result_text64, sqlite3_value, sqlite3_value_int64, sqlite3_value_type,
+        sqlite3_vtab, sqlite3_vtab_cursor, sqlite_transient, XConnect, COLUMN_NULL,
+    },
+    ehandle::MinSqliteWrapperError,
+    prelude::*,
+    value::ValueRef,
+};
+
+/// Binder of SQLITE_INDEX_SCAN_UNIQUE from C source
+const INDEX_SCAN_UNIQUE: os::raw::c_int = 1;
+
+/// Binder of SQLITE_UTF8 from C source
+const SQLITE_UTF8: os::raw::c_uchar = 1;
+
+/// Binder of SQLITE_ERROR from C source
+const SQLITE_ERROR: os::raw::c_int = 1;
+
+/// Virtual table implementation. Each instance serves one table that is
+/// created with `CREATE VIRTUAL TABLE` or used as table-valued function.
+pub trait VTab: Sized + Send + 'static {
+    /// Data of the module that is shared by all of its tables, given to
+    /// `Module` on registration.
+    type Aux: Send + 'static;
+    /// Cursor that iterates over the rows of the table.
+    type Cursor: VTabCursor;
+
+    /// Connects to an existing virtual table. `args` are the module name,
+    /// the database name, the table name and then the arguments given in
+    /// `CREATE VIRTUAL TABLE ... USING module(arguments)`.
+    ///
+    /// Returns the `CREATE TABLE` statement that declares the columns of
+    /// the table, and the table itself.
+    fn connect(
+        aux: &Self::Aux,
+        args: &[&str],
+    ) -> Result<(String, Self), MinSqliteWrapperError<'static>>;
+
+    /// Creates a new virtual table on `CREATE VIRTUAL TABLE`. Tables that
+    /// have persistent state (e.g. backing tables) should initialize it
+    /// here. Same as `connect` by default.
+    fn create(
+        aux: &Self::Aux,
+        args: &[&str],
+    ) -> Result<(String, Self), MinSqliteWrapperError<'static>> {
+        Self::connect(aux, args)
+    }
+
+    /// Chooses the best way to access the table for the constraints and
+    /// ordering of the query. Constraints that are used must be marked
+    /// with `IndexInfo::use_constraint` so that their values are passed
+    /// to `VTabCursor::filter`.
+    fn best_index(&self, info: &mut IndexInfo) -> Result<(), MinSqliteWrapperError<'static>>;
+
+    /// Creates a new cursor on the table.
+    fn open(&self) -> Result<Self::Cursor, MinSqliteWrapperError<'static>>;
+
+    /// Applies INSERT, UPDATE or DELETE on the table. Returns the rowid of
+    /// the inserted row for `UpdateOperation::Insert`, the return value is
+    /// ignored for the other operations.
+    ///
+    /// Tables are read-only by default.
+    fn update(
+        &mut self,
+        operation: UpdateOperation,
+    ) -> Result<i64, MinSqliteWrapperError<'static>> {
+        let _ = operation;
+        Err(MinSqliteWrapperError {
+            kind: "sqlite3:VTab",
+            reason: String::from("virtual table is read-only"),
+        })
+    }
+
+    /// Called on `DROP TABLE`, before the table is disconnected. Tables
+    /// that have persistent state should delete it here.
+    fn destroy(&self) -> Result<(), MinSqliteWrapperError<'static>> {
+        Ok(())
+    }
+}
+
+/// Cursor of a virtual table.
+pub trait VTabCursor: Sized + Send + 'static {
+    /// Starts a new search on the table. `idx_num` and `idx_str` are the
+    /// values set by `VTab::best_index`, and `args` are the values of the
+    /// constraints in the order of their `argv_index`.
+    fn filter(
+        &mut self,
+        idx_num: i32,
+        idx_str: Option<&str>,
+        args: &Values,
+    ) -> Result<(), MinSqliteWrapperError<'static>>;
+
+    /// Advances the cursor to the next row.
+    fn next(&mut self) -> Result<(), MinSqliteWrapperError<'static>>;
+
+    /// Returns `true` if the cursor is past the last row.
+    fn eof(&self) -> bool;
+
+    /// Returns the value of column `i` of the current row.
+    fn column(&self, i: usize) -> Result<Value, MinSqliteWrapperError<'static>>;
+
+    /// Returns the rowid of the current row.
+    fn rowid(&self) -> Result<i64, MinSqliteWrapperError<'static>>;
+}
+
+/// Arguments passed from SQLite to the virtual table methods.
+pub struct Values<'a> {
+    argv: &'a [*mut sqlite3_value],
+}
+
+impl<'a> Values<'a> {
+    #[inline]
+    unsafe fn from_raw(argc: os::raw::c_int, argv: *mut *mut sqlite3_value) -> Self {
+        if argc <= 0 || argv.is_null() {
+            return Values { argv: &[] };
+        }
+
+        Values {
+            argv: slice::from_raw_parts(argv, argc as usize),
+        }
+    }
+
+    /// Returns the number of values.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.argv.len()
+    }
+
+    /// Returns `true` if there isn't any value.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.argv.is_empty()
+    }
+
+    /// Reads the value at index `i`.
+    ///
+    /// # Panics
+    /// - If `i` is out of range.
+    #[inline]
+    pub fn get(&self, i: usize) -> Result<ValueRef<'a>, MinSqliteWrapperError<'a>> {
+        unsafe { ValueRef::from_raw(self.argv[i]) }
+    }
+
+    /// Returns the raw pointer of the value at index `i`.
+    #[inline]
+    pub(crate) fn raw(&self, i: usize) -> *mut sqlite3_value {
+        self.argv[i]
+    }
+
+    /// Returns the values from index `start`.
+    #[inline]
+    fn tail(&self, start: usize) -> Values<'a> {
+        Values {
+            argv: self.argv.get(start..).unwrap_or(&[]),
+        }
+    }
+}
+
+/// Change that is requested on a virtual table.
+pub enum UpdateOperation<'a> {
+    /// Row with `rowid` is deleted.
+    Delete {
+        /// Rowid of the deleted row.
+        rowid: i64,
+    },
+    /// A new row is inserted.
+    Insert {
+        /// Rowid given by the statement, if any.
+        rowid: Option<i64>,
+        /// Values of the columns.
+        values: Values<'a>,
+    },
+    /// The row with `old_rowid` is updated.
+    Update {
+        /// Rowid of the row before the change.
+        old_rowid: i64,
+        /// Rowid of the row after the change.
+        new_rowid: i64,
+        /// Values of the columns after the change.
+        values: Values<'a>,
+    },
+}
+
+/// Operator of a WHERE clause constraint.
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ConstraintOp {
+    /// `=`
+    Eq,
+    /// `>`
+    Gt,
+    /// `<=`
+    Le,
+    /// `<`
+    Lt,
+    /// `>=`
+    Ge,
+    /// `MATCH`
+    Match,
+    /// `LIKE`
+    Like,
+    /// `GLOB`
+    Glob,
+    /// `REGEXP`
+    Regexp,
+    /// `!=` or `<>`
+    Ne,
+    /// `IS NOT`
+    IsNot,
+    /// `IS NOT NULL`
+    IsNotNull,
+    /// `IS NULL`
+    IsNull,
+    /// `IS`
+    Is,
+    /// `LIMIT`
+    Limit,
+    /// `OFFSET`
+    Offset,
+    /// Indicates the actual operator id from SQLITE as an inner value,
+    /// e.g. overloaded functions.
+    Other(u8),
+}
+
+impl From<os::raw::c_uchar> for ConstraintOp {
+    fn from(op: os::raw::c_uchar) -> Self {
+        match op {
+            2 => ConstraintOp::Eq,
+            4 => ConstraintOp::Gt,
+            8 => ConstraintOp::Le,
+            16 => ConstraintOp::Lt,
+            32 => ConstraintOp::Ge,
+            64 => ConstraintOp::Match,
+            65 => ConstraintOp::Like,
+            66 => ConstraintOp::Glob,
+            67 => ConstraintOp::Regexp,
+            68 => ConstraintOp::Ne,
+            69 => ConstraintOp::IsNot,
+            70 => ConstraintOp::IsNotNull,
+            71 => ConstraintOp::IsNull,
+            72 => ConstraintOp::Is,
+            73 => ConstraintOp::Limit,
+            74 => ConstraintOp::Offset,
+            other => ConstraintOp::Other(other),
+        }
+    }
+}
+
+/// WHERE clause constraint on a column of the virtual table.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct IndexConstraint {
+    /// Constrained column, -1 for the rowid.
+    pub column: i32,
+    /// Operator of the constraint.
+    pub op: ConstraintOp,
+    /// Whether the constraint can be used. Unusable constraints must not
+    /// be passed to `IndexInfo::use_constraint`.
+    pub usable: bool,
+}
+
+/// ORDER BY term of the query.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct OrderBy {
+    /// Ordered column, -1 for the rowid.
+    pub column: i32,
+    /// Whether the order is descending.
+    pub desc: bool,
+}
+
+/// Typed access to the `sqlite3_index_info` that is passed to
+/// `VTab::best_index`.
+pub struct IndexInfo<'a> {
+    raw: &'a mut sqlite3_index_info,
+}
+
+impl<'a> IndexInfo<'a> {
+    /// Returns the WHERE clause constraints of the query.
+    pub fn constraints(&self) -> impl Iterator<Item = IndexConstraint> + '_ {
+        let constraints = if self.raw.n_constraint <= 0 || self.raw.a_constraint.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.raw.a_constraint, self.raw.n_constraint as usize) }
+        };
+
+        constraints.iter().map(|constraint| IndexConstraint {
+            column: constraint.i_column,
+            op: ConstraintOp::from(constraint.op),
+            usable: constraint.usable != 0,
+        })
+    }
+
+    /// Returns the ORDER BY terms of the query.
+    pub fn order_by(&self) -> impl Iterator<Item = OrderBy> + '_ {
+        let order_by = if self.raw.n_order_by <= 0 || self.raw.a_order_by.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.raw.a_order_by, self.raw.n_order_by as usize) }
+        };
+
+        order_by.iter().map(|term| OrderBy {
+            column: term.i_column,
+            desc: term.desc != 0,
+        })
+    }
+
+    /// Passes the value of the constraint at index `constraint` to
+    /// `VTabCursor::filter` as the `argv_index`th argument, starting from 1.
+    /// If `omit` is `true`, SQLite doesn't double check the constraint.
+    ///
+    /// # Panics
+    /// - If the constraint index is out of range.
+    pub fn use_constraint(&mut self, constraint: usize, argv_index: u32, omit: bool) {
+        assert!(constraint < self.raw.n_constraint.max(0) as usize);
+
+        unsafe {
+            let usage = &mut *self.raw.a_constraint_usage.add(constraint);
+            usage.argv_index = argv_index as os::raw::c_int;
+            usage.omit = omit as os::raw::c_uchar;
+        }
+    }
+
+    /// Sets the number that is passed to `VTabCursor::filter` as `idx_num`.
+    #[inline]
+    pub fn set_idx_num(&mut self, idx_num: i32) {
+        self.raw.idx_num = idx_num;
+    }
+
+    /// Sets the text that is passed to `VTabCursor::filter` as `idx_str`.
+    pub fn set_idx_str(&mut self, idx_str: &str) {
+        unsafe {
+            if self.raw.need_to_free_idx_str != 0 {
+                sqlite3_free(self.raw.idx_str as *mut os::raw::c_void);
+            }
+
+            self.raw.idx_str = sqlite_string(idx_str);
+            self.raw.need_to_free_idx_str = !self.raw.idx_str.is_null() as os::raw::c_int;
+        }
+    }
+
+    /// Tells SQLite that the rows are returned in the order of `order_by()`.
+    #[inline]
+    pub fn set_order_by_consumed(&mut self, consumed: bool) {
+        self.raw.order_by_consumed = consumed as os::raw::c_int;
+    }
+
+    /// Sets the estimated cost of the access, lower is better.
+    #[inline]
+    pub fn set_estimated_cost(&mut self, cost: f64) {
+        self.raw.estimated_cost = cost;
+    }
+
+    /// Sets the estimated number of rows the access returns.
+    #[inline]
+    pub fn set_estimated_rows(&mut self, rows: i64) {
+        self.raw.estimated_rows = rows;
+    }
+
+    /// Tells SQLite that the access returns at most one row.
+    #[inline]
+    pub fn set_unique(&mut self, unique: bool) {
+        if unique {
+            self.raw.idx_flags |= INDEX_SCAN_UNIQUE;
+        } else {
+            self.raw.idx_flags &= !INDEX_SCAN_UNIQUE;
+        }
+    }
+
+    /// Returns the mask of the columns that are used by the query. Bit `i`
+    /// is set if column `i` is used, the last bit stands for all of the
+    /// columns from 63.
+    #[inline]
+    pub fn columns_used(&self) -> u64 {
+        self.raw.col_used
+    }
+}
+
+/// How the tables of a module are created.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ModuleKind {
+    /// Tables are created with `CREATE VIRTUAL TABLE`.
+    Regular,
+    /// Tables are created with `CREATE VIRTUAL TABLE`, and the module can
+    /// also be used directly as a table called with the module name.
+    Eponymous,
+    /// Module can only be used directly as a table called with the module
+    /// name, e.g. as table-valued function.
+    EponymousOnly,
+}
+
+/// Virtual table module that is registered with `VirtualTables::create_module`.
+pub struct Module<T: VTab> {
+    kind: ModuleKind,
+    aux: T::Aux,
+}
+
+impl<T: VTab> Module<T> {
+    /// Creates a module whose tables are created with `CREATE VIRTUAL TABLE`.
+    #[inline]
+    pub fn new(aux: T::Aux) -> Self {
+        Module {
+            kind: ModuleKind::Regular,
+            aux,
+        }
+    }
+
+    /// Creates a module that can be used both with `CREATE VIRTUAL TABLE`
+    /// and directly with its name.
+    #[inline]
+    pub fn eponymous(aux: T::Aux) -> Self {
+        Module {
+            kind: ModuleKind::Eponymous,
+            aux,
+        }
+    }
+
+    /// Creates a module that can only be used directly with its name.
+    #[inline]
+    pub fn eponymous_only(aux: T::Aux) -> Self {
+        Module {
+            kind: ModuleKind::EponymousOnly,
+            aux,
+        }
+    }
+
+    /// Returns the kind of the module.
+    #[inline]
+    pub fn kind(&self) -> ModuleKind {
+        self.kind
+    }
+}
+
+/// Specifies the virtual table functions of the SQLite connection.
+pub trait VirtualTables {
+    /// Registers the virtual table module `T` as `name`. Registering another
+    /// module with the same name replaces it.
+    ///
+    /// # Usage
+    /// struct Packages { index: Arc<Vec<Package>> }
+    ///
+    /// impl VTab for Packages {
+    ///     type Aux = Arc<Vec<Package>>;
+    ///     type Cursor = PackagesCursor;
+    ///     ...
+    /// }
+    ///
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// db.create_module("packages", Module::<Packages>::eponymous_only(index)).unwrap();
+    ///
+    /// let statement = String::from("SELECT name FROM packages WHERE version > 2;");
+    /// ...
+    ///
+    /// db.close();
+    /// ```
+    fn create_module<'a, T: VTab>(
+        &self,
+        name: &str,
+        module: Module<T>,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>>;
+}
+
+impl VirtualTables for Database {
+    fn create_module<'a, T: VTab>(
+        &self,
+        name: &str,
+        module: Module<T>,
+    ) -> Result<SqlitePrimaryResult, MinSqliteWrapperError<'a>> {
+        let name = CString::new(name)?;
+
+        let (x_create, x_destroy): (Option<XConnect>, _) = match module.kind {
+            ModuleKind::Regular => (Some(create_trampoline::<T>), destroy_trampoline::<T> as _),
+            ModuleKind::Eponymous => (
+                Some(connect_trampoline::<T>),
+                disconnect_trampoline::<T> as _,
+            ),
+            ModuleKind::EponymousOnly => (None, disconnect_trampoline::<T> as _),
+        };
+
+        let data = Box::new(ModuleData::<T> {
+            raw: sqlite3_module {
+                i_version: 1,
+                x_create,
+                x_connect: Some(connect_trampoline::<T>),
+                x_best_index: Some(best_index_trampoline::<T>),
+                x_disconnect: Some(disconnect_trampoline::<T>),
+                x_destroy: Some(x_destroy),
+                x_open: Some(open_trampoline::<T>),
+                x_close: Some(close_trampoline::<T>),
+                x_filter: Some(filter_trampoline::<T>),
+                x_next: Some(next_trampoline::<T>),
+                x_eof: Some(eof_trampoline::<T>),
+                x_column: Some(column_trampoline::<T>),
+                x_rowid: Some(rowid_trampoline::<T>),
+                x_update: Some(update_trampoline::<T>),
+                x_begin: None,
+                x_sync: None,
+                x_commit: None,
+                x_rollback: None,
+                x_find_function: None,
+                x_rename: None,
+                x_savepoint: None,
+                x_release: None,
+                x_rollback_to: None,
+                x_shadow_name: None,
+            },
+            aux: module.aux,
+            _marker: PhantomData,
+        });
+
+        let data = Box::into_raw(data);
+        // SQLite calls `free_module` when the module is replaced, the
+        // connection is closed or the registration fails.
+        unsafe {
+            Ok(SqlitePrimaryResult::from(sqlite3_create_module_v2(
+                self.rp,
+                name.as_ptr(),
+                &(*data).raw,
+                data as *mut os::raw::c_void,
+                Some(free_module::<T>),
+            )))
+        }
+    }
+}
+
+/// Registered module, passed to SQLite as the client data.
+struct ModuleData<T: VTab> {
+    raw: sqlite3_module,
+    aux: T::Aux,
+    _marker: PhantomData<T>,
+}
+
+/// Table instance, `base` must stay as the first field so that the
+/// pointers can be cast between the two types.
+#[repr(C)]
+struct VTabHandle<T: VTab> {
+    base: sqlite3_vtab,
+    vtab: T,
+}
+
+/// Cursor instance, `base` must stay as the first field so that the
+/// pointers can be cast between the two types.
+#[repr(C)]
+struct CursorHandle<C: VTabCursor> {
+    base: sqlite3_vtab_cursor,
+    cursor: C,
+}
+
+/// Copies `text` into memory obtained from `sqlite3_malloc64`, so that
+/// SQLite can free it.
+pub(crate) fn sqlite_string(text: &str) -> *mut os::raw::c_char {
+    unsafe {
+        let data = sqlite3_malloc64(text.len() as os::raw::c_ulonglong + 1) as *mut u8;
+        if data.is_null() {
+            return ptr::null_mut();
+        }
+
+        ptr::copy_nonoverlapping(text.as_ptr(), data, text.len());
+        *data.add(text.len()) = 0;
+        data as *mut os::raw::c_char
+    }
+}
+
+/// Sets the result of the SQL function context to `value`.
+pub(crate) unsafe fn set_result(ctx: *mut sqlite3_context, value: &Value) {
+    match value {
+        Value::Null => sqlite3_result_null(ctx),
+        Value::Integer(t) => sqlite3_result_int64(ctx, *t),
+        Value::Real(t) => sqlite3_result_double(ctx, *t),
+        Value::Text(t) => sqlite3_result_text64(
+            ctx,
+            t.as_ptr() as *const os::raw::c_char,
+            t.len() as os::raw::c_ulonglong,
+            sqlite_transient(),
+            SQLITE_UTF8,
+        ),
+        Value::Blob(t) => sqlite3_result_blob64(
+            ctx,
+            t.as_ptr() as *const os::raw::c_void,
+            t.len() as os::raw::c_ulonglong,
+            sqlite_transient(),
+        ),
+    }
+}
+
+/// Runs `func`, turning panics into errors since unwinding into C is
+/// undefined behavior.
+#[inline]
+fn catch<R>(
+    func: impl FnOnce() -> Result<R, MinSqliteWrapperError<'static>>,
+) -> Result<R, MinSqliteWrapperError<'static>> {
+    panic::catch_unwind(AssertUnwindSafe(func)).unwrap_or_else(|_| {
+        Err(MinSqliteWrapperError {
+            kind: "sqlite3:VTab",
+            reason: String::from("virtual table method panicked"),
+        })
+    })
+}
+
+/// Reports `error` as the error message of the table.
+unsafe fn set_vtab_error(
+    vtab: *mut sqlite3_vtab,
+    error: MinSqliteWrapperError<'static>,
+) -> os::raw::c_int {
+    if !(*vtab).z_err_msg.is_null() {
+        sqlite3_free((*vtab).z_err_msg as *mut os::raw::c_void);
+    }
+
+    (*vtab).z_err_msg = sqlite_string(&error.reason);
+    SQLITE_ERROR
+}
+
+unsafe extern "C" fn free_module<T: VTab>(data: *mut os::raw::c_void) {
+    drop(Box::from_raw(data as *mut ModuleData<T>));
+}
+
+unsafe fn connect_or_create<T: VTab>(
+    db: *mut sqlite3,
+    aux: *mut os::raw::c_void,
+    argc: os::raw::c_int,
+    argv: *const *const os::raw::c_char,
+    pp_vtab: *mut *mut sqlite3_vtab,
+    pz_err: *mut *mut os::raw::c_char,
+    create: bool,
+) -> os::raw::c_int {
+    let data = &*(aux as *const ModuleData<T>);
+    let result = catch(|| {
+        let args = (0..argc.max(0) as usize)
+            .map(|i| CStr::from_ptr(*argv.add(i)).to_str())
+            .collect::<Result<Vec<&str>, _>>()?;
+
+        let (schema, vtab) = if create {
+            T::create(&data.aux, &args)?
+        } else {
+            T::connect(&data.aux, &args)?
+        };
+
+        Ok((CString::new(schema)?, vtab))
+    });
+
+    let (schema, vtab) = match result {
+        Ok(result) => result,
+        Err(error) => {
+            *pz_err = sqlite_string(&error.reason);
+            return SQLITE_ERROR;
+        }
+    };
+
+    let status = sqlite3_declare_vtab(db, schema.as_ptr());
+    if status != 0 {
+        *pz_err = sqlite_string(&CStr::from_ptr(sqlite3_errmsg(db)).to_string_lossy());
+        return status;
+    }
+
+    let handle = Box::new(VTabHandle {
+        base: sqlite3_vtab {
+            p_module: ptr::null(),
+            n_ref: 0,
+            z_err_msg: ptr::null_mut(),
+        },
+        vtab,
+    });
+    *pp_vtab = Box::into_raw(handle) as *mut sqlite3_vtab;
+    0
+}
+
+unsafe extern "C" fn create_trampoline<T: VTab>(
+    db: *mut sqlite3,
+    aux: *mut os::raw::c_void,
+    argc: os::raw::c_int,
+    argv: *const *const os::raw::c_char,
+    pp_vtab: *mut *mut sqlite3_vtab,
+    pz_err: *mut *mut os::raw::c_char,
+) -> os::raw::c_int {
+    connect_or_create::<T>(db, aux, argc, argv, pp_vtab, pz_err, true)
+}
+
+unsafe extern "C" fn connect_trampoline<T: VTab>(
+    db: *mut sqlite3,
+    aux: *mut os::raw::c_void,
+    argc: os::raw::c_int,
+    argv: *const *const os::raw::c_char,
+    pp_vtab: *mut *mut sqlite3_vtab,
+    pz_err: *mut *mut os::raw::c_char,
+) -> os::raw::c_int {
+    connect_or_create::<T>(db, aux, argc, argv, pp_vtab, pz_err, false)
+}
+
+unsafe extern "C" fn best_index_trampoline<T: VTab>(
+    vtab: *mut sqlite3_vtab,
+    info: *mut sqlite3_index_info,
+) -> os::raw::c_int {
+    let handle = &*(vtab as *const VTabHandle<T>);
+    let mut info = IndexInfo { raw: &mut *info };
+
+    match catch(|| handle.vtab.best_index(&mut info)) {
+        Ok(()) => 0,
+        Err(error) => set_vtab_error(vtab, error),
+    }
+}
+
+unsafe extern "C" fn disconnect_trampoline<T: VTab>(vtab: *mut sqlite3_vtab) -> os::raw::c_int {
+    let handle = Box::from_raw(vtab as *mut VTabHandle<T>);
+    if !handle.base.z_err_msg.is_null() {
+        sqlite3_free(handle.base.z_err_msg as *mut os::raw::c_void);
+    }
+
+    // unwinding into C is undefined behavior
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(handle)));
+    0
+}
+
+unsafe extern "C" fn destroy_trampoline<T: VTab>(vtab: *mut sqlite3_vtab) -> os::raw::c_int {
+    let handle = &*(vtab as *const VTabHandle<T>);
+
+    match catch(|| handle.vtab.destroy()) {
+        Ok(()) => disconnect_trampoline::<T>(vtab),
+        // the table stays in use if it can't be destroyed
+        Err(error) => set_vtab_error(vtab, error),
+    }
+}
+
+unsafe extern "C" fn open_trampoline<T: VTab>(
+    vtab: *mut sqlite3_vtab,
+    pp_cursor: *mut *mut sqlite3_vtab_cursor,
+) -> os::raw::c_int {
+    let handle = &*(vtab as *const VTabHandle<T>);
+
+    match catch(|| handle.vtab.open()) {
+        Ok(cursor) => {
+            let cursor = Box::new(CursorHandle {
+                base: sqlite3_vtab_cursor {
+                    p_vtab: ptr::null_mut(),
+                },
+                cursor,
+            });
+            *pp_cursor = Box::into_raw(cursor) as *mut sqlite3_vtab_cursor;
+            0
+        }
+        Err(error) => set_vtab_error(vtab, error),
+    }
+}
+
+unsafe extern "C" fn close_trampoline<T: VTab>(cursor: *mut sqlite3_vtab_cursor) -> os::raw::c_int {
+    let handle = Box::from_raw(cursor as *mut CursorHandle<T::Cursor>);
+
+    // unwinding into C is undefined behavior
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(handle)));
+    0
+}
+
+unsafe extern "C" fn filter_trampoline<T: VTab>(
+    cursor: *mut sqlite3_vtab_cursor,
+    idx_num: os::raw::c_int,
+    idx_str: *const os::raw::c_char,
+    argc: os::raw::c_int,
+    argv: *mut *mut sqlite3_value,
+) -> os::raw::c_int {
+    let handle = &mut *(cursor as *mut CursorHandle<T::Cursor>);
+    let args = Values::from_raw(argc, argv);
+
+    let result = catch(|| {
+        let idx_str = if idx_str.is_null() {
+            None
+        } else {
+            Some(CStr::from_ptr(idx_str).to_str()?)
+        };
+
+        handle.cursor.filter(idx_num, idx_str, &args)
+    });
+
+    match result {
+        Ok(()) => 0,
+        Err(error) => set_vtab_error(handle.base.p_vtab, error),
+    }
+}
+
+unsafe extern "C" fn next_trampoline<T: VTab>(cursor: *mut sqlite3_vtab_cursor) -> os::raw::c_int {
+    let handle = &mut *(cursor as *mut CursorHandle<T::Cursor>);
+
+    match catch(|| handle.cursor.next()) {
+        Ok(()) => 0,
+        Err(error) => set_vtab_error(handle.base.p_vtab, error),
+    }
+}
+
+unsafe extern "C" fn eof_trampoline<T: VTab>(cursor: *mut sqlite3_vtab_cursor) -> os::raw::c_int {
+    let handle = &*(cursor as *const CursorHandle<T::Cursor>);
+
+    // unwinding into C is undefined behavior, end the scan instead
+    panic::catch_unwind(AssertUnwindSafe(|| handle.cursor.eof())).unwrap_or(true) as os::raw::c_int
+}
+
+unsafe extern "C" fn column_trampoline<T: VTab>(
+    cursor: *mut sqlite3_vtab_cursor,
+    ctx: *mut sqlite3_context,
+    i: os::raw::c_int,
+) -> os::raw::c_int {
+    let handle = &*(cursor as *const CursorHandle<T::Cursor>);
+
+    match catch(|| handle.cursor.column(i as usize)) {
+        Ok(value) => {
+            set_result(ctx, &value);
+            0
+        }
+        Err(error) => {
+            sqlite3_result_error(
+                ctx,
+                error.reason.as_ptr() as *const os::raw::c_char,
+                error.reason.len() as os::raw::c_int,
+            );
+            SQLITE_ERROR
+        }
+    }
+}
+
+unsafe extern "C" fn rowid_trampoline<T: VTab>(
+    cursor: *mut sqlite3_vtab_cursor,
+    p_rowid: *mut os::raw::c_longlong,
+) -> os::raw::c_int {
+    let handle = &*(cursor as *const CursorHandle<T::Cursor>);
+
+    match catch(|| handle.cursor.rowid()) {
+        Ok(rowid) => {
+            *p_rowid = rowid;
+            0
+        }
+        Err(error) => set_vtab_error(handle.base.p_vtab, error),
+    }
+}
+
+unsafe extern "C" fn update_trampoline<T: VTab>(
+    vtab: *mut sqlite3_vtab,
+    argc: os::raw::c_int,
+    argv: *mut *mut sqlite3_value,
+    p_rowid: *mut os::raw::c_longlong,
+) -> os::raw::c_int {
+    let handle = &mut *(vtab as *mut VTabHandle<T>);
+    let args = Values::from_raw(argc, argv);
+
+    let is_null = |i: usize| sqlite3_value_type(args.raw(i)) as u32 == COLUMN_NULL;
+    let operation = if args.len() == 1 {
+        UpdateOperation::Delete {
+            rowid: sqlite3_value_int64(args.raw(0)),
+        }
+    } else if is_null(0) {
+        UpdateOperation::Insert {
+            rowid: (!is_null(1)).then(|| sqlite3_value_int64(args.raw(1))),
+            values: args.tail(2),
+        }
+    } else {
+        UpdateOperation::Update {
+            old_rowid: sqlite3_value_int64(args.raw(0)),
+            new_rowid: sqlite3_value_int64(args.raw(1)),
+            values: args.tail(2),
+        }
+    };
+    let is_insert = matches!(operation, UpdateOperation::Insert { .. });
+
+    match catch(|| handle.vtab.update(operation)) {
+        Ok(rowid) => {
+            if is_insert {
+                *p_rowid = rowid;
+            }
+            0
+        }
+        Err(error) => set_vtab_error(vtab, error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Table of the numbers from 1 to its argument.
+    struct Numbers {
+        count: i64,
+    }
+
+    struct NumbersCursor {
+        current: i64,
+        count: i64,
+    }
+
+    impl VTab for Numbers {
+        type Aux = ();
+        type Cursor = NumbersCursor;
+
+        fn connect(
+            _aux: &Self::Aux,
+            args: &[&str],
+        ) -> Result<(String, Self), MinSqliteWrapperError<'static>> {
+            let count = args
+                .get(3)
+                .and_then(|count| count.trim().parse().ok())
+                .ok_or_else(|| MinSqliteWrapperError {
+                    kind: "sqlite3:VTab",
+                    reason: String::from("count is required"),
+                })?;
+
+            Ok((String::from("CREATE TABLE x(value)"), Numbers { count }))
+        }
+
+        fn best_index(&self, info: &mut IndexInfo) -> Result<(), MinSqliteWrapperError<'static>> {
+            info.set_estimated_rows(self.count);
+            Ok(())
+        }
+
+        fn open(&self) -> Result<Self::Cursor, MinSqliteWrapperError<'static>> {
+            Ok(NumbersCursor {
+                current: 1,
+                count: self.count,
+            })
+        }
+    }
+
+    impl VTabCursor for NumbersCursor {
+        fn filter(
+            &mut self,
+            _idx_num: i32,
+            _idx_str: Option<&str>,
+            _args: &Values,
+        ) -> Result<(), MinSqliteWrapperError<'static>> {
+            self.current = 1;
+            Ok(())
+        }
+
+        fn next(&mut self) -> Result<(), MinSqliteWrapperError<'static>> {
+            self.current += 1;
+            Ok(())
+        }
+
+        fn eof(&self) -> bool {
+            self.current > self.count
+        }
+
+        fn column(&self, _i: usize) -> Result<Value, MinSqliteWrapperError<'static>> {
+            Ok(Value::Integer(self.current))
+        }
+
+        fn rowid(&self) -> Result<i64, MinSqliteWrapperError<'static>> {
+            Ok(self.current)
+        }
+    }
+
+    #[test]
+    fn virtual_table_is_queried() {
+        let db = Database::open(":memory:").unwrap();
+        assert_eq!(
+            db.create_module("numbers", Module::<Numbers>::new(()))
+                .unwrap(),
+            SqlitePrimaryResult::Ok
+        );
+        db.execute_changes(String::from("CREATE VIRTUAL TABLE nums USING numbers(3);"))
+            .unwrap();
+
+        let rows = db
+            .query_values("SELECT rowid, value FROM nums WHERE value > 1;")
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                [Value::Integer(2), Value::Integer(2)],
+                [Value::Integer(3), Value::Integer(3)]
+            ]
+        );
+
+        db.execute_changes(String::from("DROP TABLE nums;"))
+            .unwrap();
+        db.close();
+    }
+
+    #[test]
+    fn virtual_table_errors_are_reported() {
+        let db = Database::open(":memory:").unwrap();
+        db.create_module("numbers", Module::<Numbers>::new(()))
+            .unwrap();
+
+        let error = db
+            .execute_changes(String::from("CREATE VIRTUAL TABLE bad USING numbers(x);"))
+            .unwrap_err();
+        assert!(
+            error.reason.contains("count is required"),
+            "{}",
+            error.reason
+        );
+
+        db.execute_changes(String::from("CREATE VIRTUAL TABLE nums USING numbers(3);"))
+            .unwrap();
+        let error = db
+            .execute_changes(String::from("INSERT INTO nums VALUES (4);"))
+            .unwrap_err();
+        assert!(
+            error.reason.contains("virtual table is read-only"),
+            "{}",
+            error.reason
+        );
+
+        // eponymous-only modules can't be created as tables
+        db.create_module("numbers_fn", Module::<Numbers>::eponymous_only(()))
+            .unwrap();
+        assert!(db
+            .execute_changes(String::from(
+                "CREATE VIRTUAL TABLE other USING numbers_fn(3);"
+            ))
+            .is_err());
+        db.close();
+    }
+}
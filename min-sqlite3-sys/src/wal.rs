@@ -0,0 +1,370 @@
+//! This module contains data-types and functions to manage the
+//! journal mode and write-ahead logging of SQLite connection.
+
+#![forbid(missing_docs)]
+
+use std::{
+    ffi::CStr,
+    os,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use crate::{
+    bindings::{sqlite3, sqlite3_wal_autocheckpoint, sqlite3_wal_checkpoint_v2, sqlite3_wal_hook},
+    ehandle::MinSqliteWrapperError,
+    prelude::*,
+};
+
+/// Boxed WAL hook stored by the `Database`.
+pub(crate) type WalHookFn = Box<dyn FnMut(&str, u32) -> SqlitePrimaryResult + Send>;
+
+/// Journal modes of SQLite.
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum JournalMode {
+    /// Rollback journal is deleted at the end of each transaction.
+    Delete,
+    /// Rollback journal is truncated to zero-length at the end of each transaction.
+    Truncate,
+    /// Rollback journal header is overwritten with zeros at the end of each transaction.
+    Persist,
+    /// Rollback journal is stored in memory.
+    Memory,
+    /// Write-ahead log is used instead of the rollback journal.
+    Wal,
+    /// Rollback journal is disabled.
+    Off,
+}
+
+impl JournalMode {
+    /// Returns the name of the mode as it's used in `PRAGMA journal_mode`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JournalMode::Delete => "DELETE",
+            JournalMode::Truncate => "TRUNCATE",
+            JournalMode::Persist => "PERSIST",
+            JournalMode::Memory => "MEMORY",
+            JournalMode::Wal => "WAL",
+            JournalMode::Off => "OFF",
+        }
+    }
+
+    /// Parses the mode from its name, case-insensitively.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "DELETE" => Some(JournalMode::Delete),
+            "TRUNCATE" => Some(JournalMode::Truncate),
+            "PERSIST" => Some(JournalMode::Persist),
+            "MEMORY" => Some(JournalMode::Memory),
+            "WAL" => Some(JournalMode::Wal),
+            "OFF" => Some(JournalMode::Off),
+            _ => None,
+        }
+    }
+}
+
+/// Checkpoint modes of the write-ahead log.
+#[repr(i32)]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum CheckpointMode {
+    /// Checkpoints as many frames as possible without waiting for
+    /// any readers or writers to finish.
+    Passive = 0,
+    /// Waits for the writers, then checkpoints all the frames. Blocks new
+    /// writers while it's running.
+    Full = 1,
+    /// Works like `Full`, and then also waits for the readers so that the
+    /// next writer restarts the log file from the beginning.
+    Restart = 2,
+    /// Works like `Restart`, and also truncates the log file to zero bytes.
+    Truncate = 3,
+}
+
+/// Specifies the journal and write-ahead log functions of the SQLite connection.
+pub trait WalOperations {
+    /// Changes the journal mode of the `database`, or of all the databases of
+    /// the connection if it's `None`, and returns the mode that is in effect
+    /// afterwards. The effective mode
+    /// might differ from the requested one, e.g. in-memory databases only
+    /// support `JournalMode::Memory` and `JournalMode::Off`.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let mode = db.set_journal_mode(None, JournalMode::Wal).unwrap();
+    /// assert_eq!(mode, JournalMode::Wal);
+    ///
+    /// db.close();
+    /// ```
+    fn set_journal_mode<'a>(
+        &self,
+        database: Option<DatabaseName>,
+        mode: JournalMode,
+    ) -> Result<JournalMode, MinSqliteWrapperError<'a>>;
+
+    /// Checkpoints the write-ahead log of the `database`, or of all the databases
+    /// of the connection if it's `None`. Returns the total number of frames in the log and the number of frames
+    /// that are checkpointed. Both are -1 if the database is not in WAL mode.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let db = Database::open(db_path).unwrap();
+    ///
+    /// let (log_frames, checkpointed_frames) = db.wal_checkpoint(Some(DatabaseName::Main), CheckpointMode::Truncate).unwrap();
+    ///
+    /// db.close();
+    /// ```
+    fn wal_checkpoint<'a>(
+        &self,
+        database: Option<DatabaseName>,
+        mode: CheckpointMode,
+    ) -> Result<(i32, i32), MinSqliteWrapperError<'a>>;
+
+    /// Makes the connection checkpoint automatically whenever the write-ahead
+    /// log reaches `n` pages. Zero or negative `n` disables the automatic
+    /// checkpoints.
+    ///
+    /// # Warning
+    /// Automatic checkpoints are implemented over the WAL hook, so this
+    /// function removes the hook that is registered by `wal_hook`.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let mut db = Database::open(db_path).unwrap();
+    ///
+    /// db.wal_autocheckpoint(1000);
+    ///
+    /// db.close();
+    /// ```
+    fn wal_autocheckpoint(&mut self, n: i32) -> SqlitePrimaryResult;
+
+    /// Registers a callback that is invoked each time a transaction is committed
+    /// into a write-ahead log, with the database name and the number of pages
+    /// in the log. Returning anything other than `SqlitePrimaryResult::Ok`
+    /// fails the statement that committed, but the transaction stays
+    /// committed. Passing `None` removes the registered callback.
+    ///
+    /// # Warning
+    /// This function disables the automatic checkpoints.
+    ///
+    /// # Usage
+    /// let db_path = Path::new("./example.db");
+    /// let mut db = Database::open(db_path).unwrap();
+    ///
+    /// db.wal_hook(Some(|db_name: &str, pages: u32| {
+    ///     println!("{} has {} pages in its log", db_name, pages);
+    ///     SqlitePrimaryResult::Ok
+    /// }));
+    ///
+    /// db.close();
+    /// ```
+    fn wal_hook<F>(&mut self, hook: Option<F>)
+    where
+        F: FnMut(&str, u32) -> SqlitePrimaryResult + Send + 'static;
+}
+
+impl WalOperations for Database {
+    fn set_journal_mode<'a>(
+        &self,
+        database: Option<DatabaseName>,
+        mode: JournalMode,
+    ) -> Result<JournalMode, MinSqliteWrapperError<'a>> {
+        let statement = match database {
+            Some(database) => format!(
+                "PRAGMA {}.journal_mode = {};",
+                database.to_sql_identifier(),
+                mode.as_str()
+            ),
+            None => format!("PRAGMA journal_mode = {};", mode.as_str()),
+        };
+        let mut sql = self.prepare_checked(&statement)?;
+
+        match sql.execute_prepared() {
+            PreparedStatementStatus::FoundRow => {
+                let name = sql.get_owned_data::<String>(0)?;
+                JournalMode::from_name(&name).ok_or(MinSqliteWrapperError {
+                    kind: "sqlite3:JournalMode",
+                    reason: format!("Unknown journal mode {}", name),
+                })
+            }
+            _ => Err(MinSqliteWrapperError::from_connection(self.rp)),
+        }
+    }
+
+    fn wal_checkpoint<'a>(
+        &self,
+        database: Option<DatabaseName>,
+        mode: CheckpointMode,
+    ) -> Result<(i32, i32), MinSqliteWrapperError<'a>> {
+        let database = database.map(DatabaseName::to_cstring).transpose()?;
+        let mut log_frames = 0;
+        let mut checkpointed_frames = 0;
+
+        let status = unsafe {
+            SqlitePrimaryResult::from(sqlite3_wal_checkpoint_v2(
+                self.rp,
+                database.as_ref().map_or(ptr::null(), |name| name.as_ptr()),
+                mode as os::raw::c_int,
+                &mut log_frames,
+                &mut checkpointed_frames,
+            ))
+        };
+
+        if status != SqlitePrimaryResult::Ok {
+            return Err(MinSqliteWrapperError::from_connection(self.rp));
+        }
+
+        Ok((log_frames, checkpointed_frames))
+    }
+
+    fn wal_autocheckpoint(&mut self, n: i32) -> SqlitePrimaryResult {
+        let status = unsafe { SqlitePrimaryResult::from(sqlite3_wal_autocheckpoint(self.rp, n)) };
+        self.wal_hook = None;
+        status
+    }
+
+    fn wal_hook<F>(&mut self, hook: Option<F>)
+    where
+        F: FnMut(&str, u32) -> SqlitePrimaryResult + Send + 'static,
+    {
+        match hook {
+            Some(hook) => {
+                let mut boxed: Box<WalHookFn> = Box::new(Box::new(hook));
+                unsafe {
+                    sqlite3_wal_hook(
+                        self.rp,
+                        Some(wal_hook_trampoline),
+                        &mut *boxed as *mut WalHookFn as *mut os::raw::c_void,
+                    );
+                }
+                self.wal_hook = Some(boxed);
+            }
+            None => {
+                unsafe {
+                    sqlite3_wal_hook(self.rp, None, ptr::null_mut());
+                }
+                self.wal_hook = None;
+            }
+        }
+    }
+}
+
+unsafe extern "C" fn wal_hook_trampoline(
+    ctx: *mut os::raw::c_void,
+    _db: *mut sqlite3,
+    db_name: *const os::raw::c_char,
+    pages: os::raw::c_int,
+) -> os::raw::c_int {
+    let hook = &mut *(ctx as *mut WalHookFn);
+    let db_name = CStr::from_ptr(db_name).to_str().unwrap_or_default();
+
+    // unwinding into C is undefined behavior, report an error instead
+    panic::catch_unwind(AssertUnwindSafe(|| hook(db_name, pages as u32)))
+        .unwrap_or(SqlitePrimaryResult::Error)
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{
+        prelude::*,
+        test_support::{remove_file, temp_path},
+    };
+
+    #[test]
+    fn journal_mode_is_changed() {
+        let path = temp_path("wal_mode.db");
+        let db = Database::open(&path).unwrap();
+
+        assert_eq!(
+            db.set_journal_mode(None, JournalMode::Wal).unwrap(),
+            JournalMode::Wal
+        );
+        assert_eq!(
+            db.set_journal_mode(Some(DatabaseName::Main), JournalMode::Delete)
+                .unwrap(),
+            JournalMode::Delete
+        );
+
+        // in-memory databases keep their journal in memory
+        let memory = Database::open(":memory:").unwrap();
+        assert_eq!(
+            memory.set_journal_mode(None, JournalMode::Wal).unwrap(),
+            JournalMode::Memory
+        );
+
+        db.close();
+        remove_file(&path);
+    }
+
+    #[test]
+    fn wal_is_checkpointed() {
+        let path = temp_path("wal_checkpoint.db");
+        let db = Database::open(&path).unwrap();
+
+        assert_eq!(
+            db.wal_checkpoint(Some(DatabaseName::Main), CheckpointMode::Passive)
+                .unwrap(),
+            (-1, -1)
+        );
+
+        db.set_journal_mode(None, JournalMode::Wal).unwrap();
+        db.execute_changes(String::from("CREATE TABLE t(x);"))
+            .unwrap();
+        db.execute_changes(String::from("INSERT INTO t VALUES (1);"))
+            .unwrap();
+
+        let (log_frames, checkpointed_frames) = db
+            .wal_checkpoint(Some(DatabaseName::Main), CheckpointMode::Truncate)
+            .unwrap();
+        assert!(log_frames >= 0);
+        assert_eq!(log_frames, checkpointed_frames);
+
+        assert!(db
+            .wal_checkpoint(
+                Some(DatabaseName::Attached("missing")),
+                CheckpointMode::Passive
+            )
+            .is_err());
+
+        db.close();
+        remove_file(&path);
+    }
+
+    #[test]
+    fn wal_hook_sees_commits_and_fails_them() {
+        let path = temp_path("wal_hook.db");
+        let mut db = Database::open(&path).unwrap();
+        db.set_journal_mode(None, JournalMode::Wal).unwrap();
+        let commits = Arc::new(Mutex::new(Vec::new()));
+
+        let seen = commits.clone();
+        db.wal_hook(Some(move |name: &str, pages: u32| {
+            seen.lock().unwrap().push((name.to_owned(), pages));
+            SqlitePrimaryResult::Ok
+        }));
+        db.execute_changes(String::from("CREATE TABLE t(x);"))
+            .unwrap();
+
+        let commits = commits.lock().unwrap().clone();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].0, "main");
+        assert!(commits[0].1 > 0);
+
+        db.wal_hook(Some(|_: &str, _: u32| SqlitePrimaryResult::Busy));
+        assert!(db
+            .execute_changes(String::from("INSERT INTO t VALUES (1);"))
+            .is_err());
+
+        assert_eq!(db.wal_autocheckpoint(100), SqlitePrimaryResult::Ok);
+        db.execute_changes(String::from("INSERT INTO t VALUES (2);"))
+            .unwrap();
+
+        db.close();
+        remove_file(&path);
+    }
+}
@@ -0,0 +1,45 @@
+//! The configuration of SQLite is process-wide and can only be changed while
+//! it's shut down, so it's tested in its own process.
+
+#![cfg(not(feature = "loadable-extension"))]
+
+use std::sync::{Arc, Mutex};
+
+use min_sqlite3_sys::{config, prelude::*};
+
+#[test]
+fn log_callback_receives_errors() {
+    let messages = Arc::new(Mutex::new(Vec::new()));
+
+    assert_eq!(config::shutdown(), SqlitePrimaryResult::Ok);
+    let seen = messages.clone();
+    let status = config::set_log_callback(Some(move |code: i32, message: &str| {
+        seen.lock().unwrap().push((code & 0xff, message.to_owned()));
+    }));
+    assert_eq!(status, SqlitePrimaryResult::Ok);
+    assert_eq!(config::initialize(), SqlitePrimaryResult::Ok);
+
+    // only accepted while SQLite is shut down
+    assert_eq!(
+        config::set_memory_status(false),
+        SqlitePrimaryResult::Misuse
+    );
+
+    let db = Database::open(":memory:").unwrap();
+    assert!(db
+        .execute_changes(String::from("SELECT * FROM missing;"))
+        .is_err());
+    db.close();
+
+    assert!(messages
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|(code, message)| *code == 1 && message.contains("no such table: missing")));
+
+    assert_eq!(config::shutdown(), SqlitePrimaryResult::Ok);
+    assert_eq!(
+        config::set_log_callback(None::<fn(i32, &str)>),
+        SqlitePrimaryResult::Ok
+    );
+}
@@ -4,6 +4,32 @@ use std::{
     process::{self, Command},
 };
 
+/// Compile-time options of the library. It's rebuilt when they or the
+/// SQLite version change, see `build_stamp`.
+const COMPILE_OPTIONS: &[&str] = &[
+    "-D_POSIX_THREAD_SAFE_FUNCTIONS",
+    "-DSQLITE_ENABLE_PREUPDATE_HOOK",
+    "-DSQLITE_ENABLE_SESSION",
+    "-DSQLITE_ENABLE_COLUMN_METADATA",
+    "-DSQLITE_ENABLE_FTS5",
+    "-DSQLITE_ENABLE_RTREE",
+    // JSON functions are built in since 3.38, SQLITE_OMIT_JSON must not be set.
+];
+
+/// Returns the SQLite version of `c_source` and the compile-time options,
+/// which are stored next to the installed library.
+fn build_stamp() -> String {
+    let header = fs::read_to_string("c_source/sqlite3.h")
+        .unwrap_or_else(|_| panic!("Couldn't read c_source/sqlite3.h."));
+    let version = header
+        .lines()
+        .find(|line| line.starts_with("#define SQLITE_VERSION "))
+        .and_then(|line| line.split('"').nth(1))
+        .unwrap_or_else(|| panic!("Couldn't find SQLITE_VERSION in c_source/sqlite3.h."));
+
+    format!("{}\n{}\n", version, COMPILE_OPTIONS.join("\n"))
+}
+
 fn main() {
     if env::var("DOCS_RS").is_ok() {
         println!("cargo:warning=docs.rs build detected. Process will safely exit.");
@@ -18,44 +44,44 @@ fn main() {
     let target_dir = Path::new(&home_path).join(".local/share/min_sqlite3_sys");
     let target_dylib_path = target_dir.join("lib".to_owned() + compiled_output_name + ".so");
 
-    if target_dylib_path.exists() {
+    let stamp_path = target_dir.join("lib".to_owned() + compiled_output_name + ".stamp");
+    let stamp = build_stamp();
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=c_source");
+    println!("cargo:rerun-if-changed={}", stamp_path.display());
+    println!("cargo:rerun-if-changed={}", target_dylib_path.display());
+
+    // libraries of older versions or with other options are rebuilt, since
+    // they miss the APIs that this version links against.
+    if target_dylib_path.exists() && fs::read_to_string(&stamp_path).ok() == Some(stamp.clone()) {
         println!(
             "cargo:warning=libmin_sqlite3_sys already exists on system. Process will safely exit."
         );
         process::exit(0);
     }
 
-    #[allow(clippy::clone_double_ref)]
     Command::new("cc")
         .arg("-fpic")
-        .arg("-D_POSIX_THREAD_SAFE_FUNCTIONS")
+        .args(COMPILE_OPTIONS)
         .arg("-c")
         .arg("-I")
         .arg("c_source")
         .arg("c_source/sqlite3.c")
         .arg("-o")
-        .arg(
-            output_dir
-                .clone()
-                .join(compiled_output_name.to_owned() + ".o"),
-        )
+        .arg(output_dir.join(compiled_output_name.to_owned() + ".o"))
         .output()
         .unwrap_or_else(|_| panic!("Couldn't compile c_source into object file."));
 
-    #[allow(clippy::clone_double_ref)]
-    let dylib_path = output_dir
-        .clone()
-        .join("lib".to_owned() + compiled_output_name + ".so");
-
-    #[allow(clippy::clone_double_ref)]
-    let ofile_path = output_dir
-        .clone()
-        .join(compiled_output_name.to_owned() + ".o");
+    let dylib_path = output_dir.join("lib".to_owned() + compiled_output_name + ".so");
+    let ofile_path = output_dir.join(compiled_output_name.to_owned() + ".o");
     Command::new("cc")
         .arg("-shared")
         .arg(&ofile_path)
         .arg("-o")
         .arg(&dylib_path)
+        // FTS5 uses log() for the bm25 ranking.
+        .arg("-lm")
         .output()
         .unwrap_or_else(|_| {
             panic!(
@@ -79,6 +105,16 @@ fn main() {
     fs::create_dir_all(&target_dir)
         .unwrap_or_else(|_| panic!("{} could not create.", &target_dir.display()));
 
+    // the installed library is read-only, it's replaced instead of overwritten
+    if target_dylib_path.exists() {
+        fs::remove_file(&target_dylib_path).unwrap_or_else(|_| {
+            panic!(
+                "{} could not be removed for the rebuild.",
+                target_dylib_path.display()
+            )
+        });
+    }
+
     fs::copy(&dylib_path, &target_dylib_path).unwrap_or_else(|_| {
         panic!(
             "{} could not copy into {}",
@@ -86,4 +122,7 @@ fn main() {
             target_dir.display()
         )
     });
+
+    fs::write(&stamp_path, stamp)
+        .unwrap_or_else(|_| panic!("{} could not be written.", stamp_path.display()));
 }